@@ -0,0 +1,46 @@
+//! Comparison of the generated outputs with the existing files for `Builder::check`.
+
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use similar::TextDiff;
+
+use crate::error::BuildError;
+
+/// An output file that is out of date with the input.
+#[derive(Debug)]
+pub struct StaleOutput {
+    pub path: PathBuf,
+    /// The unified diff from the existing file to the generated one.
+    pub diff: String,
+}
+
+/// Prints the unified diff.
+impl fmt::Display for StaleOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diff)
+    }
+}
+
+/// Compares the existing file at `path` with the `generated` content. A missing file is compared as
+/// an empty one.
+pub(super) fn compare(path: PathBuf, generated: &str) -> Result<Option<StaleOutput>, BuildError> {
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(source) => return Err(BuildError::Io { path, source }),
+    };
+    if existing == generated {
+        return Ok(None);
+    }
+
+    let diff = TextDiff::from_lines(existing.as_str(), generated)
+        .unified_diff()
+        .header(
+            &path.display().to_string(),
+            &format!("{} (generated)", path.display()),
+        )
+        .to_string();
+    Ok(Some(StaleOutput { path, diff }))
+}
@@ -0,0 +1,119 @@
+//! Loading of a crate from its sources on disk, for `Builder::input_crate` and
+//! `Builder::extern_crate`. The out-of-line modules are inlined so that the crate becomes one
+//! `syn::File`, like the output of `cargo expand` but without expanding the macros.
+
+use std::path::{Path, PathBuf};
+
+use syn::{Item, Lit, Meta};
+
+use super::parse_file;
+use crate::error::{BuildError, SourceFiles};
+use crate::type_resolution::ExternCrate;
+
+/// Returns the root file of the crate at `path`, which is either the root file, e.g.
+/// `lib/foo/src/lib.rs`, or the folder of the crate, in which case the root is `src/lib.rs`.
+pub(super) fn root_file(path: &Path) -> PathBuf {
+    match path.is_dir() {
+        true => path.join("src").join("lib.rs"),
+        false => path.to_owned(),
+    }
+}
+
+/// Reads the crate at `path`, see `root_file`, with all its modules. The files that it's read from
+/// are recorded in `files`.
+pub(super) fn load(path: &Path, files: &mut SourceFiles) -> Result<syn::File, BuildError> {
+    let root = root_file(path);
+    let mut ast = parse_file(&root)?;
+    files.push(&root, &ast);
+    let dir = root.parent().unwrap_or_else(|| Path::new("")).to_owned();
+    inline_modules(&mut ast.items, &dir, files)?;
+    Ok(ast)
+}
+
+/// Reads the external crate `name` at `path`.
+pub(super) fn load_extern(
+    name: &str,
+    path: &Path,
+    files: &mut SourceFiles,
+) -> Result<ExternCrate, BuildError> {
+    let name = syn::parse_str(name).map_err(|_| {
+        BuildError::Config(format!(
+            "Invalid name `{}` for the external crate at {}",
+            name,
+            path.display()
+        ))
+    })?;
+    let ast = load(path, files)?;
+    Ok(ExternCrate { name, ast })
+}
+
+/// Replaces the out-of-line modules in `items`, e.g. `mod foo;`, with the content of their files.
+/// `dir` is the folder where the files of the modules are.
+fn inline_modules(
+    items: &mut Vec<Item>,
+    dir: &Path,
+    files: &mut SourceFiles,
+) -> Result<(), BuildError> {
+    // The tests are not part of the crate that the IDL sees.
+    items.retain(|item| match item {
+        Item::Mod(md) => !md.attrs.iter().any(|attr| {
+            attr.path.is_ident("cfg")
+                && matches!(attr.parse_args::<syn::Path>(), Ok(path) if path.is_ident("test"))
+        }),
+        _ => true,
+    });
+
+    for item in items {
+        let md = match item {
+            Item::Mod(md) => md,
+            _ => continue,
+        };
+        match &mut md.content {
+            // An inline module keeps its files in a folder of its name.
+            Some((_, items)) => inline_modules(items, &dir.join(md.ident.to_string()), files)?,
+            None => {
+                let (path, child_dir) = module_file(md, dir)?;
+                let ast = parse_file(&path)?;
+                files.push(&path, &ast);
+                let mut items = ast.items;
+                inline_modules(&mut items, &child_dir, files)?;
+                md.content = Some((Default::default(), items));
+                md.semi = None;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the file of the out-of-line module `md` declared in `dir` and the folder where the files
+/// of its own modules are.
+fn module_file(md: &syn::ItemMod, dir: &Path) -> Result<(PathBuf, PathBuf), BuildError> {
+    // `#[path = "..."]`. The file is treated like a `mod.rs`, so its modules are next to it.
+    let path_attr = md.attrs.iter().find_map(|attr| match attr.parse_meta() {
+        Ok(Meta::NameValue(meta)) if meta.path.is_ident("path") => match meta.lit {
+            Lit::Str(path) => Some(path.value()),
+            _ => None,
+        },
+        _ => None,
+    });
+    if let Some(path) = path_attr {
+        let path = dir.join(path);
+        let child_dir = path.parent().unwrap_or(dir).to_owned();
+        return Ok((path, child_dir));
+    }
+
+    // `foo.rs` or `foo/mod.rs`.
+    let name = md.ident.to_string();
+    let file = dir.join(format!("{}.rs", name));
+    let mod_file = dir.join(&name).join("mod.rs");
+    match (file.is_file(), mod_file.is_file()) {
+        (true, _) => Ok((file, dir.join(&name))),
+        (false, true) => Ok((mod_file, dir.join(&name))),
+        (false, false) => Err(BuildError::Config(format!(
+            "Unable to find the file of module `{}`: neither {} nor {} exists",
+            name,
+            file.display(),
+            mod_file.display()
+        ))),
+    }
+}
@@ -0,0 +1,591 @@
+mod check;
+mod crate_source;
+#[cfg(test)]
+mod tests;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use log::{info, warn};
+use quote::{format_ident, quote};
+use syn::{parse_quote, Item, Meta, NestedMeta};
+
+use crate::config::Config;
+use crate::domain_create::DomainCreateBuilder;
+use crate::domain_entrypoint::{DomainEntrypointFactory, EntrypointCrate};
+use crate::error::{BuildError, Diagnostics, Error, SourceFiles};
+use crate::has_attribute;
+use crate::type_resolution::symbol_tree::SymbolTree;
+use crate::type_resolution::ExternCrate;
+
+pub use check::StaleOutput;
+
+/// Where the interface comes from.
+enum Input {
+    Path(PathBuf),
+    /// The root of the interface crate, whose modules are read from their files.
+    Crate(PathBuf),
+    Ast(syn::File),
+}
+
+/// Runs ngc on an interface file.
+///
+/// ```ignore
+/// let generated = ngc::Builder::new()
+///     .input_path("interface.rs")
+///     .output("interface_generated.rs")
+///     .domain_create_output("domain_create.rs")
+///     .generate()?;
+/// ```
+pub struct Builder {
+    input: Option<Input>,
+    output: Option<PathBuf>,
+    domain_create_output: Option<PathBuf>,
+    domains_folder: Option<PathBuf>,
+    config: Config,
+    config_path: Option<PathBuf>,
+    extern_crates: Vec<(String, PathBuf)>,
+    typeid: bool,
+    proxy: bool,
+    domain_create: bool,
+    rustfmt: bool,
+}
+
+/// The outputs of ngc.
+pub struct Generated {
+    /// The interface with the generated typeid and proxies.
+    pub interface: syn::File,
+    /// The domain creates to be put into the kernel. `None` if domain create generation is
+    /// disabled.
+    pub domain_create: Option<syn::File>,
+    /// The domain entrypoint crates. Empty if no domains folder is given.
+    pub entrypoints: Vec<EntrypointCrate>,
+}
+
+impl Generated {
+    /// Returns the unformatted source of the interface.
+    pub fn interface_source(&self) -> String {
+        let interface = &self.interface;
+        quote!(#interface).to_string()
+    }
+
+    /// Returns the unformatted source of the domain creates.
+    pub fn domain_create_source(&self) -> Option<String> {
+        self.domain_create
+            .as_ref()
+            .map(|domain_create| quote!(#domain_create).to_string())
+    }
+}
+
+impl Builder {
+    /// Creates a builder that runs all the generators and formats the outputs.
+    pub fn new() -> Self {
+        Self {
+            input: None,
+            output: None,
+            domain_create_output: None,
+            domains_folder: None,
+            config: Config::default(),
+            config_path: None,
+            extern_crates: vec![],
+            typeid: true,
+            proxy: true,
+            domain_create: true,
+            rustfmt: true,
+        }
+    }
+
+    /// Reads the interface from `path`, which is usually the output of `cargo expand`.
+    pub fn input_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.input = Some(Input::Path(path.as_ref().to_owned()));
+        self
+    }
+
+    /// Reads the interface crate whose root is at `path`, e.g. `interface/src/lib.rs`, or the crate
+    /// folder. The `mod foo;` declarations are followed to `foo.rs` or `foo/mod.rs`, so the crate
+    /// doesn't need to be built and expanded first. Macros are not expanded.
+    pub fn input_crate<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.input = Some(Input::Crate(path.as_ref().to_owned()));
+        self
+    }
+
+    /// Uses `ast` as the interface.
+    pub fn input_ast(mut self, ast: syn::File) -> Self {
+        self.input = Some(Input::Ast(ast));
+        self
+    }
+
+    /// Writes the generated interface to `path`.
+    pub fn output<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.output = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Writes the generated domain creates to `path`.
+    pub fn domain_create_output<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.domain_create_output = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Generates the domain entrypoint crates into `[path]/generated`. `path` is the folder where
+    /// the domain crates are, usually `domains`.
+    pub fn domains_folder<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.domains_folder = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Uses `config` for the paths, attribute names and templates in the generated code.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Reads the config from the `redidl.toml` at `path` when generating. Takes precedence over
+    /// `config`.
+    pub fn config_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config_path = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Resolves the symbols that the interface imports from the crate `name` to their definitions
+    /// in the source at `path`, e.g. a vendored or path dependency, instead of treating them as
+    /// opaque. `path` is either the crate root, e.g. `lib/foo/src/lib.rs`, or the crate folder.
+    pub fn extern_crate<P: AsRef<Path>>(mut self, name: &str, path: P) -> Self {
+        self.extern_crates
+            .push((name.to_owned(), path.as_ref().to_owned()));
+        self
+    }
+
+    /// Whether to generate the `typeid` module. Enabled by default.
+    pub fn typeid(mut self, enable: bool) -> Self {
+        self.typeid = enable;
+        self
+    }
+
+    /// Whether to generate the proxies and trampolines. Enabled by default.
+    pub fn proxy(mut self, enable: bool) -> Self {
+        self.proxy = enable;
+        self
+    }
+
+    /// Whether to generate the domain creates. Enabled by default.
+    /// The domain create traits are processed either way since the proxy needs them.
+    pub fn domain_create(mut self, enable: bool) -> Self {
+        self.domain_create = enable;
+        self
+    }
+
+    /// Whether to run `rustfmt` on the written outputs. Enabled by default.
+    pub fn rustfmt(mut self, enable: bool) -> Self {
+        self.rustfmt = enable;
+        self
+    }
+
+    /// Runs the generators, writes the outputs, if any, and returns the generated code.
+    pub fn generate(mut self) -> Result<Generated, BuildError> {
+        let generated = self.run()?;
+
+        // Write generated proxy and domain create.
+        for (path, source) in self.formatted_outputs(&generated, false)? {
+            info!("Writing output to {}", path.display());
+            std::fs::write(&path, source).map_err(|source| BuildError::Io { path, source })?;
+        }
+
+        // Write generated entrypoints.
+        if let Some(domains_folder) = &self.domains_folder {
+            DomainEntrypointFactory::write_entrypoint_crates(
+                &DomainEntrypointFactory::output_folder(domains_folder),
+                &generated.entrypoints,
+            )?;
+        }
+
+        Ok(generated)
+    }
+
+    /// Runs the generators in memory and compares the outputs, formatted the same way as
+    /// `generate` writes them, with the existing files. Nothing is written.
+    /// Returns the outputs that are out of date.
+    pub fn check(mut self) -> Result<Vec<StaleOutput>, BuildError> {
+        let generated = self.run()?;
+
+        let mut outputs = self.formatted_outputs(&generated, true)?;
+        if let Some(domains_folder) = &self.domains_folder {
+            let output_folder = DomainEntrypointFactory::output_folder(domains_folder);
+            for entrypoint in &generated.entrypoints {
+                for (path, content) in entrypoint.files() {
+                    outputs.push((output_folder.join(path), content.to_owned()));
+                }
+            }
+        }
+
+        let mut stale = vec![];
+        for (path, content) in outputs {
+            stale.extend(check::compare(path, &content)?);
+        }
+        Ok(stale)
+    }
+
+    /// Runs the generators.
+    fn run(&mut self) -> Result<Generated, BuildError> {
+        let config = match &self.config_path {
+            Some(path) => Config::load(path)?,
+            None => self.config.clone(),
+        };
+
+        // The files that the errors may be in if the input is read from several files.
+        let mut files = SourceFiles::new();
+        let (input_path, mut ast) = match self.input.take() {
+            Some(Input::Path(path)) => {
+                info!("Running redIDL on {}", path.display());
+                let ast = parse_file(&path)?;
+                (Some(path), ast)
+            }
+            Some(Input::Crate(path)) => {
+                info!("Running redIDL on the crate at {}", path.display());
+                let ast = crate_source::load(&path, &mut files)?;
+                (Some(crate_source::root_file(&path)), ast)
+            }
+            Some(Input::Ast(ast)) => (None, ast),
+            None => return Err(BuildError::Config(String::from("No input is given"))),
+        };
+
+        // Clean the file
+        remove_prelude_and_placeholder(&mut ast);
+
+        let extern_crates = self
+            .extern_crates
+            .iter()
+            .map(|(name, path)| crate_source::load_extern(name, path, &mut files))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let domain_create_builder = match &self.domains_folder {
+            Some(domains_folder) => {
+                // Check the path is valid
+                if !domains_folder.is_dir() {
+                    return Err(BuildError::Config(format!(
+                        "Provided domains folder {} does not exist or is not a directory",
+                        domains_folder.display()
+                    )));
+                }
+
+                let domain_entrypoint_factory =
+                    DomainEntrypointFactory::new(domains_folder.to_owned(), config.clone());
+                DomainCreateBuilder::new_with_domains_folder(
+                    domain_entrypoint_factory,
+                    config.clone(),
+                )
+            }
+            None => DomainCreateBuilder::new(config.clone()),
+        };
+
+        // Generate code.
+        let (generated_domain_create, entrypoints) = generate(
+            domain_create_builder,
+            &mut ast,
+            &extern_crates,
+            &config,
+            self.typeid,
+            self.proxy,
+        )
+        .map_err(|mut errors| {
+            errors.locate(&files);
+            BuildError::Input {
+                path: input_path,
+                errors,
+            }
+        })?;
+        Ok(Generated {
+            interface: ast,
+            domain_create: if self.domain_create {
+                Some(parse_quote! {
+                    #(#generated_domain_create)*
+                })
+            } else {
+                None
+            },
+            entrypoints,
+        })
+    }
+
+    /// Returns the paths of the interface and domain create outputs, if any, and their content as
+    /// it's written. An output that `rustfmt` fails to format is written as it is, but `check`
+    /// fails instead, since the existing files are formatted and would all differ.
+    fn formatted_outputs(
+        &self,
+        generated: &Generated,
+        check: bool,
+    ) -> Result<Vec<(PathBuf, String)>, BuildError> {
+        let mut outputs = vec![];
+        if let Some(output) = &self.output {
+            outputs.push((output.clone(), generated.interface_source()));
+        }
+        if let (Some(domain_create_out), Some(domain_create)) =
+            (&self.domain_create_output, generated.domain_create_source())
+        {
+            outputs.push((domain_create_out.clone(), domain_create));
+        }
+
+        if self.rustfmt {
+            for (path, source) in &mut outputs {
+                match format_source(source, path) {
+                    Ok(formatted) => *source = formatted,
+                    Err(err) if check => {
+                        return Err(BuildError::Config(format!(
+                            "Unable to format {} with rustfmt to compare it with the existing file: {}",
+                            path.display(),
+                            err
+                        )))
+                    }
+                    Err(err) => warn!(
+                        "Failed to run formatter on output file {}. Formatting is skipped. Error {}",
+                        path.display(),
+                        err
+                    ),
+                }
+            }
+        }
+        Ok(outputs)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads and parses the interface file at `path`.
+fn parse_file(path: &Path) -> Result<syn::File, BuildError> {
+    let content = std::fs::read_to_string(path).map_err(|source| BuildError::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    let input_error = |err: Error| BuildError::Input {
+        path: Some(path.to_owned()),
+        errors: err.into(),
+    };
+
+    // Tokenizing errors, e.g. unbalanced delimiters, don't come with a location.
+    if content.parse::<proc_macro2::TokenStream>().is_err() {
+        return Err(input_error(Error::new(
+            "Failed to tokenize the input; check for unbalanced delimiters",
+        )));
+    }
+    syn::parse_file(&content).map_err(|err| input_error(err.into()))
+}
+
+// Generate proxy and other stuff from `items` in place.
+// Returns domain create and entrypoint generation.
+fn generate(
+    mut builder: DomainCreateBuilder,
+    ast: &mut syn::File,
+    extern_crates: &[ExternCrate],
+    config: &Config,
+    typeid: bool,
+    proxy: bool,
+) -> Result<(Vec<syn::Item>, Vec<EntrypointCrate>), Diagnostics> {
+    // Generate type id
+    let symbol_tree = if typeid {
+        crate::type_resolution::generate_typeid(ast, extern_crates)?
+    } else {
+        crate::type_resolution::resolve_types(ast, extern_crates)?
+    };
+
+    // Check the types that cross domains before generating the code that passes them.
+    crate::validation::validate_interfaces(ast, &symbol_tree, config)?;
+
+    // Generate proxy and domain creations.
+    let mut errors = Diagnostics::new();
+    let mut module_path = vec![format_ident!("interface")];
+    let mut generated_domain_create_items = generate_recurse(
+        &mut ast.items,
+        &mut builder,
+        &mut module_path,
+        &symbol_tree,
+        config,
+        proxy,
+        &mut errors,
+    );
+
+    // Generate create_init and add it to generated domain creates.
+    generated_domain_create_items.push(builder.generate_create_init());
+    let entrypoints = builder.take_entrypoints();
+
+    if proxy {
+        // Finds the Generates the proxy struct inplace
+        let proxy_mod = ast.items.iter_mut().find_map(|item| match item {
+            Item::Mod(md) => {
+                if md.ident == "proxy" {
+                    Some(md)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        });
+        match proxy_mod.and_then(|proxy_mod| proxy_mod.content.as_mut()) {
+            Some((_, items)) => {
+                if let Some(generated) = errors.check_all(crate::proxy::generate_proxy(
+                    builder.take(),
+                    &symbol_tree,
+                    config,
+                )) {
+                    items.extend(generated);
+                }
+            }
+            None => errors.push(Error::new(
+                "Expecting an inline `proxy` module to generate the `ProxyObject` into",
+            )),
+        }
+    }
+
+    // Return the generated domain creates and entrypoints.
+    errors.finish((generated_domain_create_items, entrypoints))
+}
+
+// Generate proxy and other stuff from `items` in place, recursively.
+// Returns domain create generation.
+fn generate_recurse(
+    items: &mut Vec<syn::Item>,
+    domain_create_builder: &mut DomainCreateBuilder,
+    module_path: &mut Vec<syn::Ident>,
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    proxy: bool,
+    errors: &mut Diagnostics,
+) -> Vec<syn::Item> {
+    let mut generated_items = Vec::<syn::Item>::new();
+    let mut generated_domain_create_items = Vec::<syn::Item>::new();
+
+    for item in items.iter_mut() {
+        match item {
+            Item::Mod(md) => {
+                if let Some((_, items)) = &mut md.content {
+                    // Recursive into the submodule.
+                    module_path.push(md.ident.clone());
+                    generated_domain_create_items.extend(generate_recurse(
+                        items,
+                        domain_create_builder,
+                        module_path,
+                        symbol_tree,
+                        config,
+                        proxy,
+                        errors,
+                    ));
+                    module_path.pop();
+                }
+            }
+            Item::Trait(tr) => {
+                // Attempt to generate proxy
+                if proxy {
+                    if let Some(Some(generated)) =
+                        errors.check_all(crate::proxy::generate_interface_proxy(
+                            tr,
+                            module_path,
+                            symbol_tree,
+                            config,
+                        ))
+                    {
+                        generated_items.extend(generated);
+                    }
+                }
+
+                // Attempt to generate domain creation
+                if let Some(Some(generated)) =
+                    errors.check(domain_create_builder.generate_domain_create(tr, module_path))
+                {
+                    generated_domain_create_items.extend(generated);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Insert the generated proxy inplace.
+    items.extend(generated_items);
+
+    // Return the generated domain create.
+    generated_domain_create_items
+}
+
+/// Remove unwanted stuff generated by cargo-expand
+fn remove_prelude_and_placeholder(ast: &mut syn::File) {
+    // Remove `#![feature(prelude_import)]`
+    ast.attrs.retain(|attr| {
+        if let Ok(Meta::List(meta)) = attr.parse_meta() {
+            if !meta.path.is_ident("feature") {
+                return true;
+            }
+
+            for meta in meta.nested {
+                if let NestedMeta::Meta(meta) = meta {
+                    if meta.path().is_ident("prelude_import") {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    });
+
+    ast.items.retain(|item| {
+        // Remove ```
+        // #[prelude_import]
+        // use core::prelude::v1::*;
+        // ```
+        const PRELUDE_IMPORT_ATTR: &str = "prelude_import";
+        if let Item::Use(item) = item {
+            if has_attribute!(item, PRELUDE_IMPORT_ATTR) {
+                return false;
+            }
+        }
+
+        // Remove
+        // ```
+        // #[macro_use]
+        // extern crate compiler_builtins;
+        // #[macro_use]
+        // extern crate core;
+        // #[macro_use]
+        // extern crate interface_attribute_placeholder;
+        // ```
+        if let Item::ExternCrate(item) = item {
+            let ident = item.ident.to_string();
+            if ident == "compiler_builtins"
+                || ident == "core"
+                || ident == "interface_attribute_placeholder"
+            {
+                return false;
+            }
+        }
+
+        true
+    });
+}
+
+/// Formats `source` with `rustfmt` as if it's the file at `output_path`, so `rustfmt` picks up
+/// the same `rustfmt.toml`. Fails if `rustfmt` is not available or emits nothing.
+fn format_source(source: &str, output_path: &Path) -> std::io::Result<String> {
+    let mut command = Command::new("rustfmt");
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if let Some(dir) = output_path.parent().filter(|dir| dir.is_dir()) {
+        command.current_dir(dir);
+    }
+
+    let mut child = command.spawn()?;
+    child.stdin.take().unwrap().write_all(source.as_bytes())?;
+    let output = child.wait_with_output()?;
+    // `rustfmt` still emits the output if it fails to format some of the lines.
+    if output.stdout.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "rustfmt emitted nothing",
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into())
+}
@@ -0,0 +1,278 @@
+use super::Builder;
+use crate::config::Config;
+use crate::error::BuildError;
+use syn::parse_quote;
+
+#[test]
+fn test_generate_from_ast() {
+    let ast: syn::File = parse_quote! {
+        pub mod proxy {
+            pub trait Proxy {}
+        }
+        pub mod foo {
+            #[interface]
+            pub trait Foo {
+                fn bar(&self, x: usize) -> usize;
+            }
+        }
+    };
+    let generated = Builder::new()
+        .input_ast(ast)
+        .domain_create(false)
+        .generate()
+        .unwrap();
+
+    let interface = generated.interface_source();
+    assert!(interface.contains("pub struct FooProxy"));
+    assert!(interface.contains("pub struct ProxyObject"));
+    assert!(interface.contains("pub mod typeid"));
+    assert!(generated.domain_create.is_none());
+}
+
+#[test]
+fn test_skip_generators() {
+    let ast: syn::File = parse_quote! {
+        pub mod foo {
+            #[interface]
+            pub trait Foo {
+                fn bar(&self, x: usize) -> usize;
+            }
+        }
+    };
+    let generated = Builder::new()
+        .input_ast(ast)
+        .typeid(false)
+        .proxy(false)
+        .generate()
+        .unwrap();
+
+    let interface = generated.interface_source();
+    assert!(!interface.contains("FooProxy"));
+    assert!(!interface.contains("typeid"));
+    assert!(generated.domain_create.is_some());
+}
+
+#[test]
+fn test_generate_with_config() {
+    let ast: syn::File = parse_quote! {
+        pub mod proxy {
+            pub trait Proxy {}
+        }
+        pub mod foo {
+            #[rpc_interface]
+            pub trait Foo {
+                fn bar(&self, x: usize) -> usize;
+            }
+        }
+    };
+    let config = Config::from_toml(
+        r#"
+        [crates]
+        unwind = "::my_unwind"
+
+        [attributes]
+        interface = "rpc_interface"
+        "#,
+    )
+    .unwrap();
+    let generated = Builder::new()
+        .input_ast(ast)
+        .config(config)
+        .domain_create(false)
+        .generate()
+        .unwrap();
+
+    let interface = generated.interface_source();
+    assert!(interface.contains("pub struct FooProxy"));
+    assert!(interface.contains(":: my_unwind :: trampoline !"));
+    assert!(!interface.contains("rpc_interface"));
+}
+
+#[test]
+fn test_report_input_errors() {
+    let ast: syn::File = parse_quote! {
+        pub mod foo {
+            #[interface]
+            pub trait Foo {
+                fn bar(&self, x: crate::nope::X) -> usize;
+            }
+        }
+    };
+    match Builder::new().input_ast(ast).generate() {
+        Err(BuildError::Input { path: None, errors }) => assert_eq!(errors.iter().count(), 1),
+        _ => panic!("Expecting an input error"),
+    }
+}
+
+#[test]
+fn test_check_outputs() {
+    let ast: syn::File = parse_quote! {
+        pub mod proxy {
+            pub trait Proxy {}
+        }
+        pub mod foo {
+            #[interface]
+            pub trait Foo {
+                fn bar(&self, x: usize) -> usize;
+            }
+        }
+    };
+    let dir = std::env::temp_dir().join(format!("ngc_test_check_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("interface.rs");
+    let domain_create_output = dir.join("domain_create.rs");
+    let builder = || {
+        Builder::new()
+            .input_ast(ast.clone())
+            .output(&output)
+            .domain_create_output(&domain_create_output)
+            .rustfmt(false)
+    };
+
+    // Everything is stale before the outputs are generated.
+    assert_eq!(builder().check().unwrap().len(), 2);
+
+    builder().generate().unwrap();
+    assert!(builder().check().unwrap().is_empty());
+
+    // Edits are reported as a diff.
+    let mut edited = std::fs::read_to_string(&output).unwrap();
+    edited.push_str("\nfn edited() {}\n");
+    std::fs::write(&output, &edited).unwrap();
+    let stale = builder().check().unwrap();
+    assert_eq!(stale.len(), 1);
+    assert_eq!(stale[0].path, output);
+    assert!(stale[0].diff.contains("-fn edited() {}"));
+
+    // Checking doesn't write anything.
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), edited);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_extern_crate_sources() {
+    let dir = std::env::temp_dir().join(format!("ngc_test_extern_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src").join("block")).unwrap();
+    std::fs::write(
+        dir.join("src").join("lib.rs"),
+        "pub mod block;\npub use block::Block;\n#[cfg(test)]\nmod tests;\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src").join("block.rs"),
+        "pub mod data;\npub use data::Block;\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src").join("block").join("data.rs"),
+        "pub struct Block;\n",
+    )
+    .unwrap();
+
+    let ast: syn::File = parse_quote! {
+        pub mod rref {
+            pub mod rref {
+                pub struct RRef<T> {
+                    value_pointer: *mut T,
+                }
+            }
+        }
+        pub mod foo {
+            #[interface]
+            pub trait Foo {
+                fn bar(&self, x: crate::rref::rref::RRef<common::Block>) -> ();
+            }
+        }
+    };
+    let generated = Builder::new()
+        .input_ast(ast)
+        .extern_crate("common", &dir)
+        .proxy(false)
+        .domain_create(false)
+        .generate()
+        .unwrap();
+
+    // The block is resolved to its definition in the nested module.
+    let interface = generated.interface_source();
+    assert!(interface.contains("impl TypeIdentifiable for common :: block :: data :: Block"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_input_crate() {
+    let dir = std::env::temp_dir().join(format!("ngc_test_crate_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src").join("rref")).unwrap();
+    std::fs::write(
+        dir.join("src").join("lib.rs"),
+        "#![no_std]\npub mod proxy;\npub mod rref;\npub mod block;\n#[cfg(test)]\nmod tests;\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("src").join("proxy.rs"), "pub trait Proxy {}\n").unwrap();
+    std::fs::write(
+        dir.join("src").join("rref").join("mod.rs"),
+        "pub mod rref;\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src").join("rref").join("rref.rs"),
+        "pub struct RRef<T> {\n    value_pointer: *mut T,\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src").join("block.rs"),
+        "use crate::rref::rref::RRef;\n\
+         pub struct Block {\n    pub data: [u8; 512],\n}\n\
+         #[interface]\n\
+         pub trait BDev {\n    fn read(&self, block: RRef<Block>) -> RRef<Block>;\n}\n",
+    )
+    .unwrap();
+
+    let generated = Builder::new()
+        .input_crate(&dir)
+        .domain_create(false)
+        .generate()
+        .unwrap();
+
+    // The modules are inlined into one file.
+    let interface = generated.interface_source();
+    assert!(interface.contains("pub mod block {"));
+    assert!(interface.contains("pub struct BDevProxy"));
+    assert!(interface.contains("impl TypeIdentifiable for crate :: block :: Block"));
+    assert!(!interface.contains("mod tests"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_report_errors_in_crate_modules() {
+    let dir = std::env::temp_dir().join(format!("ngc_test_crate_errors_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(
+        dir.join("src").join("lib.rs"),
+        "pub mod block;\n\npub struct Foo;\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src").join("block.rs"),
+        "#[interface]\n\
+         pub trait BDev {\n    fn read(&self, block: crate::nope::Block);\n}\n",
+    )
+    .unwrap();
+
+    let err = match Builder::new().input_crate(&dir).generate() {
+        Err(err) => err,
+        Ok(_) => panic!("Expecting an input error"),
+    };
+    // The error is in `block.rs` rather than the crate root.
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "error: {}:3:34: Unable to resolve `crate::nope::Block`: `nope` is not found in `crate`\n",
+            dir.join("src").join("block.rs").display()
+        )
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,206 @@
+//! Configuration of ngc, read from `redidl.toml`.
+//!
+//! Everything is optional and defaults to the layout of the RedLeaf tree, so forks with a different
+//! layout only need to override what they moved. For example,
+//!
+//! ```toml
+//! [kernel]
+//! load_domain = "crate::loader::load_domain"
+//!
+//! [crates]
+//! console = "::log_console"
+//!
+//! [attributes]
+//! interface = "rpc_interface"
+//! ```
+
+#[cfg(test)]
+mod tests;
+
+use std::path::Path;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use syn::{parse_quote, Ident};
+
+use crate::error::BuildError;
+
+/// The file that ngc reads the configuration from if no other is given.
+pub const DEFAULT_CONFIG_FILE: &str = "redidl.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub kernel: KernelConfig,
+    pub crates: CratesConfig,
+    pub attributes: AttributesConfig,
+    pub entrypoint: EntrypointConfig,
+}
+
+/// Paths to the kernel APIs used by the generated domain creates, which live in the kernel.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KernelConfig {
+    #[serde(deserialize_with = "parse")]
+    pub pdomain: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub pheap: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub mmap: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub interrupt: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub load_domain: syn::Path,
+    /// `fn(old_domain_id: u64, name: &str, binary_range: (*const u8, *const u8))`, which loads a
+    /// recreated domain in the binary range and the identity slot of the old one and returns what
+    /// `load_domain` returns.
+    #[serde(deserialize_with = "parse")]
+    pub reload_domain: syn::Path,
+    /// `fn(domain_id: u64, drop_map: &interface::typeid::DropMap)`, which drops everything that the
+    /// dead domain owns on the shared heap with the drop glue in the `DropMap`.
+    #[serde(deserialize_with = "parse")]
+    pub reclaim_domain_heap: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub enable_irq: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub disable_irq: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub get_current_thread: syn::Path,
+}
+
+/// Paths to the crates that the generated code depends on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CratesConfig {
+    /// The interface crate as seen from the kernel and the domain entrypoints.
+    #[serde(deserialize_with = "parse")]
+    pub interface: Ident,
+    #[serde(deserialize_with = "parse")]
+    pub syscalls: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub libsyscalls: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub unwind: syn::Path,
+    #[serde(deserialize_with = "parse")]
+    pub console: syn::Path,
+    /// Provides `get_ns_time() -> u64` for the proxies to time the calls of the methods with
+    /// `timeout_ms`. It's only used with the `proxy-log-error` feature of the interface crate.
+    #[serde(deserialize_with = "parse")]
+    pub time: syn::Path,
+}
+
+/// Names of the attributes that mark the traits and the methods in the input.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttributesConfig {
+    pub interface: String,
+    pub domain_create: String,
+    pub domain_create_blob: String,
+    pub domain_create_components: String,
+    /// The per-method RPC options of the interfaces, e.g. `#[rpc(no_trampoline)]`.
+    pub rpc: String,
+}
+
+/// Generation of the domain entrypoint crates.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EntrypointConfig {
+    /// The `Cargo.toml` of the entrypoint crates. `{name}` is replaced by the name of the domain
+    /// and `{domain_path}` by the path to the domain crate relative to the domains folder.
+    pub cargo_template: String,
+}
+
+impl Default for KernelConfig {
+    fn default() -> Self {
+        Self {
+            pdomain: parse_quote!(crate::syscalls::PDomain),
+            pheap: parse_quote!(crate::heap::PHeap),
+            mmap: parse_quote!(crate::syscalls::Mmap),
+            interrupt: parse_quote!(crate::syscalls::Interrupt),
+            load_domain: parse_quote!(crate::domain::load_domain),
+            reload_domain: parse_quote!(crate::domain::reload_domain),
+            reclaim_domain_heap: parse_quote!(crate::heap::reclaim_domain_heap),
+            enable_irq: parse_quote!(crate::interrupt::enable_irq),
+            disable_irq: parse_quote!(crate::interrupt::disable_irq),
+            get_current_thread: parse_quote!(crate::thread::get_current_ref),
+        }
+    }
+}
+
+impl Default for CratesConfig {
+    fn default() -> Self {
+        Self {
+            interface: parse_quote!(interface),
+            syscalls: parse_quote!(::syscalls),
+            libsyscalls: parse_quote!(::libsyscalls),
+            unwind: parse_quote!(::unwind),
+            console: parse_quote!(::console),
+            time: parse_quote!(::libtime),
+        }
+    }
+}
+
+impl Default for AttributesConfig {
+    fn default() -> Self {
+        Self {
+            interface: String::from("interface"),
+            domain_create: String::from("domain_create"),
+            domain_create_blob: String::from("domain_create_blob"),
+            domain_create_components: String::from("domain_create_components"),
+            rpc: String::from("rpc"),
+        }
+    }
+}
+
+impl Default for EntrypointConfig {
+    fn default() -> Self {
+        Self {
+            cargo_template: String::from(
+                r#"[package]
+name = "{name}_entry_point"
+version = "0.1.0"
+authors = ["Redleaf team <aburtsev@uci.edu>"]
+edition = "2018"
+
+# See more keys and their definitions at https://doc.rust-lang.org/cargo/reference/manifest.html
+
+[[bin]]
+name = "{name}"
+path = "src/main.rs"
+
+[dependencies]
+interface = { path = "../../../interface/generated" }
+libsyscalls = { path = "../../../lib/core/libsyscalls" }
+syscalls = { path = "../../../lib/core/interfaces/syscalls" }
+console = { path = "../../../lib/core/console" }
+
+{name} = { path = "../../{domain_path}" }
+"#,
+            ),
+        }
+    }
+}
+
+impl Config {
+    /// Parses the configuration from the content of a `redidl.toml`.
+    pub fn from_toml(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// Reads the configuration from the `redidl.toml` at `path`.
+    pub fn load(path: &Path) -> Result<Self, BuildError> {
+        let content = std::fs::read_to_string(path).map_err(|source| BuildError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::from_toml(&content).map_err(|err| {
+            BuildError::Config(format!("Invalid config {}: {}", path.display(), err))
+        })
+    }
+}
+
+/// Parses a path or an identifier in the config.
+fn parse<'de, D: Deserializer<'de>, T: syn::parse::Parse>(deserializer: D) -> Result<T, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    syn::parse_str(&s).map_err(|err| D::Error::custom(format!("invalid path `{}`: {}", s, err)))
+}
@@ -0,0 +1,53 @@
+use super::Config;
+use quote::quote;
+
+#[test]
+fn test_partial_override() {
+    let config = Config::from_toml(
+        r#"
+        [kernel]
+        load_domain = "crate::loader::load_domain"
+
+        [attributes]
+        interface = "rpc_interface"
+        "#,
+    )
+    .unwrap();
+
+    let load_domain = &config.kernel.load_domain;
+    assert_eq!(
+        quote!(#load_domain).to_string(),
+        quote!(crate::loader::load_domain).to_string()
+    );
+    assert_eq!(config.attributes.interface, "rpc_interface");
+
+    // Everything else keeps the default.
+    let pdomain = &config.kernel.pdomain;
+    assert_eq!(
+        quote!(#pdomain).to_string(),
+        quote!(crate::syscalls::PDomain).to_string()
+    );
+    assert_eq!(config.attributes.domain_create, "domain_create");
+    assert_eq!(config.crates.interface, "interface");
+}
+
+#[test]
+fn test_invalid_config() {
+    // Not a path.
+    assert!(Config::from_toml(
+        r#"
+        [crates]
+        console = "not a path"
+        "#
+    )
+    .is_err());
+
+    // Unknown key.
+    assert!(Config::from_toml(
+        r#"
+        [kernel]
+        load_domains = "crate::domain::load_domain"
+        "#
+    )
+    .is_err());
+}
@@ -2,12 +2,16 @@ use quote::format_ident;
 
 use syn::{parse_quote, FnArg, TraitItemMethod};
 
+use crate::config::Config;
+use crate::error::Result;
+
 /// This generates a public fn and a impl method.
 /// This public fn is exposed to the kernel while the impl method is exposed to the users.
 pub fn generate_domain_create_for_trait_method(
     domain_path: &str,
     method: &TraitItemMethod,
-) -> (syn::ImplItemMethod, syn::ItemFn) {
+    config: &Config,
+) -> Result<(syn::ImplItemMethod, syn::ItemFn)> {
     // Remove `self` from the argument list
     let selfless_args: Vec<_> = method
         .sig
@@ -33,39 +37,27 @@ pub fn generate_domain_create_for_trait_method(
         format_ident!("_binary_domains_build_{}_start", canonicalized_domain_path);
     let domain_end_ident = format_ident!("_binary_domains_build_{}_end", canonicalized_domain_path);
     let rtn = &method.sig.output;
-    let ep_rtn = match &method.sig.output {
-        syn::ReturnType::Type(_, ty) => match ty {
-            box syn::Type::Tuple(tuple) => {
-                assert_eq!(
-                    tuple.elems.iter().count(),
-                    2,
-                    "Expecting a tuple of two in the return type of method {:?} of domain {:?}",
-                    method_ident,
-                    domain_path
-                );
-                tuple.elems.iter().nth(1).unwrap()
-            }
-            _ => panic!(
-                "Expecting a tuple of two in the return type of method {:?} of domain {:?}",
-                method_ident, domain_path
-            ),
-        },
-        syn::ReturnType::Default => panic!(
-            "Method {:?} of domain {:?} does not have a return type. Expecting a tuple of two.",
-            method_ident, domain_path
-        ),
-    };
+    let ep_rtn = super::entry_point_return_type(domain_path, method)?;
+
+    // Paths to the kernel APIs.
+    let disable_irq = &config.kernel.disable_irq;
+    let enable_irq = &config.kernel.enable_irq;
+    let load_domain = &config.kernel.load_domain;
+    let get_current_thread = &config.kernel.get_current_thread;
+    let pdomain = &config.kernel.pdomain;
+    let pheap = &config.kernel.pheap;
+    let syscalls = &config.crates.syscalls;
 
     // Generate impl method.
     let generated_impl = parse_quote! {
         #method_sig {
             // Entering kernel, disable irq
-            crate::interrupt::disable_irq();
+            #disable_irq();
 
             let rtn_ = #generated_fn_ident(#(#selfless_args),*);
 
             // Leaving kernel, reable irq
-            crate::interrupt::enable_irq();
+            #enable_irq();
 
             // Returns the domain to caller.
             rtn_
@@ -78,7 +70,7 @@ pub fn generate_domain_create_for_trait_method(
     let generated_fn = parse_quote! {
         pub(crate) fn #generated_fn_ident(#(#selfless_args),*) #rtn {
             // Entering kernel, disable irq
-            crate::interrupt::disable_irq();
+            #disable_irq();
 
             extern "C" {
                 fn #domain_start_ident();
@@ -90,22 +82,22 @@ pub fn generate_domain_create_for_trait_method(
             let binary_range_ = (begin_, end_);
 
             type UserInit_ =
-                fn(::alloc::boxed::Box<dyn ::syscalls::Syscall>, ::alloc::boxed::Box<dyn ::syscalls::Heap>, #(#ep_args),*) -> #ep_rtn;
+                fn(::alloc::boxed::Box<dyn #syscalls::Syscall>, ::alloc::boxed::Box<dyn #syscalls::Heap>, #(#ep_args),*) -> #ep_rtn;
 
             #[cfg(feature = "domain_create_log")]
             println!("Loading blob_domain/{}/{}", #domain_path, name);
 
 
-            let (dom_, entry_) = unsafe { crate::domain::load_domain(#domain_path, binary_range_) };
+            let (dom_, entry_) = unsafe { #load_domain(#domain_path, binary_range_) };
 
             // Type cast the pointer to entry point to the correct type.
             let user_ep_: UserInit_ = unsafe { ::core::mem::transmute::<*const (), UserInit_>(entry_) };
 
-            let pdom_ = ::alloc::boxed::Box::new(crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom_)));
-            let pheap_ = ::alloc::boxed::Box::new(crate::heap::PHeap::new());
+            let pdom_ = ::alloc::boxed::Box::new(#pdomain::new(::alloc::sync::Arc::clone(&dom_)));
+            let pheap_ = ::alloc::boxed::Box::new(#pheap::new());
 
             // update current domain id.
-            let thread_ = crate::thread::get_current_ref();
+            let thread_ = #get_current_thread();
             let old_id_ = {
                 let mut thread = thread_.lock();
                 let old_id = thread.current_domain_id;
@@ -114,11 +106,11 @@ pub fn generate_domain_create_for_trait_method(
             };
 
             // Enable interrupts on exit to user so it can be preempted.
-            crate::interrupt::enable_irq();
+            #enable_irq();
             // Jumps to the domain entry point.
             let ep_rtn_ = user_ep_(pdom_, pheap_, #(#ep_args),*);
             // Disable interrupts as we are back to the kernel.
-            crate::interrupt::disable_irq();
+            #disable_irq();
 
             // change domain id back
             {
@@ -129,16 +121,16 @@ pub fn generate_domain_create_for_trait_method(
             println!("blob_domain/{}/{}: returned from entry point", #domain_path, name);
 
             // Setup the return object.
-            let dom_: ::alloc::boxed::Box<dyn ::syscalls::Domain> = ::alloc::boxed::Box::new(crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom_)));
+            let dom_: ::alloc::boxed::Box<dyn #syscalls::Domain> = ::alloc::boxed::Box::new(#pdomain::new(::alloc::sync::Arc::clone(&dom_)));
             let rtn_ = (dom_, ep_rtn_);
 
             // Leaving kernel, reable irq
-            crate::interrupt::enable_irq();
+            #enable_irq();
 
             // Returns the domain to caller.
             rtn_
         }
     };
 
-    (generated_impl, generated_fn)
+    Ok((generated_impl, generated_fn))
 }
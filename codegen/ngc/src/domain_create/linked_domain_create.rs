@@ -2,6 +2,9 @@ use quote::format_ident;
 
 use syn::{parse_quote, FnArg, TraitItemMethod};
 
+use crate::config::Config;
+use crate::error::{Error, Result};
+
 use super::DomainCreateComponent;
 
 /// This generates a public fn and a impl method.
@@ -10,7 +13,8 @@ pub fn generate_domain_create_for_trait_method(
     domain_path: &str,
     domain_components: &Vec<DomainCreateComponent>,
     method: &TraitItemMethod,
-) -> (syn::ImplItemMethod, syn::ItemFn) {
+    config: &Config,
+) -> Result<(syn::ImplItemMethod, syn::ItemFn)> {
     // Remove `self` from the argument list
     let selfless_args: Vec<_> = method
         .sig
@@ -22,6 +26,28 @@ pub fn generate_domain_create_for_trait_method(
         })
         .collect();
 
+    // A recreate method takes the old domain as its first argument. The old domain is torn down
+    // and replaced, so it's not passed to the entry point.
+    let is_recreate = method
+        .sig
+        .ident
+        .to_string()
+        .starts_with(super::RECREATE_DOMAIN_PREFIX);
+    let (old_dom_arg, ep_args) = if is_recreate {
+        let (old_dom_arg, ep_args) = selfless_args.split_first().ok_or_else(|| {
+            Error::new_spanned(
+                &method.sig,
+                format!(
+                    "Method `{}` of domain `{}` does not take the old domain to recreate",
+                    method.sig.ident, domain_path
+                ),
+            )
+        })?;
+        (Some(*old_dom_arg), ep_args.to_vec())
+    } else {
+        (None, selfless_args.clone())
+    };
+
     // Extract essential variables for generation.
     let method_ident = &method.sig.ident;
     let _method_args = method.sig.inputs.iter().collect::<Vec<_>>();
@@ -32,44 +58,69 @@ pub fn generate_domain_create_for_trait_method(
         format_ident!("_binary_domains_build_{}_start", canonicalized_domain_path);
     let domain_end_ident = format_ident!("_binary_domains_build_{}_end", canonicalized_domain_path);
     let rtn = &method.sig.output;
-    let ep_rtn = match &method.sig.output {
-        syn::ReturnType::Type(_, ty) => match ty {
-            box syn::Type::Tuple(tuple) => {
-                assert_eq!(
-                    tuple.elems.iter().count(),
-                    2,
-                    "Expecting a tuple of two in the return type of method {:?} of domain {:?}",
-                    method_ident,
-                    domain_path
-                );
-                tuple.elems.iter().nth(1).unwrap()
-            }
-            _ => panic!(
-                "Expecting a tuple of two in the return type of method {:?} of domain {:?}",
-                method_ident, domain_path
-            ),
-        },
-        syn::ReturnType::Default => panic!(
-            "Method {:?} of domain {:?} does not have a return type. Expecting a tuple of two.",
-            method_ident, domain_path
-        ),
-    };
+    let ep_rtn = super::entry_point_return_type(domain_path, method)?;
+
+    // Paths to the kernel APIs.
+    let disable_irq = &config.kernel.disable_irq;
+    let enable_irq = &config.kernel.enable_irq;
+    let load_domain = &config.kernel.load_domain;
+    let reload_domain = &config.kernel.reload_domain;
+    let reclaim_domain_heap = &config.kernel.reclaim_domain_heap;
+    let get_current_thread = &config.kernel.get_current_thread;
+    let pdomain = &config.kernel.pdomain;
+    let syscalls = &config.crates.syscalls;
+    let interface = &config.crates.interface;
 
     // Statements to initialize the components needed by the domain
     let domain_component_creation = domain_components
         .iter()
-        .map(|component| component.creation_statement())
+        .map(|component| component.creation_statement(config))
         .collect::<Vec<syn::Stmt>>();
 
     let domain_components_as_fn_args = domain_components
         .iter()
-        .map(|c| c.as_fn_argument())
+        .map(|c| c.as_fn_argument(config))
         .collect::<Vec<syn::FnArg>>();
 
-    let entry_point_args: Vec<&FnArg> = domain_components_as_fn_args
-        .iter()
-        .chain(selfless_args.clone())
-        .collect();
+    let entry_point_args: Vec<&FnArg> =
+        domain_components_as_fn_args.iter().chain(ep_args).collect();
+
+    // Load the domain. A recreated domain reuses the binary range and the identity slot of the old
+    // domain after everything the old domain owns on the shared heap is released.
+    let load_domain_stmts: Vec<syn::Stmt> = match old_dom_arg {
+        None => parse_quote! {
+            let (dom_, entry_) = unsafe { #load_domain(#domain_path, binary_range_) };
+        },
+        Some(old_dom_arg) => {
+            let old_dom = match old_dom_arg {
+                FnArg::Typed(arg) => match arg.pat.as_ref() {
+                    syn::Pat::Ident(id) => &id.ident,
+                    pat => {
+                        return Err(Error::new_spanned(
+                            pat,
+                            format!(
+                            "Unsupported pattern of the old domain in method `{}` of domain `{}`",
+                            method_ident, domain_path
+                        ),
+                        ))
+                    }
+                },
+                FnArg::Receiver(_) => unreachable!(),
+            };
+            parse_quote! {
+                let old_dom_id_ = #old_dom.get_domain_id();
+                ::core::mem::drop(#old_dom);
+
+                // Release the objects the old domain owns on the shared heap. Each of them is
+                // dropped with the drop glue of its type.
+                #reclaim_domain_heap(old_dom_id_, &#interface::typeid::DROP_MAP);
+
+                let (dom_, entry_) = unsafe {
+                    #reload_domain(old_dom_id_, #domain_path, binary_range_)
+                };
+            }
+        }
+    };
 
     let entry_point_args_no_types = entry_point_args.iter().filter_map(|c| match c {
         FnArg::Typed(arg) => match arg.pat.as_ref() {
@@ -83,12 +134,12 @@ pub fn generate_domain_create_for_trait_method(
     let generated_impl = parse_quote! {
         #method_sig {
             // Entering kernel, disable irq
-            crate::interrupt::disable_irq();
+            #disable_irq();
 
             let rtn_ = #generated_fn_ident(#(#selfless_args),*);
 
             // Leaving kernel, reable irq
-            crate::interrupt::enable_irq();
+            #enable_irq();
 
             // Returns the domain to caller.
             rtn_
@@ -105,7 +156,7 @@ pub fn generate_domain_create_for_trait_method(
     let generated_fn = parse_quote! {
         pub(crate) fn #generated_fn_ident(#(#selfless_args),*) #rtn {
             // Entering kernel, disable irq
-            crate::interrupt::disable_irq();
+            #disable_irq();
 
             extern "C" {
                 fn #domain_start_ident();
@@ -120,7 +171,7 @@ pub fn generate_domain_create_for_trait_method(
             type UserInit_ =
                 fn(#(#entry_point_args),*) -> #ep_rtn;
 
-            let (dom_, entry_) = unsafe { crate::domain::load_domain(#domain_path, binary_range_) };
+            #(#load_domain_stmts)*
 
             // Type cast the pointer to entry point to the correct type.
             let user_ep_: UserInit_ = unsafe { ::core::mem::transmute::<*const (), UserInit_>(entry_) };
@@ -129,7 +180,7 @@ pub fn generate_domain_create_for_trait_method(
             #(#domain_component_creation)*
 
             // update current domain id.
-            let thread_ = crate::thread::get_current_ref();
+            let thread_ = #get_current_thread();
             let old_id_ = {
                 let mut thread = thread_.lock();
                 let old_id = thread.current_domain_id;
@@ -138,11 +189,11 @@ pub fn generate_domain_create_for_trait_method(
             };
 
             // Enable interrupts on exit to user so it can be preempted.
-            crate::interrupt::enable_irq();
+            #enable_irq();
             // Jumps to the domain entry point.
             let ep_rtn_ = user_ep_(#(#entry_point_args_no_types),*);
             // Disable interrupts as we are back to the kernel.
-            crate::interrupt::disable_irq();
+            #disable_irq();
 
             // change domain id back
             {
@@ -153,16 +204,16 @@ pub fn generate_domain_create_for_trait_method(
             println!("domain/{}: returned from entry point", #domain_path);
 
             // Setup the return object.
-            let dom_: ::alloc::boxed::Box<dyn ::syscalls::Domain> = ::alloc::boxed::Box::new(crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom_)));
+            let dom_: ::alloc::boxed::Box<dyn #syscalls::Domain> = ::alloc::boxed::Box::new(#pdomain::new(::alloc::sync::Arc::clone(&dom_)));
             let rtn_ = (dom_, ep_rtn_);
 
             // Leaving kernel, reable irq
-            crate::interrupt::enable_irq();
+            #enable_irq();
 
             // Returns the domain to caller.
             rtn_
         }
     };
 
-    (generated_impl, generated_fn)
+    Ok((generated_impl, generated_fn))
 }
@@ -1,18 +1,23 @@
 mod blob_domain_create;
 mod linked_domain_create;
 
-use crate::{domain_entrypoint::DomainEntrypointFactory, has_attribute, remove_attribute};
+#[cfg(test)]
+mod tests;
+
+use crate::config::Config;
+use crate::domain_entrypoint::{DomainEntrypointFactory, EntrypointCrate};
+use crate::error::{Error, Result};
+use crate::{has_attribute, remove_attribute};
 use log::{debug, error, info, warn};
 use quote::{format_ident, ToTokens};
 use std::collections::HashMap;
 use syn::{
     parse_quote, Expr, Ident, ImplItemMethod, Item, ItemFn, ItemTrait, Lit, Meta, NestedMeta, Path,
-    TraitItem,
+    TraitItem, TraitItemMethod, Type,
 };
 
-pub const LINKED_DOMAIN_CREATE_ATTR: &str = "domain_create";
-pub const BLOB_DOMAIN_CREATE_ATTR: &str = "domain_create_blob";
-pub const DOMAIN_CREATE_COMPONENTS_ATTR: &str = "domain_create_components";
+/// Methods of domain create traits that replace an existing domain are prefixed with this.
+pub const RECREATE_DOMAIN_PREFIX: &str = "recreate_domain_";
 
 #[derive(Debug, Clone, Copy)]
 pub enum DomainCreateComponent {
@@ -22,30 +27,34 @@ pub enum DomainCreateComponent {
 }
 
 impl DomainCreateComponent {
-    fn creation_statement(&self) -> syn::Stmt {
+    fn creation_statement(&self, config: &Config) -> syn::Stmt {
+        let pdomain = &config.kernel.pdomain;
+        let mmap = &config.kernel.mmap;
+        let pheap = &config.kernel.pheap;
         match self {
             &DomainCreateComponent::Domain => parse_quote! {
-                let pdom_ = ::alloc::boxed::Box::new(crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom_)));
+                let pdom_ = ::alloc::boxed::Box::new(#pdomain::new(::alloc::sync::Arc::clone(&dom_)));
             },
             &DomainCreateComponent::MMap => parse_quote! {
-                let pmmap_ = ::alloc::boxed::Box::new(crate::syscalls::Mmap::new());
+                let pmmap_ = ::alloc::boxed::Box::new(#mmap::new());
             },
             &DomainCreateComponent::Heap => parse_quote! {
-                let pheap_ = ::alloc::boxed::Box::new(crate::heap::PHeap::new());
+                let pheap_ = ::alloc::boxed::Box::new(#pheap::new());
             },
         }
     }
 
-    fn as_fn_argument(&self) -> syn::FnArg {
+    fn as_fn_argument(&self, config: &Config) -> syn::FnArg {
+        let syscalls = &config.crates.syscalls;
         match self {
             &DomainCreateComponent::Domain => parse_quote! {
-                pdom_: ::alloc::boxed::Box<dyn syscalls::Syscall>
+                pdom_: ::alloc::boxed::Box<dyn #syscalls::Syscall>
             },
             &DomainCreateComponent::MMap => parse_quote! {
-                pmmap_: ::alloc::boxed::Box<dyn syscalls::Mmap>
+                pmmap_: ::alloc::boxed::Box<dyn #syscalls::Mmap>
             },
             &DomainCreateComponent::Heap => parse_quote! {
-                pheap_: ::alloc::boxed::Box<dyn syscalls::Heap>
+                pheap_: ::alloc::boxed::Box<dyn #syscalls::Heap>
             },
         }
     }
@@ -57,51 +66,60 @@ pub struct DomainCreateBuilder {
     /// The path to the root of the domains folder for RedLeaf, used for entrypoint generation
     domain_entrypoint_factory: Option<DomainEntrypointFactory>,
     domain_creates: Vec<(Path, ItemTrait)>,
+    config: Config,
 }
 
 impl DomainCreateBuilder {
-    pub fn new() -> Self {
+    pub fn new(config: Config) -> Self {
         Self {
             domain_entrypoint_factory: None,
             domain_creates: vec![],
+            config,
         }
     }
 
-    pub fn new_with_domains_folder(domains_folder: DomainEntrypointFactory) -> Self {
+    pub fn new_with_domains_folder(
+        domains_folder: DomainEntrypointFactory,
+        config: Config,
+    ) -> Self {
         Self {
             domain_entrypoint_factory: Some(domains_folder),
             domain_creates: vec![],
+            config,
         }
     }
 
-    /// Generates the domain create for `input` if it has the `DOMAIN_CREATE_ATTR` attribute.
+    /// Generates the domain create for `input` if it has the domain create attribute.
     pub fn generate_domain_create(
         &mut self,
         input: &mut ItemTrait,
         module_path: &[Ident],
-    ) -> Option<Vec<Item>> {
+    ) -> Result<Option<Vec<Item>>> {
         // Create an attribute map.
-        let attrs: HashMap<String, Option<Lit>> = crate::utils::create_attribue_map(&input.attrs);
+        let attrs: HashMap<String, Option<Lit>> = crate::utils::create_attribue_map(&input.attrs)?;
 
         // Filter out non-domain_create traits and remove domain_create attributes.
+        let linked_domain_create_attr = &self.config.attributes.domain_create;
+        let blob_domain_create_attr = &self.config.attributes.domain_create_blob;
+        let domain_create_components_attr = &self.config.attributes.domain_create_components;
         let is_blob_domain_create;
-        if has_attribute!(input, LINKED_DOMAIN_CREATE_ATTR) {
+        if has_attribute!(input, linked_domain_create_attr) {
             is_blob_domain_create = false;
-            remove_attribute!(input, LINKED_DOMAIN_CREATE_ATTR);
-        } else if has_attribute!(input, BLOB_DOMAIN_CREATE_ATTR) {
+            remove_attribute!(input, linked_domain_create_attr);
+        } else if has_attribute!(input, blob_domain_create_attr) {
             is_blob_domain_create = true;
-            remove_attribute!(input, BLOB_DOMAIN_CREATE_ATTR);
+            remove_attribute!(input, blob_domain_create_attr);
         } else {
-            return None;
+            return Ok(None);
         }
 
         // Default domain create components, can be overridden with #[domain_create_components(Domain, MMap, Heap)]
         let mut domain_components =
             vec![DomainCreateComponent::Domain, DomainCreateComponent::Heap];
 
-        let metas = input.attrs.iter().map(|attr| attr.parse_meta().unwrap());
-        for meta in metas {
-            if meta.path().is_ident(DOMAIN_CREATE_COMPONENTS_ATTR) {
+        for attr in &input.attrs {
+            let meta = attr.parse_meta()?;
+            if meta.path().is_ident(domain_create_components_attr) {
                 let mut new_domain_components = vec![];
                 // Override domain components
                 match meta {
@@ -119,10 +137,13 @@ impl DomainCreateBuilder {
                                             new_domain_components.push(DomainCreateComponent::Heap)
                                         }
                                         other => {
-                                            panic!(
-                                                "Unsupported domain_create_component '{:#?}'",
-                                                &other
-                                            );
+                                            return Err(Error::new_spanned(
+                                                ident,
+                                                format!(
+                                                    "Unsupported domain create component `{}`; expecting `Domain`, `MMap`, or `Heap`",
+                                                    other
+                                                ),
+                                            ));
                                         }
                                     }
                                 }
@@ -137,7 +158,7 @@ impl DomainCreateBuilder {
             }
         }
 
-        remove_attribute!(input, DOMAIN_CREATE_COMPONENTS_ATTR);
+        remove_attribute!(input, domain_create_components_attr);
 
         info!("Generating domain create for trait {:?}.", input.ident);
 
@@ -159,14 +180,14 @@ impl DomainCreateBuilder {
         self.domain_creates
             .push((trait_path.clone(), input.clone()));
 
-        // Create a copy of the input, refactor the path from `crate` to `interface, and we will be
+        // Create a copy of the input, refactor the path from `crate` to `interface`, and we will be
         // working with the refactored one from now on.
         // The reason is that domain create will be generated into the kernel, which has a different
         // dependency path to the interface
         let mut input_copy = input.clone();
         crate::path_refactoring::refactor_path_in_trait(
             &format_ident!("crate"),
-            &format_ident!("interface"),
+            &self.config.crates.interface,
             &mut input_copy,
         );
 
@@ -181,15 +202,26 @@ impl DomainCreateBuilder {
             self.get_relative_domain_path(&input, attrs.get("relative_path"));
 
         // Extract the domain path.
-        let domain_path = crate::expect!(
-            attrs.get("path"),
-            "Domain path not found for trait {}",
-            input.ident
-        );
-        let domain_path = domain_path.as_ref().expect("Domain path is empty");
-        let domain_path = match domain_path {
-            Lit::Str(domain_path) => domain_path.value(),
-            _ => panic!("Expecting a string."),
+        let domain_path = match attrs.get("path") {
+            Some(Some(Lit::Str(domain_path))) => domain_path.value(),
+            Some(Some(lit)) => {
+                return Err(Error::new_spanned(
+                    lit,
+                    format!(
+                        "Expecting a string for the domain path of trait `{}`",
+                        input.ident
+                    ),
+                ))
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    format!(
+                        "Domain path not found for trait `{}`; expecting `#[domain_create(path = \"...\")]`",
+                        input.ident
+                    ),
+                ))
+            }
         };
 
         // Generate code. Proxy is generated inplace and domain create is returned.
@@ -202,19 +234,20 @@ impl DomainCreateBuilder {
                         self::blob_domain_create::generate_domain_create_for_trait_method(
                             &domain_path,
                             method,
+                            &self.config,
                         )
                     } else {
                         // If we have a relative path then we'll generate an entrypoint
                         if self.domain_entrypoint_factory.is_some() {
                             if let Some(domain_relative_path) = domain_relative_path.as_ref() {
                                 self.domain_entrypoint_factory
-                                    .as_ref()
+                                    .as_mut()
                                     .unwrap()
                                     .generate_domain_entrypoint_crates(
                                         domain_relative_path,
                                         &domain_components,
                                         method,
-                                    );
+                                    )?;
                             }
                         }
 
@@ -222,17 +255,27 @@ impl DomainCreateBuilder {
                             &domain_path,
                             &domain_components,
                             method,
+                            &self.config,
                         )
                     }
                 }
-                _ => unimplemented!("Non-method member found in trait {:#?}", input),
+                item => Err(Error::new_spanned(
+                    item,
+                    format!(
+                        "Domain create trait `{}` must only contain methods",
+                        input.ident
+                    ),
+                )),
             })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
             .unzip();
 
         // Generate the impl block.
+        let pdomain = &self.config.kernel.pdomain;
         let mut generated: Vec<Item> = Vec::new();
         generated.push(Item::Impl(parse_quote! {
-            impl #trait_path for crate::syscalls::PDomain {
+            impl #trait_path for #pdomain {
                 #(#generated_impl_items)*
             }
         }));
@@ -241,7 +284,7 @@ impl DomainCreateBuilder {
         generated.extend(generated_fns.into_iter().map(Item::Fn));
 
         // Return the generated code.
-        Some(generated)
+        Ok(Some(generated))
     }
 
     pub fn get_domain_paths(&self) -> Vec<&Path> {
@@ -250,16 +293,25 @@ impl DomainCreateBuilder {
 
     pub fn generate_create_init(&self) -> Item {
         let domain_create_paths = self.get_domain_paths();
-
-        let arcs: Vec<Expr> = self.domain_creates.iter().map(|_| {
-            parse_quote! {
-                ::alloc::sync::Arc::new(crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom)))
-            }
-        }).collect();
+        let kernel = &self.config.kernel;
+        let (pdomain, pheap, interrupt) = (&kernel.pdomain, &kernel.pheap, &kernel.interrupt);
+        let (load_domain, get_current_thread) = (&kernel.load_domain, &kernel.get_current_thread);
+        let (enable_irq, disable_irq) = (&kernel.enable_irq, &kernel.disable_irq);
+        let syscalls = &self.config.crates.syscalls;
+
+        let arcs: Vec<Expr> = self
+            .domain_creates
+            .iter()
+            .map(|_| {
+                parse_quote! {
+                    ::alloc::sync::Arc::new(#pdomain::new(::alloc::sync::Arc::clone(&dom)))
+                }
+            })
+            .collect();
 
         parse_quote! {
 
-            pub fn create_domain_init() -> ::alloc::boxed::Box<dyn ::syscalls::Domain> {
+            pub fn create_domain_init() -> ::alloc::boxed::Box<dyn #syscalls::Domain> {
                 let name = "init";
 
                 extern "C" {
@@ -273,19 +325,19 @@ impl DomainCreateBuilder {
                 );
 
                 type UserInit = fn(
-                    ::alloc::boxed::Box<dyn ::syscalls::Syscall + Send + Sync>,
-                    ::alloc::boxed::Box<dyn ::syscalls::Heap + Send + Sync>,
-                    ::alloc::boxed::Box<dyn ::syscalls::Interrupt>,
+                    ::alloc::boxed::Box<dyn #syscalls::Syscall + Send + Sync>,
+                    ::alloc::boxed::Box<dyn #syscalls::Heap + Send + Sync>,
+                    ::alloc::boxed::Box<dyn #syscalls::Interrupt>,
 
                     #(::alloc::sync::Arc<dyn #domain_create_paths>,)*
                 );
 
-                let (dom, entry) = unsafe { crate::domain::load_domain(name, binary_range) };
+                let (dom, entry) = unsafe { #load_domain(name, binary_range) };
 
                 let user_ep: UserInit = unsafe { ::core::mem::transmute::<*const (), UserInit>(entry) };
 
                 // update current domain id
-                let thread = crate::thread::get_current_ref();
+                let thread = #get_current_thread();
                 let old_id = {
                     let mut thread = thread.lock();
                     let old_id = thread.current_domain_id;
@@ -294,14 +346,14 @@ impl DomainCreateBuilder {
                 };
 
                 // Enable interrupts on exit to user so it can be preempted
-                crate::interrupt::enable_irq();
+                #enable_irq();
                 user_ep(
-                    ::alloc::boxed::Box::new(crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom))),
-                    ::alloc::boxed::Box::new(crate::heap::PHeap::new()),
-                    ::alloc::boxed::Box::new(crate::syscalls::Interrupt::new()),
+                    ::alloc::boxed::Box::new(#pdomain::new(::alloc::sync::Arc::clone(&dom))),
+                    ::alloc::boxed::Box::new(#pheap::new()),
+                    ::alloc::boxed::Box::new(#interrupt::new()),
                     #(#arcs),*
                 );
-                crate::interrupt::disable_irq();
+                #disable_irq();
 
                 // change domain id back
                 {
@@ -310,11 +362,19 @@ impl DomainCreateBuilder {
 
                 #[cfg(feature = "domain_create_log")]
                 println!("domain/{}: returned from entry point", name);
-                ::alloc::boxed::Box::new(crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom)))
+                ::alloc::boxed::Box::new(#pdomain::new(::alloc::sync::Arc::clone(&dom)))
             }
         }
     }
 
+    /// Takes the domain entrypoint crates generated so far. Empty if there's no domains folder.
+    pub fn take_entrypoints(&mut self) -> Vec<EntrypointCrate> {
+        self.domain_entrypoint_factory
+            .as_mut()
+            .map(|factory| factory.take_entrypoints())
+            .unwrap_or_default()
+    }
+
     pub fn take(self) -> Vec<(Path, ItemTrait)> {
         self.domain_creates
     }
@@ -372,3 +432,27 @@ impl DomainCreateBuilder {
         return true;
     }
 }
+
+/// Returns the type that the domain entry point returns, i.e. `T` in the `(Box<dyn Domain>, T)`
+/// returned by the domain create method.
+fn entry_point_return_type<'a>(domain_path: &str, method: &'a TraitItemMethod) -> Result<&'a Type> {
+    match &method.sig.output {
+        syn::ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Tuple(tuple) if tuple.elems.len() == 2 => Ok(&tuple.elems[1]),
+            _ => Err(Error::new_spanned(
+                ty,
+                format!(
+                    "Expecting a tuple of two in the return type of method `{}` of domain `{}`",
+                    method.sig.ident, domain_path
+                ),
+            )),
+        },
+        syn::ReturnType::Default => Err(Error::new_spanned(
+            &method.sig,
+            format!(
+                "Method `{}` of domain `{}` does not have a return type. Expecting a tuple of two.",
+                method.sig.ident, domain_path
+            ),
+        )),
+    }
+}
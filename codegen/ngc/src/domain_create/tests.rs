@@ -0,0 +1,103 @@
+use super::linked_domain_create::generate_domain_create_for_trait_method;
+use super::DomainCreateComponent;
+use crate::config::Config;
+use quote::quote;
+use syn::{parse_quote, TraitItemMethod};
+
+fn recreate_bdev() -> TraitItemMethod {
+    parse_quote! {
+        fn recreate_domain_bdev(
+            &self,
+            dom: Box<dyn syscalls::Domain>,
+            pci: &dyn PCI,
+        ) -> (Box<dyn syscalls::Domain>, Box<dyn BDev>);
+    }
+}
+
+#[test]
+fn test_recreate_tears_down_old_domain() {
+    let (_, generated_fn) = generate_domain_create_for_trait_method(
+        "bdev",
+        &vec![DomainCreateComponent::Domain],
+        &recreate_bdev(),
+        &Config::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        quote!(#generated_fn).to_string(),
+        quote! {
+            pub(crate) fn bdev_recreate_domain_bdev(
+                dom: Box<dyn syscalls::Domain>,
+                pci: &dyn PCI
+            ) -> (Box<dyn syscalls::Domain>, Box<dyn BDev>) {
+                crate::interrupt::disable_irq();
+                extern "C" {
+                    fn _binary_domains_build_bdev_start();
+                    fn _binary_domains_build_bdev_end();
+                }
+                let binary_range_ = (
+                    _binary_domains_build_bdev_start as *const u8,
+                    _binary_domains_build_bdev_end as *const u8,
+                );
+                type UserInit_ =
+                    fn(pdom_: ::alloc::boxed::Box<dyn ::syscalls::Syscall>, pci: &dyn PCI) -> Box<dyn BDev>;
+                let old_dom_id_ = dom.get_domain_id();
+                ::core::mem::drop(dom);
+                crate::heap::reclaim_domain_heap(old_dom_id_, &interface::typeid::DROP_MAP);
+                let (dom_, entry_) = unsafe {
+                    crate::domain::reload_domain(old_dom_id_, "bdev", binary_range_)
+                };
+                let user_ep_: UserInit_ =
+                    unsafe { ::core::mem::transmute::<*const (), UserInit_>(entry_) };
+                let pdom_ = ::alloc::boxed::Box::new(crate::syscalls::PDomain::new(
+                    ::alloc::sync::Arc::clone(&dom_)
+                ));
+                let thread_ = crate::thread::get_current_ref();
+                let old_id_ = {
+                    let mut thread = thread_.lock();
+                    let old_id = thread.current_domain_id;
+                    thread.current_domain_id = dom_.lock().id;
+                    old_id
+                };
+                crate::interrupt::enable_irq();
+                let ep_rtn_ = user_ep_(pdom_, pci);
+                crate::interrupt::disable_irq();
+                {
+                    thread_.lock().current_domain_id = old_id_;
+                }
+                #[cfg(feature = "domain_create_log")]
+                println!("domain/{}: returned from entry point", "bdev");
+                let dom_: ::alloc::boxed::Box<dyn ::syscalls::Domain> = ::alloc::boxed::Box::new(
+                    crate::syscalls::PDomain::new(::alloc::sync::Arc::clone(&dom_))
+                );
+                let rtn_ = (dom_, ep_rtn_);
+                crate::interrupt::enable_irq();
+                rtn_
+            }
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_create_loads_new_domain() {
+    let create: TraitItemMethod = parse_quote! {
+        fn create_domain_bdev(&self, pci: &dyn PCI) -> (Box<dyn syscalls::Domain>, Box<dyn BDev>);
+    };
+    let (_, generated_fn) = generate_domain_create_for_trait_method(
+        "bdev",
+        &vec![DomainCreateComponent::Domain],
+        &create,
+        &Config::default(),
+    )
+    .unwrap();
+    let generated = quote!(#generated_fn).to_string();
+    assert!(generated.contains(
+        &quote! {
+            let (dom_, entry_) = unsafe { crate::domain::load_domain("bdev", binary_range_) };
+        }
+        .to_string()
+    ));
+    assert!(!generated.contains("reload_domain"));
+    assert!(!generated.contains("reclaim_domain_heap"));
+}
@@ -1,6 +1,9 @@
+use crate::config::Config;
 use crate::domain_create::DomainCreateComponent;
+use crate::error::{BuildError, Error, Result};
 use std::{
-    fs::{canonicalize, create_dir, remove_dir_all, File},
+    fs::{create_dir, create_dir_all, remove_dir_all, File},
+    io,
     iter::Map,
     path::{Path, PathBuf},
 };
@@ -9,59 +12,83 @@ use log::{debug, error, info, warn};
 use quote::{format_ident, ToTokens};
 use syn::{parse_quote, TraitItemMethod};
 
-pub struct DomainEntrypointFactory {
-    output_folder_created: bool,
-    output_folder_path: PathBuf,
+/// A generated domain entrypoint crate.
+#[derive(Debug, Clone)]
+pub struct EntrypointCrate {
+    /// Name of the domain.
+    pub name: String,
+    pub cargo_toml: String,
+    pub main_rs: String,
+}
 
+impl EntrypointCrate {
+    /// Returns the files of the crate, relative to the output folder, and their contents.
+    pub fn files(&self) -> Vec<(PathBuf, &str)> {
+        let crate_path = PathBuf::from(self.name.to_owned() + "_entry_point");
+        vec![
+            (crate_path.join("Cargo.toml"), &self.cargo_toml),
+            (crate_path.join("src/main.rs"), &self.main_rs),
+        ]
+    }
+}
+
+pub struct DomainEntrypointFactory {
     domains_folder: PathBuf,
+    config: Config,
+    entrypoints: Vec<EntrypointCrate>,
 }
 
 impl DomainEntrypointFactory {
-    pub fn new(domains_folder: PathBuf) -> Self {
-        let output_folder_path = &domains_folder.join("generated");
-
-        Self::setup_output_folder(output_folder_path);
-
+    pub fn new(domains_folder: PathBuf, config: Config) -> Self {
         DomainEntrypointFactory {
-            output_folder_created: true,
-            output_folder_path: output_folder_path.to_path_buf(),
             domains_folder,
+            config,
+            entrypoints: vec![],
         }
     }
 
-    fn setup_output_folder(output_path: &Path) {
-        // Check if folder already exists and if there're files inside
-        let output_path = Path::new(output_path);
-
-        // Check we're not overriding something important!
-        if output_path.exists()
-            && !output_path
-                .join("ngc_generated_domain_entrypoints")
-                .exists()
-        {
-            panic!("NGC will not override directories that it did not generate. {:#?} does not appear to be generated!", output_path)
+    /// Returns the folder that the entrypoint crates are generated into.
+    pub fn output_folder(domains_folder: &Path) -> PathBuf {
+        domains_folder.join("generated")
+    }
+
+    /// Replaces the output folder with the `entrypoints`.
+    pub fn write_entrypoint_crates(
+        output_folder: &Path,
+        entrypoints: &[EntrypointCrate],
+    ) -> std::result::Result<(), BuildError> {
+        Self::setup_output_folder(output_folder)?;
+        for entrypoint in entrypoints {
+            Self::write_entrypoint_crate(output_folder, entrypoint)?;
         }
+        Ok(())
+    }
 
-        // If we made it here, we're okay!
+    fn setup_output_folder(output_path: &Path) -> std::result::Result<(), BuildError> {
+        let io_error = |path: &Path| {
+            let path = path.to_owned();
+            move |source| BuildError::Io { path, source }
+        };
+        let indicator_path = output_path.join("ngc_generated_domain_entrypoints");
 
         if output_path.exists() {
-            // Delete the folder and replace it with a new one
-            info!("Going to delete {:#?}", canonicalize(output_path).unwrap());
-            let res = remove_dir_all(output_path);
-            if res.is_err() {
-                panic!("Failed to remove generated directory {:#?}", output_path);
+            // Check we're not overriding something important!
+            if !indicator_path.exists() {
+                return Err(io_error(output_path)(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "NGC will not override directories that it did not generate",
+                )));
             }
+
+            // Delete the folder and replace it with a new one
+            info!("Going to delete {}", output_path.display());
+            remove_dir_all(output_path).map_err(io_error(output_path))?;
         }
 
         // At this point we need to create the directory and the `ngc_generated_domain_entrypoints` file
-        if let Err(e) = create_dir(output_path) {
-            panic!(
-                "Failed to create directory at {:#?}, ERROR: {:#?}",
-                output_path, e
-            );
-        }
-        File::create(output_path.join("ngc_generated_domain_entrypoints"))
-            .expect("Failed to create indicator file for entrypoint crates");
+        create_dir(output_path).map_err(io_error(output_path))?;
+        File::create(&indicator_path).map_err(io_error(&indicator_path))?;
+        Ok(())
     }
 
     pub fn as_relative_to_domains_folder(&self, path: &Path) -> PathBuf {
@@ -73,39 +100,18 @@ impl DomainEntrypointFactory {
         domain_relative_path: &Path,
         domain_name: &str,
     ) -> String {
-        format!(
-            r#"
-                [package]
-                name = "{:}"
-                version = "0.1.0"
-                authors = ["Redleaf team <aburtsev@uci.edu>"]
-                edition = "2018"
-    
-                # See more keys and their definitions at https://doc.rust-lang.org/cargo/reference/manifest.html
-    
-                [[bin]]
-                name = "{:}"
-                path = "src/main.rs"
-    
-                [dependencies]
-                interface = {{ path = "../../../interface/generated" }}
-                libsyscalls = {{ path = "../../../lib/core/libsyscalls" }}
-                syscalls = {{ path = "../../../lib/core/interfaces/syscalls" }}
-                console = {{ path = "../../../lib/core/console" }}
-    
-                {:} = {{ path = "../../{:}" }}       
-                "#,
-            domain_name.to_owned() + "_entry_point",
-            domain_name,
-            domain_name,
-            domain_relative_path
-                .strip_prefix(&self.domains_folder)
-                .unwrap()
-                .display()
-        )
-        .split_terminator("\n")
-        .filter_map(|line| if line.is_empty() {None} else { Some(line.trim().to_owned() + "\n" )})
-        .collect()
+        self.config
+            .entrypoint
+            .cargo_template
+            .replace("{name}", domain_name)
+            .replace(
+                "{domain_path}",
+                &domain_relative_path
+                    .strip_prefix(&self.domains_folder)
+                    .unwrap()
+                    .display()
+                    .to_string(),
+            )
     }
 
     pub fn generate_entrypoint_main_rs(
@@ -113,31 +119,34 @@ impl DomainEntrypointFactory {
         domain_name: &str,
         domain_components: &Vec<DomainCreateComponent>,
         method: &TraitItemMethod,
-    ) -> String {
+    ) -> Result<String> {
+        let interface = &self.config.crates.interface;
+        let syscalls = &self.config.crates.syscalls;
+        let libsyscalls = &self.config.crates.libsyscalls;
         let domain_components_args = domain_components
             .iter()
             .map(|comp| match comp {
                 &DomainCreateComponent::Domain => {
-                    parse_quote! {s: Box<dyn syscalls::Syscall + Send + Sync>}
+                    parse_quote! {s: Box<dyn #syscalls::Syscall + Send + Sync>}
                 }
                 &DomainCreateComponent::Heap => {
-                    parse_quote! {heap: Box<dyn syscalls::Heap + Send + Sync>}
+                    parse_quote! {heap: Box<dyn #syscalls::Heap + Send + Sync>}
                 }
                 &DomainCreateComponent::MMap => {
-                    parse_quote! {mmap: Box<dyn syscalls::Mmap + Send + Sync>}
+                    parse_quote! {mmap: Box<dyn #syscalls::Mmap + Send + Sync>}
                 }
             })
             .collect::<Vec<syn::FnArg>>();
 
         let domain_components_init_statements = domain_components.iter().map(|comp| match comp {
             &DomainCreateComponent::Domain => {
-                parse_quote! {  libsyscalls::syscalls::init(s);  }
+                parse_quote! {  #libsyscalls::syscalls::init(s);  }
             }
             &DomainCreateComponent::Heap => {
-                parse_quote! {  interface::rref::init(heap, libsyscalls::syscalls::sys_get_current_domain_id());  }
+                parse_quote! {  #interface::rref::init(heap, #libsyscalls::syscalls::sys_get_current_domain_id());  }
             }
             &DomainCreateComponent::MMap => {
-                parse_quote! {  libsyscalls::syscalls::init_mmap(mmap);  }
+                parse_quote! {  #libsyscalls::syscalls::init_mmap(mmap);  }
             }
         }).collect::<Vec<syn::Stmt>>();
 
@@ -174,7 +183,13 @@ impl DomainEntrypointFactory {
                 syn::Type::Tuple(tuple_type) => {
                     let iter = tuple_type.elems.iter();
                     if iter.len() <= 1 {
-                        panic!("Return Type doesn't have enough elements, minimum of two required");
+                        return Err(Error::new_spanned(
+                            tuple_type,
+                            format!(
+                                "Method `{}` of domain `{}` returns too few elements; expecting the domain and at least one more",
+                                method.sig.ident, domain_name
+                            ),
+                        ));
                     }
 
                     let returned_types = iter.skip(1);
@@ -191,9 +206,25 @@ impl DomainEntrypointFactory {
 
                     return_type
                 }
-                _ => panic!("Domain must return a tuple!"),
+                _ => {
+                    return Err(Error::new_spanned(
+                        return_type,
+                        format!(
+                            "Method `{}` of domain `{}` must return a tuple",
+                            method.sig.ident, domain_name
+                        ),
+                    ))
+                }
             },
-            syn::ReturnType::Default => panic!("Domain must return a tuple!"),
+            syn::ReturnType::Default => {
+                return Err(Error::new_spanned(
+                    &method.sig,
+                    format!(
+                        "Method `{}` of domain `{}` must return a tuple",
+                        method.sig.ident, domain_name
+                    ),
+                ))
+            }
         };
 
         let main_rs: syn::File = parse_quote!(
@@ -215,34 +246,44 @@ impl DomainEntrypointFactory {
             }
         );
 
-        return main_rs.to_token_stream().to_string();
+        Ok(main_rs.to_token_stream().to_string())
     }
 
-    pub fn write_entrypoint_crate(&self, crate_name: &str, cargo_toml: String, main_rs: String) {
-        let output_path = self
-            .output_folder_path
-            .join(crate_name.to_owned() + "_entry_point");
-
-        std::fs::create_dir(&output_path);
-        std::fs::write(&output_path.join("Cargo.toml"), cargo_toml);
+    fn write_entrypoint_crate(
+        output_folder: &Path,
+        entrypoint: &EntrypointCrate,
+    ) -> std::result::Result<(), BuildError> {
+        for (path, content) in entrypoint.files() {
+            let path = output_folder.join(path);
+            // Every file of the crate is inside of its folder.
+            let folder = path.parent().unwrap_or(output_folder);
+            create_dir_all(folder).map_err(|source| BuildError::Io {
+                path: folder.to_owned(),
+                source,
+            })?;
+            std::fs::write(&path, content).map_err(|source| BuildError::Io { path, source })?;
+        }
+        Ok(())
+    }
 
-        std::fs::create_dir(&output_path.join("src"));
-        std::fs::write(&output_path.join("src/main.rs"), main_rs);
+    /// Takes the entrypoint crates generated so far.
+    pub fn take_entrypoints(&mut self) -> Vec<EntrypointCrate> {
+        std::mem::take(&mut self.entrypoints)
     }
 
     pub fn generate_domain_entrypoint_crates(
-        &self,
+        &mut self,
         domain_relative_path: &Path,
         domain_components: &Vec<DomainCreateComponent>,
         method: &TraitItemMethod,
-    ) {
+    ) -> Result<()> {
         let method_name = method.sig.ident.to_string();
         if !method_name.starts_with("create_domain_") {
             warn!(
                 "Method name {:} does not start with 'create_domain_', skipping entrypoint generation",
                 method_name
             );
-            return;
+            return Ok(());
         }
 
         let domain_name = method_name.trim_start_matches("create_domain_");
@@ -250,9 +291,14 @@ impl DomainEntrypointFactory {
         let cargo_toml = self.generate_entrypoint_cargo(domain_relative_path, domain_name);
         debug!("CARGO TOML: {:}", cargo_toml);
 
-        let main_rs = self.generate_entrypoint_main_rs(domain_name, domain_components, method);
+        let main_rs = self.generate_entrypoint_main_rs(domain_name, domain_components, method)?;
         debug!("main.rs: {:}", main_rs);
 
-        self.write_entrypoint_crate(domain_name, cargo_toml, main_rs);
+        self.entrypoints.push(EntrypointCrate {
+            name: domain_name.to_owned(),
+            cargo_toml,
+            main_rs,
+        });
+        Ok(())
     }
 }
@@ -0,0 +1,260 @@
+//! Errors in the IDL input.
+//!
+//! Every error points at the offending trait, method, or type in the input file, so the user gets
+//! a line and column instead of a dump of the AST.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::Span;
+use quote::ToTokens;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error in the IDL input.
+#[derive(Debug, Clone)]
+pub struct Error {
+    /// Where the error is in the input. `None` if the error is not about any particular tokens.
+    span: Option<Span>,
+    message: String,
+    /// The file that the error is in, if the input is read from more than one file.
+    file: Option<PathBuf>,
+}
+
+impl Error {
+    /// Creates an error that points at `tokens`.
+    pub fn new_spanned<T: ToTokens, M: fmt::Display>(tokens: T, message: M) -> Self {
+        let span = tokens
+            .into_token_stream()
+            .into_iter()
+            .next()
+            .map(|token| token.span());
+        Self {
+            span,
+            message: message.to_string(),
+            file: None,
+        }
+    }
+
+    /// Creates an error that doesn't point at anything in the input.
+    pub fn new<M: fmt::Display>(message: M) -> Self {
+        Self {
+            span: None,
+            message: message.to_string(),
+            file: None,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the file that the error is in, if it's found by `Diagnostics::locate`.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns the line(1-indexed) and column(1-indexed) of the error in the input.
+    pub fn line_column(&self) -> Option<(usize, usize)> {
+        self.span.map(|span| {
+            let start = span.start();
+            (start.line, start.column + 1)
+        })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line_column() {
+            Some((line, column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<syn::Error> for Error {
+    fn from(err: syn::Error) -> Self {
+        Self {
+            span: Some(err.span()),
+            message: err.to_string(),
+            file: None,
+        }
+    }
+}
+
+/// Collects the errors of a pass. A pass keeps going after an error so that all the errors in the
+/// input are reported at once.
+#[derive(Debug, Default)]
+pub struct Diagnostics(Vec<Error>);
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self(vec![])
+    }
+
+    pub fn push(&mut self, err: Error) {
+        self.0.push(err)
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.0.extend(other.0)
+    }
+
+    /// Takes the value out of `result`, or records the error and returns `None`.
+    pub fn check<T>(&mut self, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.push(err);
+                None
+            }
+        }
+    }
+
+    /// Same as `check` but for the results of other passes.
+    pub fn check_all<T>(&mut self, result: std::result::Result<T, Diagnostics>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(errs) => {
+                self.extend(errs);
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Error> {
+        self.0.iter()
+    }
+
+    /// Records the file of each error, for an input that is read from several `files`.
+    pub fn locate(&mut self, files: &SourceFiles) {
+        for err in &mut self.0 {
+            if let Some(span) = err.span {
+                err.file = files.file_of(span).map(Path::to_owned);
+            }
+        }
+    }
+
+    /// Returns `value` if there's no error.
+    pub fn finish<T>(self, value: T) -> std::result::Result<T, Diagnostics> {
+        if self.is_empty() {
+            Ok(value)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl From<Error> for Diagnostics {
+    fn from(err: Error) -> Self {
+        Self(vec![err])
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for err in &self.0 {
+            writeln!(f, "error: {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// The files that an input is read from, e.g. the modules of a crate, by the span of a token in
+/// each file. The spans of the tokens parsed from one file can be joined, and the spans from
+/// different files can't, which tells the files apart.
+#[derive(Debug, Clone, Default)]
+pub struct SourceFiles(Vec<(PathBuf, Span)>);
+
+impl SourceFiles {
+    pub fn new() -> Self {
+        Self(vec![])
+    }
+
+    /// Records that `ast` is parsed from the file at `path`.
+    pub fn push(&mut self, path: &Path, ast: &syn::File) {
+        if let Some(token) = ast.to_token_stream().into_iter().next() {
+            self.0.push((path.to_owned(), token.span()));
+        }
+    }
+
+    pub fn extend(&mut self, other: SourceFiles) {
+        self.0.extend(other.0)
+    }
+
+    /// Returns the file that `span` is in. `None` if it's not in any of the files, e.g. a span of
+    /// the generated code.
+    pub fn file_of(&self, span: Span) -> Option<&Path> {
+        self.0
+            .iter()
+            .find(|(_, file_span)| file_span.join(span).is_some())
+            .map(|(path, _)| path.as_path())
+    }
+}
+
+/// An error of running ngc with the `Builder`.
+#[derive(Debug)]
+pub enum BuildError {
+    /// The builder is not set up properly, e.g. no input is given.
+    Config(String),
+    /// Failed to read the input or write an output.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Errors in the input. `path` is `None` if the input is given as an AST. The errors in the
+    /// other files of an input crate, e.g. its modules, point at their own files.
+    Input {
+        path: Option<PathBuf>,
+        errors: Diagnostics,
+    },
+}
+
+/// Prints one error per line, e.g. `error: interface.rs:12:5: message`.
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Config(message) => writeln!(f, "error: {}", message),
+            BuildError::Io { path, source } => {
+                writeln!(f, "error: {}: {}", path.display(), source)
+            }
+            BuildError::Input { path, errors } => {
+                let path = match path {
+                    Some(path) => path.display().to_string(),
+                    None => String::from("<input>"),
+                };
+                for err in errors.iter() {
+                    let path = match err.file() {
+                        Some(file) => file.display().to_string(),
+                        None => path.clone(),
+                    };
+                    match err.line_column() {
+                        Some((line, column)) => {
+                            writeln!(f, "error: {}:{}:{}: {}", path, line, column, err.message())?
+                        }
+                        None => writeln!(f, "error: {}: {}", path, err.message())?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Io { source, .. } => Some(source),
+            BuildError::Input { errors, .. } => Some(errors),
+            BuildError::Config(_) => None,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! RedIDL New Generation Compiler(NGC).
+//!
+//! Use `Builder` to run the compiler, e.g. from a `build.rs`. The passes are exposed as well for
+//! other tools to reuse.
+
+#![feature(box_syntax, box_patterns)]
+
+mod builder;
+pub mod config;
+pub mod domain_create;
+pub mod domain_entrypoint;
+pub mod error;
+mod path_refactoring;
+pub mod proxy;
+pub mod type_resolution;
+pub mod validation;
+#[macro_use]
+mod utils;
+
+#[macro_use]
+extern crate derivative;
+
+pub use builder::{Builder, Generated, StaleOutput};
@@ -0,0 +1,1060 @@
+mod ownership;
+mod recovery;
+mod rpc;
+#[cfg(test)]
+mod tests;
+mod trait_object;
+
+use crate::config::Config;
+use crate::error::{Diagnostics, Error, Result};
+use crate::type_resolution::instantiation::{self, generic_params, instance_ident, is_generic};
+use crate::type_resolution::supertraits;
+use crate::type_resolution::symbol_tree::{Definition, SymbolTree};
+use crate::type_resolution::type_resolver::TypeResolver;
+use crate::{has_attribute, remove_attribute};
+
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_quote, FnArg, GenericArgument, Generics, Ident, ImplItem, ImplItemMethod, Item, ItemFn,
+    ItemTrait, Path, PathArguments, PathSegment, Token, TraitItem, TraitItemMethod, TraitItemType,
+    Type,
+};
+
+/// Generate the proxy for a IPC interface trait.
+pub fn generate_interface_proxy(
+    input: &mut ItemTrait,
+    module_path: &[Ident],
+    symbol_tree: &SymbolTree,
+    config: &Config,
+) -> std::result::Result<Option<Vec<Item>>, Diagnostics> {
+    // Noop if the input is not a proxy interface.
+    let interface_attr = &config.attributes.interface;
+    if !has_attribute!(input, interface_attr) {
+        return Ok(None);
+    }
+
+    // The interface lives in the module of the same path in the crate that we generate into.
+    let crate_module_path: Vec<Ident> = std::iter::once(format_ident!("crate"))
+        .chain(module_path.iter().skip(1).cloned())
+        .collect();
+    let recovery = recovery::parse_recovery(symbol_tree, config, input, &crate_module_path)?;
+
+    // Remove the interface attribute and add a comment so we know it's an interface
+    remove_attribute!(input, interface_attr);
+    input.attrs.push(
+        parse_quote! {#[doc = "redIDL Auto Generated: interface trait. Generations are below"]},
+    );
+
+    let trait_ident = &input.ident;
+    let proxy_ident = format_ident!("{}Proxy", trait_ident);
+
+    // A trait object can't have generic methods, and the trampolines can't be generic.
+    for item in &input.items {
+        if let TraitItem::Method(method) = item {
+            if !generic_params(&method.sig.generics).is_empty() {
+                return Err(Error::new_spanned(
+                    &method.sig,
+                    format!(
+                        "Generic method `{}` of interface `{}` can't be proxied; only lifetime parameters are supported",
+                        method.sig.ident, trait_ident
+                    ),
+                )
+                .into());
+            }
+        }
+    }
+    if recovery.is_some() && is_generic(input) {
+        return Err(Error::new_spanned(
+            &input.generics,
+            format!("Recoverable interface `{}` can't be generic", trait_ident),
+        )
+        .into());
+    }
+    let associated_types = find_associated_types(input)?;
+
+    let proxy = match &recovery {
+        Some(recovery) => {
+            let object = with_bindings(parse_quote!(#trait_ident), &associated_types);
+            recovery::generate_recoverable_proxy(
+                trait_ident,
+                &object,
+                &proxy_ident,
+                recovery,
+                config,
+            )
+        }
+        None => {
+            let (_, ty_generics, _) = input.generics.split_for_impl();
+            let object = with_bindings(parse_quote!(#trait_ident #ty_generics), &associated_types);
+            generate_proxy_struct(&object, &proxy_ident, &input.generics)
+        }
+    };
+
+    // A generic interface is proxied for each of its instantiations, e.g. `Queue<u64>`.
+    let module = match symbol_tree.lookup(&crate_module_path) {
+        Some(node) => match &node.borrow().terminal.as_ref().unwrap().definition {
+            Definition::Module(module) => module.clone(),
+            _ => unreachable!(),
+        },
+        None => unreachable!(),
+    };
+    let instances: Vec<(PathSegment, ItemTrait)> = match is_generic(input) {
+        true => instantiation::instances(symbol_tree, &module, input)?
+            .into_iter()
+            .map(|(path, instance)| (path.segments.last().unwrap().clone(), instance))
+            .collect(),
+        false => vec![(parse_quote!(#trait_ident), input.clone())],
+    };
+
+    // The proxy implements the interface and its supertraits, whose methods are forwarded to the
+    // domain the same way.
+    let mut supertraits = vec![];
+    for supertrait in supertraits::find_supertraits(&module, input)? {
+        let mut definition = supertrait.definition;
+        TypeResolver::new(symbol_tree.clone()).resolve_trait(supertrait.module, &mut definition)?;
+        // The trait object of the interface would have to bind the associated types of the
+        // supertraits as well.
+        if let Some(item) = definition
+            .items
+            .iter()
+            .find(|item| !matches!(item, TraitItem::Method(_)))
+        {
+            return Err(Error::new_spanned(
+                item,
+                format!(
+                    "Supertrait `{}` of interface `{}` can't be proxied; only methods are supported in supertraits",
+                    definition.ident, trait_ident
+                ),
+            )
+            .into());
+        }
+        supertraits.push((supertrait.path, definition));
+    }
+    let mut proxied_traits = vec![];
+    for (interface, definition) in &instances {
+        let mut proxy = interface.clone();
+        proxy.ident = proxy_ident.clone();
+        let instance = Instance {
+            prefix: instance_ident(&parse_quote!(#interface)),
+            interface: parse_quote!(#interface),
+            object: with_bindings(
+                parse_quote!(#interface),
+                &find_associated_types(definition)?,
+            ),
+            proxy: parse_quote!(#proxy),
+        };
+        proxied_traits.push(ProxiedTrait::new(
+            instance.interface.clone(),
+            instance.clone(),
+            None,
+            definition,
+            config,
+        )?);
+        for (path, supertrait) in &supertraits {
+            proxied_traits.push(ProxiedTrait::new(
+                path.clone(),
+                instance.clone(),
+                Some(supertrait.ident.clone()),
+                supertrait,
+                config,
+            )?);
+        }
+    }
+
+    let mut proxy_impl = quote!();
+    let mut trampolines = quote!();
+    for proxied in &proxied_traits {
+        proxy_impl.extend(generate_proxy_impl(
+            trait_ident,
+            proxied,
+            symbol_tree,
+            config,
+            recovery.as_ref(),
+        )?);
+        trampolines.extend(generate_trampolines(proxied, config, recovery.is_some()));
+    }
+
+    let proxy_comment_begin_str = format!(
+        "----------{} Proxy generation begins-------------",
+        trait_ident
+    );
+    let tramp_comment_begin_str = format!(
+        "----------{} Trampoline generation begins-------------",
+        trait_ident
+    );
+
+    let output: syn::File = parse_quote! {
+        #[doc = #proxy_comment_begin_str]
+        #proxy
+
+        #proxy_impl
+
+        #[doc = #tramp_comment_begin_str]
+        #trampolines
+    };
+
+    // The options are only known to ngc.
+    rpc::remove_rpc_attributes(input, config);
+
+    Ok(Some(output.items))
+}
+
+/// An instantiation of the interface, or the interface itself if it's not generic.
+#[derive(Clone)]
+struct Instance {
+    /// The prefix of the trampolines, e.g. `Queue_u64` for `Queue<u64>`.
+    prefix: Ident,
+    /// The interface, e.g. `Queue<u64>`.
+    interface: Path,
+    /// The trait of the domain object, which is the interface with its associated types bound to
+    /// their defaults, e.g. `Queue<u64, Buf = RRef<u64>>`.
+    object: Path,
+    /// The proxy of the interface, e.g. `QueueProxy<u64>`.
+    proxy: Path,
+}
+
+/// A trait that the proxy implements, i.e. the interface or one of its supertraits.
+struct ProxiedTrait {
+    /// The path to the trait from the module of the interface.
+    path: Path,
+    /// The instantiation of the interface whose proxy implements the trait.
+    instance: Instance,
+    /// The name of the trait if it's a supertrait, which tells its trampolines and proxy methods
+    /// apart from the ones of the interface.
+    supertrait: Option<Ident>,
+    /// The associated types, which all have defaults.
+    associated_types: Vec<TraitItemType>,
+    /// The methods that are forwarded to the domain, i.e. all but the ones with
+    /// `#[rpc(skip_proxy)]`.
+    methods: Vec<TraitItemMethod>,
+    /// `methods` without `&self` or `&mut self`.
+    cleaned_methods: Vec<TraitItemMethod>,
+    /// The RPC options of `methods`.
+    options: Vec<rpc::RpcOptions>,
+}
+
+impl ProxiedTrait {
+    fn new(
+        path: Path,
+        instance: Instance,
+        supertrait: Option<Ident>,
+        definition: &ItemTrait,
+        config: &Config,
+    ) -> Result<Self> {
+        let associated_types = definition
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                TraitItem::Type(ty) => Some(ty.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut methods = vec![];
+        let mut options = vec![];
+        for item in &definition.items {
+            if let TraitItem::Method(method) = item {
+                let method_options = rpc::parse_rpc_options(&definition.ident, method, config)?;
+                if !method_options.skip_proxy {
+                    methods.push(method.clone());
+                    options.push(method_options);
+                }
+            }
+        }
+
+        // Filter out `&self` and `&mut self`
+        let mut cleaned_methods = methods.clone();
+        for method in &mut cleaned_methods {
+            let mut args = Punctuated::<FnArg, Token![,]>::new();
+            for arg in &method.sig.inputs {
+                match arg {
+                    FnArg::Receiver(_) => {}
+                    FnArg::Typed(typed) => args.push(FnArg::Typed(typed.clone())),
+                }
+            }
+            method.sig.inputs = args;
+        }
+
+        Ok(Self {
+            path,
+            instance,
+            supertrait,
+            associated_types,
+            methods,
+            cleaned_methods,
+            options,
+        })
+    }
+
+    /// Returns the name of the trampoline of `method`, e.g. `NvmeBDev_flush`, `NvmeBDev_BDev_read`
+    /// for the supertrait `BDev`, or `Queue_u64_push` for the instantiation `Queue<u64>`.
+    fn trampoline_ident(&self, method: &Ident) -> Ident {
+        let prefix = &self.instance.prefix;
+        match &self.supertrait {
+            Some(supertrait) => format_ident!("{}_{}_{}", prefix, supertrait, method),
+            None => format_ident!("{}_{}", prefix, method),
+        }
+    }
+
+    /// Returns the name of the method of a recoverable proxy that does the actual call.
+    fn proxy_method_ident(&self, method: &Ident) -> Ident {
+        match &self.supertrait {
+            Some(supertrait) => format_ident!(
+                "{}_{}_proxy_",
+                supertrait.to_string().to_lowercase(),
+                method
+            ),
+            None => format_ident!("{}_proxy_", method),
+        }
+    }
+
+    /// Returns the name of the static that the `_err` trampoline of `method` of a recoverable proxy
+    /// counts the panics in, e.g. `NVMEBDEV_FLUSH_PANICS`.
+    fn panics_ident(&self, method: &Ident) -> Ident {
+        format_ident!(
+            "{}_PANICS",
+            self.trampoline_ident(method).to_string().to_uppercase()
+        )
+    }
+
+    /// Returns the call of `method` on `domain`, which is a `Box<dyn Interface>`. The methods of the
+    /// supertraits are called by their paths, since the supertraits may not be imported.
+    fn call(
+        &self,
+        domain: &proc_macro2::TokenStream,
+        method: &Ident,
+        args: &Punctuated<FnArg, Token![,]>,
+    ) -> proc_macro2::TokenStream {
+        match &self.supertrait {
+            Some(_) => {
+                let path = &self.path;
+                quote!(#path::#method(&*#domain, #args))
+            }
+            None => quote!(#domain.#method(#args)),
+        }
+    }
+
+    /// Returns the impls of the associated types with their defaults, e.g. `type Buf = Block;`.
+    fn associated_type_impls(&self) -> Vec<proc_macro2::TokenStream> {
+        self.associated_types
+            .iter()
+            .map(|ty| {
+                let ident = &ty.ident;
+                let (_, default) = ty.default.as_ref().unwrap();
+                quote!(type #ident = #default;)
+            })
+            .collect()
+    }
+}
+
+/// Returns the associated types of the interface `tr`. The proxy is used as a trait object, which
+/// binds the associated types, so `tr` can't have associated consts.
+///
+/// The associated types must have defaults: `Self::Buf` in the methods is resolved to the default,
+/// which the proxy implements `Buf` with, so the trait objects must bind `Buf` to the default too.
+/// Binding them per trait object, like the generic parameters, is not supported.
+fn find_associated_types(tr: &ItemTrait) -> Result<Vec<TraitItemType>> {
+    let mut types = vec![];
+    for item in &tr.items {
+        match item {
+            TraitItem::Method(_) => {}
+            TraitItem::Type(ty) if !ty.generics.params.is_empty() => {
+                return Err(Error::new_spanned(
+                    &ty.generics,
+                    format!(
+                        "Generic associated type `{}` of interface `{}` can't be proxied",
+                        ty.ident, tr.ident
+                    ),
+                ))
+            }
+            TraitItem::Type(ty) if ty.default.is_none() => {
+                return Err(Error::new_spanned(
+                    ty,
+                    format!(
+                        "Associated type `{}` of interface `{}` must have a default for its proxy to implement it with",
+                        ty.ident, tr.ident
+                    ),
+                ))
+            }
+            TraitItem::Type(ty) => types.push(ty.clone()),
+            TraitItem::Const(constant) => {
+                return Err(Error::new_spanned(
+                    constant,
+                    format!(
+                        "Associated const `{}` makes interface `{}` unusable as a trait object; consider a `const` in its module instead",
+                        constant.ident, tr.ident
+                    ),
+                ))
+            }
+            TraitItem::Macro(mac) => {
+                return Err(Error::new_spanned(
+                    mac,
+                    format!(
+                        "Macro in interface `{}` must be expanded before running ngc",
+                        tr.ident
+                    ),
+                ))
+            }
+            item => {
+                return Err(Error::new_spanned(
+                    item,
+                    format!("Unsupported item in interface `{}`", tr.ident),
+                ))
+            }
+        }
+    }
+    Ok(types)
+}
+
+/// Returns `path` with the associated types bound to their defaults, e.g. `Disk<Buf = Block>`.
+fn with_bindings(mut path: Path, types: &[TraitItemType]) -> Path {
+    if types.is_empty() {
+        return path;
+    }
+    let segment = path.segments.last_mut().unwrap();
+    if let PathArguments::None = segment.arguments {
+        segment.arguments = PathArguments::AngleBracketed(parse_quote!(<>));
+    }
+    if let PathArguments::AngleBracketed(arguments) = &mut segment.arguments {
+        for ty in types {
+            let ident = &ty.ident;
+            let (_, default) = ty.default.as_ref().unwrap();
+            arguments.args.push(parse_quote!(#ident = #default));
+        }
+    }
+    path
+}
+
+/// Generate the proxy struct, which can't recover its domain, of the trait object `object`. The
+/// proxy of a generic interface takes the same generic parameters.
+fn generate_proxy_struct(
+    object: &Path,
+    proxy_ident: &Ident,
+    generics: &Generics,
+) -> proc_macro2::TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        #[cfg(feature = "proxy")]
+        pub struct #proxy_ident #impl_generics #where_clause {
+            domain: ::alloc::boxed::Box<dyn #object>,
+            domain_id: u64,
+        }
+
+        #[cfg(feature = "proxy")]
+        unsafe impl #impl_generics Sync for #proxy_ident #ty_generics #where_clause {}
+        #[cfg(feature = "proxy")]
+        unsafe impl #impl_generics Send for #proxy_ident #ty_generics #where_clause {}
+
+        #[cfg(feature = "proxy")]
+        impl #impl_generics #proxy_ident #ty_generics #where_clause {
+            pub fn new(domain_id: u64, domain: ::alloc::boxed::Box<dyn #object>) -> Self {
+                Self {
+                    domain,
+                    domain_id,
+                }
+            }
+        }
+    }
+}
+
+/// Generate the proxy itself and the impl block for it.
+pub fn generate_proxy(
+    domain_creates: Vec<(Path, ItemTrait)>,
+    symbol_tree: &SymbolTree,
+    config: &Config,
+) -> std::result::Result<Vec<Item>, Diagnostics> {
+    let mut errors = Diagnostics::new();
+    let mut generated_items = vec![];
+    let proxy_struct_ident = format_ident!("ProxyObject");
+
+    // Create a mapping between the names and the interfaces.
+    let domain_creates: Vec<(Ident, Path, ItemTrait)> = domain_creates
+        .into_iter()
+        .filter(|(path, _)| {
+            // Filter out "CreateProxy" since we don't proxy the proxy creation.
+            path.segments.last().unwrap().ident != "CreateProxy"
+        })
+        .map(|(mut path, definition)| {
+            // Make sure the path starts with `crate` since proxy will be generated inside of interface.
+            path.segments.first_mut().unwrap().ident = format_ident!("crate");
+
+            // The first ident is skipped because it's redundant.
+            let path_str = path
+                .segments
+                .iter()
+                .skip(1)
+                .map(|seg| seg.ident.to_string())
+                .collect::<Vec<String>>()
+                .join("_");
+            let name = format_ident!("{}", path_str);
+            (name, path, definition)
+        })
+        .collect();
+
+    // Generate each struct field.
+    let struct_fields: Vec<FnArg> = domain_creates
+        .iter()
+        .map(|(name, path, _)| {
+            parse_quote! {
+                #name: ::alloc::sync::Arc<dyn #path>
+            }
+        })
+        .collect();
+
+    // Generate the struct.
+    generated_items.push(Item::Struct(parse_quote! {
+        #[cfg(feature = "proxy")]
+        #[derive(Clone)]
+        pub struct #proxy_struct_ident {
+                #(#struct_fields),*
+        }
+    }));
+
+    // Generate unsafe impl for Send and Sync
+    generated_items.push(Item::Impl(parse_quote! {
+        #[cfg(feature = "proxy")]
+        unsafe impl Send for #proxy_struct_ident {}
+    }));
+    generated_items.push(Item::Impl(parse_quote! {
+        #[cfg(feature = "proxy")]
+        unsafe impl Sync for #proxy_struct_ident {}
+    }));
+
+    // Generate the main impl block.
+    let struct_fields_names_only: Vec<_> = domain_creates.iter().map(|(name, _, _)| name).collect();
+    generated_items.push(Item::Impl(parse_quote! {
+        #[cfg(feature = "proxy")]
+        impl #proxy_struct_ident {
+            pub fn new(#(#struct_fields),*) -> Self {
+                Self {
+                    #(#struct_fields_names_only),*
+                }
+            }
+        }
+    }));
+
+    // Generate impl block for trait Proxy
+    let as_fns: Vec<ImplItemMethod> = domain_creates
+        .iter()
+        .map(|(name, path, _)| {
+            let ident = format_ident!("as_{}", name);
+            parse_quote! {
+                fn #ident(&self) -> ::alloc::sync::Arc<dyn #path> {
+                    ::alloc::sync::Arc::new(self.clone())
+                }
+            }
+        })
+        .collect();
+    generated_items.push(Item::Impl(parse_quote! {
+        #[cfg(feature = "proxy")]
+        impl crate::proxy::Proxy for #proxy_struct_ident {
+            // TODO: figure out how to do this without Arc::new every time
+            #(#as_fns)*
+        }
+    }));
+
+    // Generate impls for domain create traits.
+    for (name, path, tr) in &domain_creates {
+        // Generate the fns inside of the impl block.
+        let impl_fns: Vec<ImplItem> = tr
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                TraitItem::Method(md) => {
+                    errors.check(generate_domain_create_proxy(
+                        name,
+                        path,
+                        md,
+                        symbol_tree,
+                        config,
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+
+        // Generate the impl block
+        generated_items.push(Item::Impl(parse_quote! {
+            #[cfg(feature = "proxy")]
+            impl #path for #proxy_struct_ident {
+                #(#impl_fns)*
+            }
+        }));
+    }
+
+    // Return the generated items.
+    errors.finish(generated_items)
+}
+
+/// Generate the method of `ProxyObject` that creates the domain with domain create method `md` of
+/// `path` and wraps the returned interface in its proxy.
+fn generate_domain_create_proxy(
+    name: &Ident,
+    path: &Path,
+    md: &TraitItemMethod,
+    symbol_tree: &SymbolTree,
+    config: &Config,
+) -> Result<ImplItem> {
+    let sig = &md.sig;
+    let ident = &sig.ident;
+    let invalid_return_type = || {
+        Error::new_spanned(
+            sig,
+            format!(
+                "Invalid return type for domain create method `{}`; expecting `(Box<dyn syscalls::Domain>, Box<dyn Interface>)` or `(Box<dyn syscalls::Domain>, ())`",
+                ident
+            ),
+        )
+    };
+
+    // Extract the return type of the usr_ep and generate the return statement for the proxy.
+    // We only support zero or one trait object currently. Nested and tuples are not supported.
+    // Note that the domain create's return type follows the format of
+    // "(Box<dyn Domain>, ()|Box<dyn SomeTraitObject>)"
+    let usr_ep_rtn = match &sig.output {
+        syn::ReturnType::Type(_, ty) => match &**ty {
+            syn::Type::Tuple(tuple) if tuple.elems.len() == 2 => &tuple.elems[1],
+            _ => return Err(invalid_return_type()),
+        },
+        syn::ReturnType::Default => return Err(invalid_return_type()),
+    };
+    let proxy_rtn_stmt: syn::Stmt = match usr_ep_rtn {
+        syn::Type::Path(ty) if ty.path.segments.last().unwrap().ident == "Box" => {
+            let usr_ep_rtn_trait = crate::utils::get_type_inside_of_box(usr_ep_rtn)?;
+            let tr = match usr_ep_rtn_trait {
+                syn::Type::TraitObject(tr) if tr.bounds.len() == 1 => {
+                    tr.bounds.iter().next().unwrap()
+                }
+                _ => {
+                    return Err(Error::new_spanned(
+                        usr_ep_rtn,
+                        format!(
+                            "Expecting a boxed trait object of an interface as the second return value of domain create method `{}`",
+                            ident
+                        ),
+                    ))
+                }
+            };
+            let tr = match tr {
+                syn::TypeParamBound::Trait(tr) => tr,
+                syn::TypeParamBound::Lifetime(_) => return Err(invalid_return_type()),
+            };
+            // The generated proxy is located in the same
+            // module as the trait.
+            // It's path should be "trait_module::TraitPath" + "Proxy".
+            // The generic arguments of a generic proxy are inferred.
+            let mut tr_proxy = tr.path.clone();
+            let tr_proxy_ident = tr_proxy.segments.last_mut().unwrap();
+            tr_proxy_ident.ident = format_ident!("{}Proxy", tr_proxy_ident.ident);
+            tr_proxy_ident.arguments = PathArguments::None;
+            // A recoverable proxy keeps the domain and its create to recreate the domain.
+            match recovery::recovery_of_trait_object(symbol_tree, config, usr_ep_rtn_trait) {
+                Some(recovery) if recovery.create_trait == *path => parse_quote! {
+                    {
+                        let proxy_ = #tr_proxy::new_recoverable(::alloc::sync::Arc::clone(&self.#name), domain_, rtn_);
+                        return (proxy_.domain_handle(), ::alloc::boxed::Box::new(proxy_));
+                    }
+                },
+                _ => parse_quote! {
+                    return (domain_, ::alloc::boxed::Box::new(#tr_proxy::new(domain_id_, rtn_)));
+                },
+            }
+        }
+        syn::Type::Tuple(tu) if tu.elems.is_empty() => parse_quote! {
+            return (domain_, rtn_);
+        },
+        _ => return Err(invalid_return_type()),
+    };
+
+    let selfless_args = super::utils::get_selfless_args(sig.inputs.iter());
+    Ok(ImplItem::Method(parse_quote! {
+        #sig {
+            let (domain_, rtn_) = self.#name.#ident(#(#selfless_args),*);
+            let domain_id_ = domain_.get_domain_id();
+            #proxy_rtn_stmt
+        }
+    }))
+}
+
+/// Generate trampolines for the methods of `proxied`, which the proxy of an instantiation of the
+/// interface implements.
+/// The `_err` trampolines of a `recoverable` proxy count the panics, so the proxy knows when to
+/// recover.
+fn generate_trampolines(
+    proxied: &ProxiedTrait,
+    config: &Config,
+    recoverable: bool,
+) -> proc_macro2::TokenStream {
+    let console = &config.crates.console;
+    let unwind = &config.crates.unwind;
+    let interface = &proxied.instance.object;
+    let trampolines = proxied.cleaned_methods.iter()
+        .zip(&proxied.options)
+        .filter(|(_, options)| !options.no_trampoline)
+        .map(|(method, _)| {
+            let sig = &method.sig;
+            let ident = &sig.ident;
+            let args = &sig.inputs;
+            let return_ty = &sig.output;
+
+            let domain_variable_ident = format_ident!("redidl_generated_domain_{}", proxied.instance.prefix.to_string().to_lowercase());
+            let trampoline_ident = proxied.trampoline_ident(ident);
+            let trampoline_err_ident = format_ident!("{}_err", trampoline_ident);
+            let trampoline_addr_ident = format_ident!("{}_addr", trampoline_ident);
+            let trampoline_tramp_ident = format_ident!("{}_tramp", trampoline_ident);
+            let call = match &proxied.supertrait {
+                Some(_) => proxied.call(&quote!((*#domain_variable_ident)), ident, args),
+                None => quote!((&**#domain_variable_ident).#ident(#args)),
+            };
+            let (panics, count_panic) = if recoverable {
+                let panics_ident = proxied.panics_ident(ident);
+                (
+                    Some(quote! {
+                        // The number of calls that panicked, which tells the proxy to recover.
+                        #[cfg(feature = "trampoline")]
+                        #[cfg(feature = "proxy")]
+                        static #panics_ident: ::core::sync::atomic::AtomicU64 = ::core::sync::atomic::AtomicU64::new(0);
+                    }),
+                    Some(quote! {
+                        #panics_ident.fetch_add(1, ::core::sync::atomic::Ordering::SeqCst);
+                    }),
+                )
+            } else {
+                (None, None)
+            };
+
+            quote! {
+                // Wrapper of the original function.
+                // The trampoline should call this after saving the continuation stack.
+                #[cfg(feature = "trampoline")]
+                #[cfg(feature = "proxy")]
+                #[no_mangle]
+                extern fn #trampoline_ident(#domain_variable_ident: &alloc::boxed::Box<dyn #interface>, #args) #return_ty {
+                    #call
+                }
+    
+                // When the call panics, the continuation stack will jump this function.
+                // This function will return a `RpcError::panic` to the caller domain.
+                #[cfg(feature = "trampoline")]
+                #[cfg(feature = "proxy")]
+                #[no_mangle]
+                extern fn #trampoline_err_ident(#domain_variable_ident: &alloc::boxed::Box<dyn #interface>, #args) #return_ty  {
+                    #[cfg(feature = "proxy-log-error")]
+                    #console::println!("proxy: {} aborted", stringify!(#ident));
+
+                    #count_panic
+                    Err(unsafe{crate::rpc::RpcError::panic()})
+                }
+
+                #panics
+    
+                // A workaround to get the address of the error function
+                #[cfg(feature = "trampoline")]
+                #[cfg(feature = "proxy")]
+                #[no_mangle]
+                extern "C" fn #trampoline_addr_ident() -> u64 {
+                    #trampoline_err_ident as u64
+                }
+                
+                // FFI to the trampoline.
+                #[cfg(feature = "proxy")]
+                #[cfg(feature = "trampoline")]
+
+                extern {
+                    fn #trampoline_tramp_ident(#domain_variable_ident: &alloc::boxed::Box<dyn #interface>, #args) #return_ty;
+                }
+    
+                #[cfg(feature = "proxy")]
+                #[cfg(feature = "trampoline")]
+                #unwind::trampoline!(#trampoline_ident);
+            }
+        });
+
+    quote! {
+        #(#trampolines)*
+    }
+}
+
+/// Generate proxy implementation, e.g., `impl DomC for DomCProxy`.
+/// The methods of a recoverable proxy recover the domain and then forward to the actual proxy
+/// implementations, which are generated in a separate impl block.
+/// `proxied` is the interface `trait_ident`, or one of its instantiations, or one of its supertraits.
+fn generate_proxy_impl(
+    trait_ident: &Ident,
+    proxied: &ProxiedTrait,
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    recovery: Option<&recovery::Recovery>,
+) -> Result<proc_macro2::TokenStream> {
+    let (methods, cleaned_methods) = (&proxied.methods, &proxied.cleaned_methods);
+    let trait_path = &proxied.path;
+    let proxy_ident = &proxied.instance.proxy;
+    let associated_types = proxied.associated_type_impls();
+    let recovery = match recovery {
+        Some(recovery) => recovery,
+        None => {
+            let proxy_impls = methods
+                .iter()
+                .zip(cleaned_methods)
+                .zip(&proxied.options)
+                .map(|((method, cleaned), options)| {
+                    generate_proxy_impl_one(
+                        proxied,
+                        method,
+                        cleaned,
+                        options,
+                        symbol_tree,
+                        config,
+                        false,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(quote! {
+                #[cfg(feature = "proxy")]
+                impl #trait_path for #proxy_ident {
+                    #(#associated_types)*
+
+                    #(#proxy_impls)*
+                }
+            });
+        }
+    };
+
+    let proxy_impls = methods
+        .iter()
+        .zip(cleaned_methods)
+        .zip(&proxied.options)
+        .map(|((method, cleaned), options)| {
+            generate_proxy_impl_one(proxied, method, cleaned, options, symbol_tree, config, true)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut recoverable_impls = vec![];
+    for ((method, cleaned), options) in methods.iter().zip(cleaned_methods).zip(&proxied.options) {
+        let sig = &method.sig;
+        let arg_idents = cleaned
+            .sig
+            .inputs
+            .iter()
+            .map(|arg| match arg {
+                FnArg::Typed(arg) => match arg.pat.as_ref() {
+                    syn::Pat::Ident(pat) => Ok(&pat.ident),
+                    pat => Err(Error::new_spanned(
+                        pat,
+                        format!(
+                            "Unsupported argument pattern in method `{}` of recoverable interface `{}`; expecting an identifier",
+                            sig.ident, trait_ident
+                        ),
+                    )),
+                },
+                FnArg::Receiver(_) => unreachable!(),
+            })
+            .collect::<Result<Vec<&Ident>>>()?;
+        // The arguments must be passed again on retry.
+        let retry = recovery.retry
+            && cleaned.sig.inputs.iter().all(|arg| match arg {
+                FnArg::Typed(arg) => recovery::is_reusable(&arg.ty),
+                FnArg::Receiver(_) => true,
+            });
+        // A panic is only caught, and counted, by the trampolines.
+        let panics = if options.no_trampoline {
+            None
+        } else {
+            Some(proxied.panics_ident(&sig.ident))
+        };
+        recoverable_impls.push(recovery::generate_recoverable_method(
+            &sig.inputs,
+            &arg_idents,
+            &sig.output,
+            &sig.ident,
+            &proxied.proxy_method_ident(&sig.ident),
+            panics.as_ref(),
+            retry,
+        ));
+    }
+
+    Ok(quote! {
+        #[cfg(feature = "proxy")]
+        impl #trait_path for #proxy_ident {
+            #(#associated_types)*
+
+            #(#recoverable_impls)*
+        }
+
+        #[cfg(feature = "proxy")]
+        impl #proxy_ident {
+            #(#proxy_impls)*
+        }
+    })
+}
+
+/// Generate the proxy implementation for one single method of `proxied`.
+/// The implementation of a `recoverable` proxy is named `<method>_proxy_` and accesses the domain
+/// through the lock.
+fn generate_proxy_impl_one(
+    proxied: &ProxiedTrait,
+    method: &TraitItemMethod,
+    cleaned_method: &TraitItemMethod,
+    options: &rpc::RpcOptions,
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    recoverable: bool,
+) -> Result<ItemFn> {
+    let libsyscalls = &config.crates.libsyscalls;
+    let console = &config.crates.console;
+    let time = &config.crates.time;
+    let sig = &method.sig;
+    let ident = &sig.ident;
+    let trampoline_ident = format_ident!("{}_tramp", proxied.trampoline_ident(ident));
+    let args = &sig.inputs;
+    let cleaned_args = &cleaned_method.sig.inputs;
+    let return_ty = &sig.output;
+
+    let (fn_ident, get_domain, domain, domain_id) = if recoverable {
+        (
+            proxied.proxy_method_ident(ident),
+            Some(quote! {
+                let domain_ = self.domain.read();
+            }),
+            quote!((*domain_)),
+            quote!(self.domain_id.load(::core::sync::atomic::Ordering::SeqCst)),
+        )
+    } else {
+        (
+            ident.clone(),
+            None,
+            quote!(self.domain),
+            quote!(self.domain_id),
+        )
+    };
+
+    // Move the `RRef`ed arguments to the callee and the `RRef`ed return values back to the caller.
+    let (canonical_args, canonical_return_ty) =
+        ownership::canonicalize(symbol_tree, cleaned_args.iter(), return_ty)?;
+    let move_args_to_callee = ownership::move_args_to(canonical_args.iter(), &domain_id);
+    let move_rtn_to_caller =
+        ownership::move_return_to(&quote!(r), &canonical_return_ty, &quote!(caller_domain_id_));
+    // Bracket the call with borrow and forfeit on the immutably borrowed `RRef`s.
+    let (borrow_args, forfeit_args) = ownership::borrow_args(canonical_args.iter());
+    // Wrap the trait objects passed in by the caller and the ones returned by the callee.
+    let wrap_args = trait_object::wrap_args(
+        symbol_tree,
+        config,
+        &cleaned_args.iter().collect::<Vec<_>>(),
+        &quote!(caller_domain_id_),
+    );
+    let wrap_rtn =
+        trait_object::wrap_return(symbol_tree, config, &quote!(r), return_ty, &domain_id);
+    let get_caller_domain_id = if move_rtn_to_caller.is_empty() && wrap_args.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            let caller_domain_id_ = #libsyscalls::syscalls::sys_get_current_domain_id();
+        })
+    };
+
+    let call = proxied.call(&domain, ident, cleaned_args);
+    let call = if options.no_trampoline {
+        quote! {
+            let r = #call;
+        }
+    } else {
+        quote! {
+            #[cfg(not(feature = "trampoline"))]
+            let r = #call;
+            #[cfg(feature = "trampoline")]
+            let r = unsafe { #trampoline_ident(&#domain, #cleaned_args) };
+
+            #[cfg(feature = "trampoline")]
+            unsafe {
+                #libsyscalls::syscalls::sys_discard_cont();
+            }
+        }
+    };
+    // The call is only timed and its errors logged with `proxy-log-error`, so the interface crate
+    // depends on the `time` crate only with the feature.
+    let (start_timer, check_slow) = match options.timeout_ms {
+        Some(timeout_ms) => (
+            Some(quote! {
+                #[cfg(feature = "proxy-log-error")]
+                let start_ns_ = #time::get_ns_time();
+            }),
+            Some(quote! {
+                #[cfg(feature = "proxy-log-error")]
+                {
+                    let elapsed_ms_ = (#time::get_ns_time() - start_ns_) / 1_000_000;
+                    if elapsed_ms_ > #timeout_ms {
+                        #console::println!(
+                            "proxy: {} took {} ms, over {} ms",
+                            stringify!(#ident),
+                            elapsed_ms_,
+                            #timeout_ms
+                        );
+                    }
+                }
+            }),
+        ),
+        None => (None, None),
+    };
+    let log_error = if options.log_errors {
+        Some(quote! {
+            #[cfg(feature = "proxy-log-error")]
+            if r.is_err() {
+                #console::println!("proxy: {} returned an error", stringify!(#ident));
+            }
+        })
+    } else {
+        None
+    };
+
+    Ok(parse_quote! {
+        fn #fn_ident(#args) #return_ty {
+            // This is no longer needed because we can get domain_id from binary region.
+            // move thread to next domain
+            // let caller_domain = unsafe { ::libsyscalls::syscalls::sys_update_current_domain_id(self.domain_id) };
+
+            #get_domain
+
+            #get_caller_domain_id
+
+            // The trait objects passed in live in the caller domain.
+            #(#wrap_args)*
+
+            // Transfer the ownership of the `RRef`ed arguments to the callee domain.
+            #(#move_args_to_callee)*
+
+            // Increment the borrow count of the borrowed `RRef`s for the duration of the call.
+            #(#borrow_args)*
+
+            #start_timer
+            #call
+            #check_slow
+            #log_error
+
+            // Decrement the borrow count of the borrowed `RRef`s. If the callee panics, the
+            // `_err` trampoline returns to here as well, so the count is restored on both paths.
+            #(#forfeit_args)*
+
+            // move thread back
+            // unsafe { ::libsyscalls::syscalls::sys_update_current_domain_id(caller_domain) };
+
+            // Transfer the ownership of the returned `RRef`s back to the caller domain.
+            #(#move_rtn_to_caller)*
+
+            // The trait objects returned live in the callee domain.
+            #wrap_rtn
+
+            r
+        }
+    })
+}
+
+/// Returns the first generic type argument, e.g. `T` in `Result<T, E>`.
+fn first_type_argument(arguments: &PathArguments) -> Option<&Type> {
+    match arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+//! Generation of the statements that transfer the ownership of `RRef`ed values across a RPC call.
+//!
+//! All types passed in here must already be canonical, see `canonicalize`, so that the shared heap
+//! types can be recognized by their fully-qualified paths.
+
+use crate::error::Result;
+use crate::type_resolution::rref_kind::RRefKind;
+use crate::type_resolution::rrefed_finder::RRefedFinder;
+use crate::type_resolution::symbol_tree::SymbolTree;
+
+use super::first_type_argument;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{FnArg, Pat, ReturnType, Type};
+
+/// Returns `args` and `return_ty`, whose types are resolved by the `TypeResolver`, with their types
+/// expanded to the canonical types, so that the `RRef`s behind type aliases, e.g. `Block` of
+/// `type Block = RRef<[u8; 4096]>`, are recognized.
+pub fn canonicalize<'a, T: Iterator<Item = &'a FnArg>>(
+    symbol_tree: &SymbolTree,
+    args: T,
+    return_ty: &ReturnType,
+) -> Result<(Vec<FnArg>, ReturnType)> {
+    // The resolved types are fully-qualified, so they can be expanded from any module.
+    let mut finder = RRefedFinder::new(symbol_tree.clone());
+    let module = symbol_tree.root_module();
+    let args = args
+        .map(|arg| {
+            let mut arg = arg.clone();
+            if let FnArg::Typed(arg) = &mut arg {
+                *arg.ty = finder.resolve_type(module.clone(), &arg.ty)?;
+            }
+            Ok(arg)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut return_ty = return_ty.clone();
+    if let ReturnType::Type(_, ty) = &mut return_ty {
+        **ty = finder.resolve_type(module, ty)?;
+    }
+    Ok((args, return_ty))
+}
+
+/// Generates the statements that move all `RRef`ed arguments passed by value to `domain_id`.
+pub fn move_args_to<'a, T: Iterator<Item = &'a FnArg>>(
+    args: T,
+    domain_id: &TokenStream,
+) -> Vec<TokenStream> {
+    args.filter_map(|arg| match arg {
+        FnArg::Receiver(_) => None,
+        FnArg::Typed(arg) => match arg.pat.as_ref() {
+            Pat::Ident(pat) => {
+                let ident = &pat.ident;
+                Some(move_to(&quote!(#ident), &arg.ty, domain_id))
+            }
+            _ => None,
+        },
+    })
+    .flatten()
+    .collect()
+}
+
+/// Generates the statements that increment and decrement the borrow count of every `&RRef`,
+/// `&RRefArray` and `&RRefDeque` argument. The first list is to be run before the call and the
+/// second one after the call.
+pub fn borrow_args<'a, T: Iterator<Item = &'a FnArg>>(
+    args: T,
+) -> (Vec<TokenStream>, Vec<TokenStream>) {
+    args.filter_map(|arg| match arg {
+        FnArg::Receiver(_) => None,
+        FnArg::Typed(arg) => match (arg.pat.as_ref(), arg.ty.as_ref()) {
+            (Pat::Ident(pat), Type::Reference(reference))
+                if reference.mutability.is_none()
+                    && RRefKind::of_type(&reference.elem).is_some() =>
+            {
+                let ident = &pat.ident;
+                Some((quote!(#ident.borrow();), quote!(#ident.forfeit();)))
+            }
+            _ => None,
+        },
+    })
+    .unzip()
+}
+
+/// Generates the statements that move the `RRef`ed values in the returned value `rtn` to
+/// `domain_id`.
+pub fn move_return_to(
+    rtn: &TokenStream,
+    return_ty: &ReturnType,
+    domain_id: &TokenStream,
+) -> Vec<TokenStream> {
+    match return_ty {
+        ReturnType::Default => vec![],
+        ReturnType::Type(_, ty) => move_to(rtn, ty, domain_id),
+    }
+}
+
+/// Generates the statements that move all `RRef`ed values in `expr`, which is of type `ty`, to
+/// `domain_id`. `RRef`s that are behind a reference are not moved.
+pub fn move_to(expr: &TokenStream, ty: &Type, domain_id: &TokenStream) -> Vec<TokenStream> {
+    move_to_recursive(expr, ty, domain_id, 0)
+}
+
+fn move_to_recursive(
+    expr: &TokenStream,
+    ty: &Type,
+    domain_id: &TokenStream,
+    depth: usize,
+) -> Vec<TokenStream> {
+    if RRefKind::of_type(ty).is_some() {
+        return vec![quote! {
+            #expr.move_to(#domain_id);
+        }];
+    }
+
+    match ty {
+        Type::Path(path) => {
+            // Look into the variants that carry a value, e.g. `Some(x)` or `Ok(x)`.
+            let last_segment = path.path.segments.last().unwrap();
+            let variant = match last_segment.ident.to_string().as_str() {
+                "Option" => quote!(Some),
+                "Result" | "RpcResult" => quote!(Ok),
+                _ => return vec![],
+            };
+            let inner_ty = match first_type_argument(&last_segment.arguments) {
+                Some(inner_ty) => inner_ty,
+                None => return vec![],
+            };
+            let binding = format_ident!("rref_{}_", depth);
+            let moves = move_to_recursive(&quote!(#binding), inner_ty, domain_id, depth + 1);
+            if moves.is_empty() {
+                return vec![];
+            }
+            vec![quote! {
+                if let #variant(#binding) = &#expr {
+                    #(#moves)*
+                }
+            }]
+        }
+        Type::Tuple(tuple) => tuple
+            .elems
+            .iter()
+            .enumerate()
+            .flat_map(|(i, elem)| {
+                let index = syn::Index::from(i);
+                move_to_recursive(&quote!(#expr.#index), elem, domain_id, depth + 1)
+            })
+            .collect(),
+        Type::Array(arr) => {
+            let binding = format_ident!("rref_{}_", depth);
+            let moves = move_to_recursive(&quote!(#binding), &arr.elem, domain_id, depth + 1);
+            if moves.is_empty() {
+                return vec![];
+            }
+            vec![quote! {
+                for #binding in #expr.iter() {
+                    #(#moves)*
+                }
+            }]
+        }
+        Type::Paren(paren) => move_to_recursive(expr, &paren.elem, domain_id, depth),
+        // References are borrowed, not moved.
+        _ => vec![],
+    }
+}
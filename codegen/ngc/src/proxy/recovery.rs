@@ -0,0 +1,402 @@
+//! Automatic domain recovery for the proxies of `#[interface(recover = "path::to::CreateX")]`.
+//!
+//! When the callee panics, the `_err` trampoline returns an `RpcError` to the proxy and counts the
+//! panic in the `*_PANICS` static of the method. An `Err` alone doesn't mean that the callee is
+//! dead, since a healthy callee may pass along the `RpcError` of its own calls. So a recoverable
+//! proxy recovers only if the count changed during the call: it calls the matching
+//! `recreate_domain_*` method of the domain create trait, swaps in the new domain, and optionally
+//! retries the call once.
+
+use crate::config::Config;
+use crate::domain_create::RECREATE_DOMAIN_PREFIX;
+use crate::error::{Error, Result};
+use crate::type_resolution::symbol_tree::{Definition, SymbolTree};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, FnArg, Ident, Item, ItemTrait, Lit, Path, ReturnType, TraitItem, Type};
+
+const RECOVER_ATTR: &str = "recover";
+const RETRY_ATTR: &str = "retry";
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128",
+    "isize", "f32", "f64",
+];
+
+/// How a proxy recovers its domain.
+#[derive(Debug, Clone)]
+pub struct Recovery {
+    /// Fully-qualified path to the domain create trait.
+    pub create_trait: Path,
+    /// The `recreate_domain_*` method in the domain create trait that recreates the domain.
+    pub recreate_method: Ident,
+    /// Whether the call is retried once after the domain is recovered.
+    pub retry: bool,
+}
+
+/// Parses the recovery configuration from the `#[interface]` attribute of `tr`, which lives in
+/// `module`, e.g. `crate::foo`.
+/// Returns `None` if the interface is not recoverable.
+pub fn parse_recovery(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    tr: &ItemTrait,
+    module: &[Ident],
+) -> Result<Option<Recovery>> {
+    let interface_attrs: Vec<_> = tr
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident(&config.attributes.interface))
+        .cloned()
+        .collect();
+    let attrs = crate::utils::create_attribue_map(&interface_attrs)?;
+
+    let (create_trait, create_trait_lit) = match attrs.get(RECOVER_ATTR) {
+        None => return Ok(None),
+        Some(Some(Lit::Str(path))) => match path.parse::<Path>() {
+            Ok(create_trait) => (create_trait, path),
+            Err(err) => {
+                return Err(Error::new_spanned(
+                    path,
+                    format!(
+                        "Invalid `{}` path for interface `{}`: {}",
+                        RECOVER_ATTR, tr.ident, err
+                    ),
+                ))
+            }
+        },
+        Some(_) => {
+            return Err(Error::new_spanned(
+                &tr.ident,
+                format!(
+                    "Expecting `{} = \"path::to::CreateX\"` for interface `{}`",
+                    RECOVER_ATTR, tr.ident
+                ),
+            ))
+        }
+    };
+    let retry = attrs.contains_key(RETRY_ATTR);
+
+    // Find the domain create trait.
+    let node = symbol_tree.resolve(module, &create_trait).ok_or_else(|| {
+        Error::new_spanned(
+            create_trait_lit,
+            format!(
+                "Unable to find domain create trait `{}` for interface `{}`",
+                create_trait_lit.value(),
+                tr.ident
+            ),
+        )
+    })?;
+    let node = node.borrow();
+    let create_tr = match node.terminal.as_ref().map(|terminal| &terminal.definition) {
+        Some(Definition::Type(Item::Trait(create_tr))) => create_tr,
+        _ => {
+            return Err(Error::new_spanned(
+                create_trait_lit,
+                format!(
+                    "`{}` in the `{}` of interface `{}` is not a trait",
+                    create_trait_lit.value(),
+                    RECOVER_ATTR,
+                    tr.ident
+                ),
+            ))
+        }
+    };
+    let create_trait_path = &node.path;
+
+    // Find the method that recreates the domain, i.e. `recreate_domain_*` that returns the
+    // interface.
+    let recreate_method = create_tr.items.iter().find_map(|item| match item {
+        TraitItem::Method(method)
+            if method
+                .sig
+                .ident
+                .to_string()
+                .starts_with(RECREATE_DOMAIN_PREFIX)
+                && returns_interface(&method.sig.output, &tr.ident) =>
+        {
+            Some(method)
+        }
+        _ => None,
+    });
+    let recreate_method = recreate_method.ok_or_else(|| {
+        Error::new_spanned(
+            &create_tr.ident,
+            format!(
+                "Domain create trait `{}` has no `{}*` method that returns `Box<dyn {}>`",
+                create_tr.ident, RECREATE_DOMAIN_PREFIX, tr.ident
+            ),
+        )
+    })?;
+
+    // The proxy only has the old domain to give to the recreate method.
+    let args = crate::utils::get_selfless_args(recreate_method.sig.inputs.iter());
+    if args.len() != 1 {
+        return Err(Error::new_spanned(
+            &recreate_method.sig,
+            format!(
+                "`{}::{}` must take the old domain as its only argument to recover interface `{}`",
+                create_tr.ident, recreate_method.sig.ident, tr.ident
+            ),
+        ));
+    }
+
+    Ok(Some(Recovery {
+        create_trait: parse_quote!(#(#create_trait_path)::*),
+        recreate_method: recreate_method.sig.ident.clone(),
+        retry,
+    }))
+}
+
+/// Returns the recovery configuration of the interface of trait object `ty`, if it's recoverable.
+/// Errors in the configuration are ignored here since they are reported along with the interface.
+pub fn recovery_of_trait_object(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    ty: &Type,
+) -> Option<Recovery> {
+    let trait_object = match ty {
+        Type::TraitObject(trait_object) => trait_object,
+        _ => return None,
+    };
+    trait_object.bounds.iter().find_map(|bound| match bound {
+        syn::TypeParamBound::Trait(tr) => {
+            let idents: Vec<_> = tr
+                .path
+                .segments
+                .iter()
+                .map(|seg| seg.ident.clone())
+                .collect();
+            let node = symbol_tree.lookup(&idents)?;
+            let node = node.borrow();
+            match &node.terminal.as_ref()?.definition {
+                Definition::Type(Item::Trait(tr)) => {
+                    parse_recovery(symbol_tree, config, tr, &node.path[..node.path.len() - 1])
+                        .ok()
+                        .flatten()
+                }
+                _ => None,
+            }
+        }
+        syn::TypeParamBound::Lifetime(_) => None,
+    })
+}
+
+/// Returns true if `rtn` is `(Box<dyn Domain>, Box<dyn Interface>)`.
+fn returns_interface(rtn: &ReturnType, interface: &Ident) -> bool {
+    let ty = match rtn {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => return false,
+    };
+    let boxed = match ty.as_ref() {
+        Type::Tuple(tuple) if tuple.elems.len() == 2 => match &tuple.elems[1] {
+            Type::Path(path) if path.path.segments.last().unwrap().ident == "Box" => {
+                match crate::utils::get_type_inside_of_box(&tuple.elems[1]) {
+                    Ok(boxed) => boxed,
+                    Err(_) => return false,
+                }
+            }
+            _ => return false,
+        },
+        _ => return false,
+    };
+    match boxed {
+        Type::TraitObject(tr) => tr.bounds.iter().any(|bound| match bound {
+            syn::TypeParamBound::Trait(tr) => &tr.path.segments.last().unwrap().ident == interface,
+            syn::TypeParamBound::Lifetime(_) => false,
+        }),
+        _ => false,
+    }
+}
+
+/// Returns true if an argument of type `ty` can be passed to the callee again after the first call,
+/// i.e. it's a reference or a primitive.
+pub fn is_reusable(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(_) => true,
+        Type::Path(path) => {
+            path.qself.is_none() && PRIMITIVES.iter().any(|prim| path.path.is_ident(prim))
+        }
+        Type::Tuple(tuple) => tuple.elems.iter().all(is_reusable),
+        Type::Array(arr) => is_reusable(&arr.elem),
+        Type::Paren(paren) => is_reusable(&paren.elem),
+        _ => false,
+    }
+}
+
+/// Generate the recoverable proxy struct, the domain handle it gives out, and the methods to
+/// construct and recover it. `object` is the trait of the domain object, i.e. the interface
+/// `trait_ident` with its associated types bound.
+pub fn generate_recoverable_proxy(
+    trait_ident: &Ident,
+    object: &Path,
+    proxy_ident: &Ident,
+    recovery: &Recovery,
+    config: &Config,
+) -> TokenStream {
+    let syscalls = &config.crates.syscalls;
+    let console = &config.crates.console;
+    let handle_ident = format_ident!("{}Domain", proxy_ident);
+    let create_trait = &recovery.create_trait;
+    let recreate_method = &recovery.recreate_method;
+
+    quote! {
+        #[cfg(feature = "proxy")]
+        pub struct #proxy_ident {
+            domain: ::spin::RwLock<::alloc::boxed::Box<dyn #object>>,
+            domain_id: ::alloc::sync::Arc<::core::sync::atomic::AtomicU64>,
+            // The domain create and the handle of the current domain, used for recovery.
+            recovery: ::spin::Mutex<
+                Option<(
+                    ::alloc::sync::Arc<dyn #create_trait>,
+                    ::alloc::boxed::Box<dyn #syscalls::Domain>,
+                )>,
+            >,
+        }
+
+        #[cfg(feature = "proxy")]
+        unsafe impl Sync for #proxy_ident {}
+        #[cfg(feature = "proxy")]
+        unsafe impl Send for #proxy_ident {}
+
+        /// The domain handle given out in place of the handle of the proxied domain, which is kept
+        /// by the proxy for recovery. It always refers to the current incarnation of the domain.
+        #[cfg(feature = "proxy")]
+        pub struct #handle_ident {
+            domain_id: ::alloc::sync::Arc<::core::sync::atomic::AtomicU64>,
+        }
+
+        #[cfg(feature = "proxy")]
+        impl #syscalls::Domain for #handle_ident {
+            fn get_domain_id(&self) -> u64 {
+                self.domain_id.load(::core::sync::atomic::Ordering::SeqCst)
+            }
+        }
+
+        #[cfg(feature = "proxy")]
+        impl #proxy_ident {
+            /// Creates a proxy that is not able to recover its domain.
+            pub fn new(domain_id: u64, domain: ::alloc::boxed::Box<dyn #object>) -> Self {
+                Self {
+                    domain: ::spin::RwLock::new(domain),
+                    domain_id: ::alloc::sync::Arc::new(::core::sync::atomic::AtomicU64::new(domain_id)),
+                    recovery: ::spin::Mutex::new(None),
+                }
+            }
+
+            /// Creates a proxy that recovers its domain with `create` when the domain panics.
+            pub fn new_recoverable(
+                create: ::alloc::sync::Arc<dyn #create_trait>,
+                dom: ::alloc::boxed::Box<dyn #syscalls::Domain>,
+                domain: ::alloc::boxed::Box<dyn #object>,
+            ) -> Self {
+                let proxy = Self::new(dom.get_domain_id(), domain);
+                *proxy.recovery.lock() = Some((create, dom));
+                proxy
+            }
+
+            /// Returns a handle of the proxied domain.
+            pub fn domain_handle(&self) -> ::alloc::boxed::Box<dyn #syscalls::Domain> {
+                ::alloc::boxed::Box::new(#handle_ident {
+                    domain_id: ::alloc::sync::Arc::clone(&self.domain_id),
+                })
+            }
+
+            /// Recreates the domain `failed_domain_id`, which a call failed on, and swaps it in.
+            /// Returns false if the proxy is not able to recover.
+            fn recover_(&self, failed_domain_id: u64) -> bool {
+                let mut recovery = self.recovery.lock();
+
+                // Another caller that failed on the same domain has recovered it while this one
+                // waited for the lock. Recreating it again would kill the healthy domain.
+                if self.domain_id.load(::core::sync::atomic::Ordering::SeqCst) != failed_domain_id {
+                    return true;
+                }
+
+                let (create, dom) = match recovery.take() {
+                    Some(recovery) => recovery,
+                    None => return false,
+                };
+
+                #[cfg(feature = "proxy-log-error")]
+                #console::println!("proxy: recovering domain of {}", stringify!(#trait_ident));
+
+                let (dom, domain) = create.#recreate_method(dom);
+                // Swap the domain and its id together, so a caller that reads the new id calls the
+                // new domain.
+                // The drop glue of the old object is in the binary of the dead domain, so it must
+                // not run.
+                let mut domain_ = self.domain.write();
+                ::core::mem::forget(::core::mem::replace(&mut *domain_, domain));
+                self.domain_id
+                    .store(dom.get_domain_id(), ::core::sync::atomic::Ordering::SeqCst);
+                drop(domain_);
+                *recovery = Some((create, dom));
+                true
+            }
+        }
+    }
+}
+
+/// Generates the trait method of a recoverable proxy. The actual proxying is done by `inner`,
+/// which takes the same arguments as the method.
+/// `panics` is the static that the `_err` trampoline of the method counts the panics in, or `None`
+/// if the method has no trampoline, in which case a panic is not caught and there's nothing to
+/// recover.
+/// The call is retried only if `retry` is set and the arguments can be passed again, i.e. nothing
+/// is moved into the callee.
+pub fn generate_recoverable_method(
+    method_args: &syn::punctuated::Punctuated<FnArg, syn::Token![,]>,
+    arg_idents: &[&Ident],
+    return_ty: &ReturnType,
+    ident: &Ident,
+    inner: &Ident,
+    panics: Option<&Ident>,
+    retry: bool,
+) -> TokenStream {
+    let panics = match panics {
+        Some(panics) => panics,
+        None => {
+            return quote! {
+                fn #ident(#method_args) #return_ty {
+                    self.#inner(#(#arg_idents),*)
+                }
+            }
+        }
+    };
+
+    // An `Err` without a panic is passed along by a healthy callee, which must not be recreated.
+    let recover = if retry {
+        quote! {
+            #[cfg(feature = "trampoline")]
+            if r.is_err()
+                && #panics.load(::core::sync::atomic::Ordering::SeqCst) != panics_
+                && self.recover_(domain_id_)
+            {
+                return self.#inner(#(#arg_idents),*);
+            }
+        }
+    } else {
+        quote! {
+            #[cfg(feature = "trampoline")]
+            if r.is_err() && #panics.load(::core::sync::atomic::Ordering::SeqCst) != panics_ {
+                self.recover_(domain_id_);
+            }
+        }
+    };
+
+    quote! {
+        fn #ident(#method_args) #return_ty {
+            // The domain that the call goes to, which is the one to recover if the call panics.
+            // Without trampolines a panic is not caught, so there's nothing to recover.
+            #[cfg(feature = "trampoline")]
+            let domain_id_ = self.domain_id.load(::core::sync::atomic::Ordering::SeqCst);
+            #[cfg(feature = "trampoline")]
+            let panics_ = #panics.load(::core::sync::atomic::Ordering::SeqCst);
+            let r = self.#inner(#(#arg_idents),*);
+            #recover
+            r
+        }
+    }
+}
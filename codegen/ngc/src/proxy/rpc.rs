@@ -0,0 +1,203 @@
+//! Per-method RPC options, e.g. `#[rpc(no_trampoline, timeout_ms = 10)]` on a method of an
+//! interface.
+//!
+//! The options tune how the proxy calls one method, where the cargo features of the interface crate
+//! apply to every method. The attributes are stripped from the emitted trait.
+//!
+//! `log_errors` and `timeout_ms` only log, and only with the `proxy-log-error` feature of the
+//! interface crate. Without it they compile to nothing.
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+
+use syn::{
+    Attribute, Ident, ItemTrait, Lit, Meta, NestedMeta, PathArguments, ReturnType, TraitItem,
+    TraitItemMethod, Type,
+};
+
+const NO_TRAMPOLINE: &str = "no_trampoline";
+const LOG_ERRORS: &str = "log_errors";
+const TIMEOUT_MS: &str = "timeout_ms";
+const SKIP_PROXY: &str = "skip_proxy";
+const OPTIONS: &[&str] = &[NO_TRAMPOLINE, LOG_ERRORS, TIMEOUT_MS, SKIP_PROXY];
+
+/// How the proxy calls a method.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpcOptions {
+    /// Call the domain directly instead of through the trampoline, which saves the continuation
+    /// but leaves the caller unprotected if the callee panics.
+    pub no_trampoline: bool,
+    /// Log the errors returned by the call with the `proxy-log-error` feature.
+    pub log_errors: bool,
+    /// Log the calls that take longer than this with the `proxy-log-error` feature. Despite the
+    /// name, the call is not aborted: the callee can't be preempted, so it always runs to
+    /// completion.
+    pub timeout_ms: Option<u64>,
+    /// Don't forward the method. The proxy keeps its default implementation, which runs in the
+    /// caller domain.
+    pub skip_proxy: bool,
+}
+
+/// Parses the options of `method` of the trait `tr` from its `#[rpc(...)]` attributes.
+pub fn parse_rpc_options(
+    tr: &Ident,
+    method: &TraitItemMethod,
+    config: &Config,
+) -> Result<RpcOptions> {
+    let rpc_attr = &config.attributes.rpc;
+    let ident = &method.sig.ident;
+    let rpc_attrs: Vec<Attribute> = method
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident(rpc_attr))
+        .cloned()
+        .collect();
+    let first_attr = match rpc_attrs.first() {
+        Some(attr) => attr,
+        None => return Ok(RpcOptions::default()),
+    };
+
+    // `create_attribue_map` expects a list of flags and `key = value`s.
+    for attr in &rpc_attrs {
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list,
+            _ => {
+                return Err(Error::new_spanned(
+                    attr,
+                    format!(
+                        "Expecting `#[{}(option, ...)]` on method `{}` of `{}`",
+                        rpc_attr, ident, tr
+                    ),
+                ))
+            }
+        };
+        for nested in &list.nested {
+            let is_option = match nested {
+                NestedMeta::Meta(Meta::Path(path)) => path.get_ident().is_some(),
+                NestedMeta::Meta(Meta::NameValue(kv)) => kv.path.get_ident().is_some(),
+                _ => false,
+            };
+            if !is_option {
+                return Err(Error::new_spanned(
+                    nested,
+                    format!(
+                        "Unknown RPC option of method `{}` of `{}`; expecting one of {}",
+                        ident,
+                        tr,
+                        expected_options()
+                    ),
+                ));
+            }
+        }
+    }
+    let attrs = crate::utils::create_attribue_map(&rpc_attrs)?;
+    // Report the errors in a stable order.
+    let mut attrs: Vec<_> = attrs.into_iter().collect();
+    attrs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut options = RpcOptions::default();
+    for (key, value) in &attrs {
+        let error = |message: String| Err(Error::new_spanned(first_attr, message));
+        match (key.as_str(), value) {
+            (TIMEOUT_MS, Some(Lit::Int(ms))) => match ms.base10_parse::<u64>() {
+                Ok(ms) if ms > 0 => options.timeout_ms = Some(ms),
+                _ => {
+                    return error(format!(
+                        "`{}` of method `{}` of `{}` must be a positive number of milliseconds",
+                        TIMEOUT_MS, ident, tr
+                    ))
+                }
+            },
+            (TIMEOUT_MS, _) => {
+                return error(format!(
+                    "Expecting `{} = <milliseconds>` on method `{}` of `{}`",
+                    TIMEOUT_MS, ident, tr
+                ))
+            }
+            (NO_TRAMPOLINE, None) => options.no_trampoline = true,
+            (LOG_ERRORS, None) => options.log_errors = true,
+            (SKIP_PROXY, None) => options.skip_proxy = true,
+            (key, Some(_)) if OPTIONS.contains(&key) => {
+                return error(format!(
+                    "RPC option `{}` of method `{}` of `{}` doesn't take a value",
+                    key, ident, tr
+                ))
+            }
+            (key, _) => {
+                return error(format!(
+                    "Unknown RPC option `{}` of method `{}` of `{}`; expecting one of {}",
+                    key,
+                    ident,
+                    tr,
+                    expected_options()
+                ))
+            }
+        }
+    }
+
+    if options.skip_proxy {
+        if attrs.len() > 1 {
+            return Err(Error::new_spanned(
+                first_attr,
+                format!(
+                    "`{}` of method `{}` of `{}` can't be combined with other RPC options",
+                    SKIP_PROXY, ident, tr
+                ),
+            ));
+        }
+        if method.default.is_none() {
+            return Err(Error::new_spanned(
+                first_attr,
+                format!(
+                    "Method `{}` of `{}` is `{}` but has no default implementation for the proxy to keep",
+                    ident, tr, SKIP_PROXY
+                ),
+            ));
+        }
+    }
+    if options.log_errors && !returns_result(&method.sig.output) {
+        return Err(Error::new_spanned(
+            first_attr,
+            format!(
+                "`{}` of method `{}` of `{}` needs it to return an `RpcResult`",
+                LOG_ERRORS, ident, tr
+            ),
+        ));
+    }
+
+    Ok(options)
+}
+
+/// Removes the `#[rpc(...)]` attributes from the methods of `tr`.
+pub fn remove_rpc_attributes(tr: &mut ItemTrait, config: &Config) {
+    let rpc_attr = &config.attributes.rpc;
+    for item in &mut tr.items {
+        if let TraitItem::Method(method) = item {
+            method.attrs.retain(|attr| !attr.path.is_ident(rpc_attr));
+        }
+    }
+}
+
+fn expected_options() -> String {
+    OPTIONS
+        .iter()
+        .map(|option| format!("`{}`", option))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns true if `rtn` is a `Result`, e.g. `RpcResult<()>`, so the proxy can tell the errors.
+fn returns_result(rtn: &ReturnType) -> bool {
+    let ty = match rtn {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => return false,
+    };
+    match ty.as_ref() {
+        Type::Path(path) => {
+            let segment = path.path.segments.last().unwrap();
+            (segment.ident == "RpcResult" || segment.ident == "Result")
+                && matches!(segment.arguments, PathArguments::AngleBracketed(_))
+        }
+        _ => false,
+    }
+}
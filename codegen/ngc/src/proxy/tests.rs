@@ -0,0 +1,734 @@
+use super::{generate_interface_proxy, ownership, recovery, rpc, trait_object};
+use crate::config::Config;
+use crate::type_resolution::instantiation::instance_ident;
+use crate::type_resolution::type_info_finder::TypeInfoFinder;
+use crate::type_resolution::{resolve_types, ExternCrate};
+use quote::quote;
+use syn::{parse_quote, FnArg, Item, ItemTrait, ReturnType};
+
+#[test]
+fn test_move_rref_arg_to_callee() {
+    let args: Vec<FnArg> = vec![
+        parse_quote! { block: u32 },
+        parse_quote! { data: crate::rref::rref::RRef<[u8; 4096]> },
+        parse_quote! { borrowed: &crate::rref::rref::RRef<[u8; 4096]> },
+    ];
+    let moves = ownership::move_args_to(args.iter(), &quote!(self.domain_id));
+    assert_eq!(
+        quote!(#(#moves)*).to_string(),
+        quote! {
+            data.move_to(self.domain_id);
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_move_user_defined_rref_is_noop() {
+    let args: Vec<FnArg> = vec![parse_quote! { data: crate::foo::RRef<u8> }];
+    let moves = ownership::move_args_to(args.iter(), &quote!(self.domain_id));
+    assert!(moves.is_empty());
+}
+
+#[test]
+fn test_move_rpc_result_back_to_caller() {
+    let rtn: ReturnType = parse_quote! {
+        -> crate::rpc::RpcResult<(usize, crate::rref::rref_deque::RRefDeque<crate::bdev::BlkReq, 1024>)>
+    };
+    let moves = ownership::move_return_to(&quote!(r), &rtn, &quote!(caller_domain_id_));
+    assert_eq!(
+        quote!(#(#moves)*).to_string(),
+        quote! {
+            if let Ok(rref_0_) = &r {
+                rref_0_.1.move_to(caller_domain_id_);
+            }
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_move_optional_rref_in_tuple() {
+    let rtn: ReturnType = parse_quote! {
+        -> (Option<crate::rref::rref::RRef<u8>>, u64)
+    };
+    let moves = ownership::move_return_to(&quote!(r), &rtn, &quote!(caller_domain_id_));
+    assert_eq!(
+        quote!(#(#moves)*).to_string(),
+        quote! {
+            if let Some(rref_1_) = &r.0 {
+                rref_1_.move_to(caller_domain_id_);
+            }
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_borrow_rref_args() {
+    let args: Vec<FnArg> = vec![
+        parse_quote! { data: &crate::rref::rref::RRef<[u8; 4096]> },
+        parse_quote! { queue: &crate::rref::rref_deque::RRefDeque<u8, 32> },
+        parse_quote! { moved: crate::rref::rref::RRef<u8> },
+        parse_quote! { bytes: &[u8] },
+    ];
+    let (borrows, forfeits) = ownership::borrow_args(args.iter());
+    assert_eq!(
+        quote!(#(#borrows)*).to_string(),
+        quote! {
+            data.borrow();
+            queue.borrow();
+        }
+        .to_string()
+    );
+    assert_eq!(
+        quote!(#(#forfeits)*).to_string(),
+        quote! {
+            data.forfeit();
+            queue.forfeit();
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_rref_behind_type_alias() {
+    let mut ast: syn::File = parse_quote! {
+        pub mod rref {
+            pub mod rref {
+                pub struct RRef<T> {
+                    value_pointer: *mut T,
+                }
+            }
+        }
+        use crate::rref::rref::RRef;
+
+        pub type Block = RRef<[u8; 4096]>;
+
+        #[interface]
+        pub trait BDev {
+            fn write(&self, block: Block) -> Option<Block>;
+            fn read(&self, block: &Block);
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[3] {
+        Item::Trait(tr) => tr.clone(),
+        _ => unreachable!(),
+    };
+    let items = generate_interface_proxy(
+        &mut tr,
+        &[quote::format_ident!("interface")],
+        &symbol_tree,
+        &Config::default(),
+    )
+    .unwrap()
+    .unwrap();
+    let generated = quote!(#(#items)*).to_string();
+
+    // The aliased `RRef`s are moved and borrowed like the ones written out.
+    assert!(generated.contains(&quote!(block.move_to(self.domain_id);).to_string()));
+    assert!(generated.contains(
+        &quote! {
+            if let Some(rref_0_) = &r {
+                rref_0_.move_to(caller_domain_id_);
+            }
+        }
+        .to_string()
+    ));
+    assert!(generated.contains(&quote!(block.borrow();).to_string()));
+    assert!(generated.contains(&quote!(block.forfeit();).to_string()));
+}
+
+#[test]
+fn test_wrap_trait_objects_in_return() {
+    let ast: syn::File = parse_quote! {
+        pub mod pci {
+            #[interface]
+            pub trait PCI {}
+            pub trait NotInterface {}
+        }
+        pub mod net {
+            use crate::pci::PCI;
+        }
+    };
+    let symbol_tree = TypeInfoFinder::new().find_type_info(&ast).unwrap();
+
+    let rtn: ReturnType = parse_quote! {
+        -> crate::rpc::RpcResult<(usize, Box<dyn crate::pci::PCI>, Box<dyn crate::pci::NotInterface>)>
+    };
+    let wrapped = trait_object::wrap_return(
+        &symbol_tree,
+        &Config::default(),
+        &quote!(r),
+        &rtn,
+        &quote!(self.domain_id),
+    );
+    assert_eq!(
+        quote!(#wrapped).to_string(),
+        quote! {
+            let r: crate::rpc::RpcResult<(usize, Box<dyn crate::pci::PCI>, Box<dyn crate::pci::NotInterface>)> =
+                r.map(|tr_obj_0_| {
+                    let (tr_obj_1_0_, tr_obj_1_1_, tr_obj_1_2_,) = tr_obj_0_;
+                    (
+                        tr_obj_1_0_,
+                        (::alloc::boxed::Box::new(crate::pci::PCIProxy::new(self.domain_id, tr_obj_1_1_)) as Box<dyn crate::pci::PCI>),
+                        tr_obj_1_2_,
+                    )
+                });
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_wrap_non_interface_is_noop() {
+    let ast: syn::File = parse_quote! {
+        pub trait NotInterface {}
+    };
+    let symbol_tree = TypeInfoFinder::new().find_type_info(&ast).unwrap();
+
+    let rtn: ReturnType = parse_quote! {
+        -> Box<dyn crate::NotInterface>
+    };
+    assert!(trait_object::wrap_return(
+        &symbol_tree,
+        &Config::default(),
+        &quote!(r),
+        &rtn,
+        &quote!(self.domain_id)
+    )
+    .is_none());
+}
+
+#[test]
+fn test_parse_recovery() {
+    let ast: syn::File = parse_quote! {
+        pub mod pci {
+            #[interface(recover = "super::create::CreatePCI", retry)]
+            pub trait PCI {}
+        }
+        pub mod create {
+            use alloc::boxed::Box;
+            use crate::pci::PCI;
+            pub trait CreatePCI {
+                fn create_domain_pci(&self) -> (Box<dyn syscalls::Domain>, Box<dyn PCI>);
+                fn recreate_domain_pci(
+                    &self,
+                    dom: Box<dyn syscalls::Domain>,
+                ) -> (Box<dyn syscalls::Domain>, Box<dyn PCI>);
+            }
+        }
+    };
+    let symbol_tree = TypeInfoFinder::new().find_type_info(&ast).unwrap();
+
+    let tr: ItemTrait = parse_quote! {
+        #[interface(recover = "super::create::CreatePCI", retry)]
+        pub trait PCI {}
+    };
+    let recovery = recovery::parse_recovery(
+        &symbol_tree,
+        &Config::default(),
+        &tr,
+        &[quote::format_ident!("crate"), quote::format_ident!("pci")],
+    )
+    .unwrap()
+    .unwrap();
+    let create_trait = &recovery.create_trait;
+    assert_eq!(
+        quote!(#create_trait).to_string(),
+        quote!(crate::create::CreatePCI).to_string()
+    );
+    assert_eq!(recovery.recreate_method, "recreate_domain_pci");
+    assert!(recovery.retry);
+
+    let tr: ItemTrait = parse_quote! {
+        #[interface]
+        pub trait PCI {}
+    };
+    assert!(recovery::parse_recovery(
+        &symbol_tree,
+        &Config::default(),
+        &tr,
+        &[quote::format_ident!("crate")]
+    )
+    .unwrap()
+    .is_none());
+}
+
+#[test]
+fn test_retry_only_reusable_args() {
+    assert!(recovery::is_reusable(&parse_quote!(
+        &crate::rref::rref::RRef<u8>
+    )));
+    assert!(recovery::is_reusable(&parse_quote!((usize, [u8; 4]))));
+    assert!(!recovery::is_reusable(&parse_quote!(
+        crate::rref::rref::RRef<u8>
+    )));
+    assert!(!recovery::is_reusable(&parse_quote!(
+        Box<dyn crate::pci::PCI>
+    )));
+}
+
+#[test]
+fn test_recover_once_per_failed_domain() {
+    let recovery = recovery::Recovery {
+        create_trait: parse_quote!(crate::CreateBDev),
+        recreate_method: quote::format_ident!("recreate_domain_bdev"),
+        retry: true,
+    };
+    let proxy = recovery::generate_recoverable_proxy(
+        &quote::format_ident!("BDev"),
+        &parse_quote!(BDev),
+        &quote::format_ident!("BDevProxy"),
+        &recovery,
+        &Config::default(),
+    )
+    .to_string();
+    // A domain that another caller has recovered already is not recreated again.
+    assert!(proxy.contains(&format!(
+        "{} {{ {} {}",
+        quote!(fn recover_(&self, failed_domain_id: u64) -> bool),
+        quote!(let mut recovery = self.recovery.lock();),
+        quote! {
+            if self.domain_id.load(::core::sync::atomic::Ordering::SeqCst) != failed_domain_id {
+                return true;
+            }
+        }
+    )));
+
+    let method = recovery::generate_recoverable_method(
+        &parse_quote!(&self, block: u32),
+        &[&quote::format_ident!("block")],
+        &parse_quote!(-> RpcResult<()>),
+        &quote::format_ident!("read"),
+        &quote::format_ident!("read_proxy_"),
+        Some(&quote::format_ident!("BDEV_READ_PANICS")),
+        true,
+    );
+    // Only a panic, which the `_err` trampoline counts, recovers the domain. Other errors are
+    // passed along by a healthy callee.
+    assert_eq!(
+        method.to_string(),
+        quote! {
+            fn read(&self, block: u32) -> RpcResult<()> {
+                #[cfg(feature = "trampoline")]
+                let domain_id_ = self.domain_id.load(::core::sync::atomic::Ordering::SeqCst);
+                #[cfg(feature = "trampoline")]
+                let panics_ = BDEV_READ_PANICS.load(::core::sync::atomic::Ordering::SeqCst);
+                let r = self.read_proxy_(block);
+                #[cfg(feature = "trampoline")]
+                if r.is_err()
+                    && BDEV_READ_PANICS.load(::core::sync::atomic::Ordering::SeqCst) != panics_
+                    && self.recover_(domain_id_)
+                {
+                    return self.read_proxy_(block);
+                }
+                r
+            }
+        }
+        .to_string()
+    );
+
+    // The old object is forgotten, since its drop glue is in the binary of the dead domain.
+    assert!(proxy.contains(
+        &quote!(::core::mem::forget(::core::mem::replace(&mut *domain_, domain));).to_string()
+    ));
+}
+
+#[test]
+fn test_proxy_supertraits() {
+    let mut ast: syn::File = parse_quote! {
+        pub mod bdev {
+            pub trait BDev {
+                fn read(&self, block: u32) -> u64;
+            }
+        }
+        pub mod nvme {
+            #[interface]
+            pub trait NvmeBDev: crate::bdev::BDev + Send + Sync {
+                fn flush(&self);
+            }
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[1] {
+        Item::Mod(md) => match &md.content.as_ref().unwrap().1[0] {
+            Item::Trait(tr) => tr.clone(),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    let module_path = [
+        quote::format_ident!("interface"),
+        quote::format_ident!("nvme"),
+    ];
+    let items = generate_interface_proxy(&mut tr, &module_path, &symbol_tree, &Config::default())
+        .unwrap()
+        .unwrap();
+    let generated = quote!(#(#items)*).to_string();
+
+    assert!(generated.contains(&quote!(impl NvmeBDev for NvmeBDevProxy).to_string()));
+    assert!(generated.contains(&quote!(impl crate::bdev::BDev for NvmeBDevProxy).to_string()));
+    assert!(
+        generated.contains(&quote!(crate::bdev::BDev::read(&*self.domain, block: u32)).to_string())
+    );
+    assert!(generated.contains("fn NvmeBDev_BDev_read"));
+    assert_eq!(
+        quote!(#tr).to_string(),
+        quote! {
+            #[doc = "redIDL Auto Generated: interface trait. Generations are below"]
+            pub trait NvmeBDev: crate::bdev::BDev + Send + Sync {
+                fn flush(&self);
+            }
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_proxy_generic_interface() {
+    let mut ast: syn::File = parse_quote! {
+        pub mod queue {
+            #[interface]
+            pub trait Queue<T: Copy> {
+                fn push(&self, item: T) -> u64;
+            }
+        }
+        #[interface]
+        pub trait Foo {
+            fn queue(&self) -> Box<dyn queue::Queue<u64>>;
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[0] {
+        Item::Mod(md) => match &md.content.as_ref().unwrap().1[0] {
+            Item::Trait(tr) => tr.clone(),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    let module_path = [
+        quote::format_ident!("interface"),
+        quote::format_ident!("queue"),
+    ];
+    let items = generate_interface_proxy(&mut tr, &module_path, &symbol_tree, &Config::default())
+        .unwrap()
+        .unwrap();
+    let generated = quote!(#(#items)*).to_string();
+
+    // The proxy is generic, and each instantiation gets its own impl and trampolines.
+    assert!(generated.contains(
+        &quote! {
+            pub struct QueueProxy<T: Copy> {
+                domain: ::alloc::boxed::Box<dyn Queue<T> >,
+                domain_id: u64,
+            }
+        }
+        .to_string()
+    ));
+    assert!(generated.contains(&quote!(impl Queue<u64> for QueueProxy<u64>).to_string()));
+    assert!(generated.contains(
+        &quote! {
+            extern fn Queue_u64_push(redidl_generated_domain_queue_u64: &alloc::boxed::Box<dyn Queue<u64> >, item: u64) -> u64
+        }
+        .to_string()
+    ));
+}
+
+#[test]
+fn test_proxy_generic_method() {
+    let mut ast: syn::File = parse_quote! {
+        #[interface]
+        pub trait Foo {
+            fn bar<T>(&self, x: T);
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[0] {
+        Item::Trait(tr) => tr.clone(),
+        _ => unreachable!(),
+    };
+    let err = generate_interface_proxy(
+        &mut tr,
+        &[quote::format_ident!("interface")],
+        &symbol_tree,
+        &Config::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.iter().map(|err| err.message()).collect::<Vec<_>>(),
+        vec![            "Generic method `bar` of interface `Foo` can't be proxied; only lifetime parameters are supported"
+        ]
+    );
+}
+
+#[test]
+fn test_proxy_supertrait_errors() {
+    // The traits of the external crates are resolved only when they're proxied.
+    let blk = ExternCrate {
+        name: quote::format_ident!("blk"),
+        ast: parse_quote! {
+            pub trait BDev {
+                fn read(&self, block: crate::nope::Block);
+                fn write(&self, data: crate::nope::Data);
+            }
+        },
+    };
+    let mut ast: syn::File = parse_quote! {
+        #[interface]
+        pub trait NvmeBDev: blk::BDev {
+            fn flush(&self);
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[blk]).unwrap();
+    let mut tr = match &ast.items[0] {
+        Item::Trait(tr) => tr.clone(),
+        _ => unreachable!(),
+    };
+    let err = generate_interface_proxy(
+        &mut tr,
+        &[quote::format_ident!("interface")],
+        &symbol_tree,
+        &Config::default(),
+    )
+    .unwrap_err();
+    // Every error in the supertrait is reported.
+    assert_eq!(
+        err.iter().map(|err| err.message()).collect::<Vec<_>>(),
+        vec![
+            "Unable to resolve `crate::nope::Block`: `nope` is not found in `blk`",
+            "Unable to resolve `crate::nope::Data`: `nope` is not found in `blk`",
+        ]
+    );
+}
+
+#[test]
+fn test_proxy_associated_types() {
+    let mut ast: syn::File = parse_quote! {
+        #[interface]
+        pub trait Disk {
+            type Buf = [u8; 512];
+            fn read(&self, block: u32) -> Self::Buf;
+        }
+        #[interface]
+        pub trait Foo {
+            fn disk(&self) -> Box<dyn Disk<Buf = [u8; 512]>>;
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[0] {
+        Item::Trait(tr) => tr.clone(),
+        _ => unreachable!(),
+    };
+    let items = generate_interface_proxy(
+        &mut tr,
+        &[quote::format_ident!("interface")],
+        &symbol_tree,
+        &Config::default(),
+    )
+    .unwrap()
+    .unwrap();
+    let generated = quote!(#(#items)*).to_string();
+
+    // The domain is a trait object, which binds the associated types to their defaults.
+    assert!(generated
+        .contains(&quote!(domain: ::alloc::boxed::Box<dyn Disk<Buf = [u8; 512]> >,).to_string()));
+    assert!(generated.contains(&format!(
+        "{} {{ {}",
+        quote!(impl Disk for DiskProxy),
+        quote!(
+            type Buf = [u8; 512];
+        )
+    )));
+    assert!(generated.contains(
+        &quote! {
+            extern fn Disk_read(redidl_generated_domain_disk: &alloc::boxed::Box<dyn Disk<Buf = [u8; 512]> >, block: u32)
+        }
+        .to_string()
+    ));
+}
+
+#[test]
+fn test_proxy_associated_type_without_default() {
+    let mut ast: syn::File = parse_quote! {
+        #[interface]
+        pub trait Disk {
+            type Buf;
+            fn flush(&self);
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[0] {
+        Item::Trait(tr) => tr.clone(),
+        _ => unreachable!(),
+    };
+    let err = generate_interface_proxy(
+        &mut tr,
+        &[quote::format_ident!("interface")],
+        &symbol_tree,
+        &Config::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.iter().map(|err| err.message()).collect::<Vec<_>>(),
+        vec!["Associated type `Buf` of interface `Disk` must have a default for its proxy to implement it with"]
+    );
+}
+
+#[test]
+fn test_proxy_associated_const() {
+    let mut ast: syn::File = parse_quote! {
+        #[interface]
+        pub trait Net {
+            const MAX_QUEUE: usize = 32;
+            fn submit(&self, len: usize);
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[0] {
+        Item::Trait(tr) => tr.clone(),
+        _ => unreachable!(),
+    };
+    let err = generate_interface_proxy(
+        &mut tr,
+        &[quote::format_ident!("interface")],
+        &symbol_tree,
+        &Config::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.iter().map(|err| err.message()).collect::<Vec<_>>(),
+        vec![            "Associated const `MAX_QUEUE` makes interface `Net` unusable as a trait object; consider a `const` in its module instead"
+        ]
+    );
+}
+
+#[test]
+fn test_proxy_rpc_options() {
+    let mut ast: syn::File = parse_quote! {
+        #[interface]
+        pub trait Net {
+            #[rpc(no_trampoline, timeout_ms = 10)]
+            fn poll(&self) -> u64;
+            #[rpc(log_errors)]
+            fn submit(&self, len: usize) -> RpcResult<()>;
+            #[rpc(skip_proxy)]
+            fn poll_twice(&self) -> u64 {
+                self.poll() + self.poll()
+            }
+        }
+    };
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    let mut tr = match &ast.items[0] {
+        Item::Trait(tr) => tr.clone(),
+        _ => unreachable!(),
+    };
+    let items = generate_interface_proxy(
+        &mut tr,
+        &[quote::format_ident!("interface")],
+        &symbol_tree,
+        &Config::default(),
+    )
+    .unwrap()
+    .unwrap();
+    let generated = quote!(#(#items)*).to_string();
+
+    // `poll` is called directly and timed.
+    assert!(!generated.contains("fn Net_poll"));
+    assert!(generated.contains(
+        &quote! {
+            #[cfg(feature = "proxy-log-error")]
+            let start_ns_ = ::libtime::get_ns_time();
+            let r = self.domain.poll();
+            #[cfg(feature = "proxy-log-error")]
+            {
+                let elapsed_ms_ = (::libtime::get_ns_time() - start_ns_) / 1_000_000;
+                if elapsed_ms_ > 10u64 {
+                    ::console::println!(
+                        "proxy: {} took {} ms, over {} ms",
+                        stringify!(poll),
+                        elapsed_ms_,
+                        10u64
+                    );
+                }
+            }
+        }
+        .to_string()
+    ));
+    assert!(generated.contains(
+        &quote! {
+            #[cfg(feature = "proxy-log-error")]
+            if r.is_err() {
+                ::console::println!("proxy: {} returned an error", stringify!(submit));
+            }
+        }
+        .to_string()
+    ));
+    assert!(generated.contains("fn Net_submit"));
+    // `poll_twice` keeps its default implementation.
+    assert!(!generated.contains("poll_twice"));
+    assert_eq!(
+        quote!(#tr).to_string(),
+        quote! {
+            #[doc = "redIDL Auto Generated: interface trait. Generations are below"]
+            pub trait Net {
+                fn poll(&self) -> u64;
+                fn submit(&self, len: usize) -> RpcResult<()>;
+                fn poll_twice(&self) -> u64 {
+                    self.poll() + self.poll()
+                }
+            }
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn test_invalid_rpc_options() {
+    let tr = quote::format_ident!("Net");
+    let config = Config::default();
+    let cases = [
+        (
+            quote!(#[rpc(fast)] fn poll(&self) -> u64;),
+            "Unknown RPC option `fast` of method `poll` of `Net`; expecting one of `no_trampoline`, `log_errors`, `timeout_ms`, `skip_proxy`",
+        ),
+        (
+            quote!(#[rpc(timeout_ms)] fn poll(&self) -> u64;),
+            "Expecting `timeout_ms = <milliseconds>` on method `poll` of `Net`",
+        ),
+        (
+            quote!(#[rpc(no_trampoline = true)] fn poll(&self) -> u64;),
+            "RPC option `no_trampoline` of method `poll` of `Net` doesn't take a value",
+        ),
+        (
+            quote!(#[rpc(skip_proxy)] fn poll(&self) -> u64;),
+            "Method `poll` of `Net` is `skip_proxy` but has no default implementation for the proxy to keep",
+        ),
+        (
+            quote!(#[rpc(log_errors)] fn poll(&self) -> u64;),
+            "`log_errors` of method `poll` of `Net` needs it to return an `RpcResult`",
+        ),
+    ];
+    for (method, message) in &cases {
+        let method = syn::parse2(method.clone()).unwrap();
+        let err = rpc::parse_rpc_options(&tr, &method, &config).unwrap_err();
+        assert_eq!(err.message(), *message);
+    }
+}
+
+#[test]
+fn test_instance_ident() {
+    let ident = |path: syn::Path| instance_ident(&path).to_string();
+    assert_eq!(ident(parse_quote!(crate::queue::Queue<u64>)), "Queue_u64");
+    assert_eq!(ident(parse_quote!(Disk<'a, Buf = [u8; 512usize]>)), "Disk");
+    // The same words in different brackets are different instantiations.
+    assert_eq!(
+        ident(parse_quote!(Queue<[u8; 4usize]>)),
+        "Queue_u8_4usize_c449ddf9"
+    );
+    assert_eq!(
+        ident(parse_quote!(Queue<u8, 4usize>)),
+        "Queue_u8_4usize_12f420ae"
+    );
+}
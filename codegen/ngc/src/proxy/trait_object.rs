@@ -0,0 +1,200 @@
+//! Generation of the expressions that wrap interface trait objects into their proxies.
+//!
+//! When an interface method takes or returns a `Box<dyn OtherInterface>`, the object lives in
+//! another domain and the calls on it must go through a proxy as well. All types passed in here
+//! must already be resolved by the `TypeResolver`.
+
+use crate::config::Config;
+use crate::has_attribute;
+use crate::type_resolution::symbol_tree::{Definition, SymbolTree};
+
+use super::first_type_argument;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, FnArg, Item, Pat, Path, ReturnType, Type};
+
+/// Generates the statements that rebind every argument that contains interface trait objects to
+/// its wrapped version.
+pub fn wrap_args(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    args: &[&FnArg],
+    domain_id: &TokenStream,
+) -> Vec<TokenStream> {
+    args.iter()
+        .filter_map(|arg| match arg {
+            FnArg::Receiver(_) => None,
+            FnArg::Typed(arg) => match arg.pat.as_ref() {
+                Pat::Ident(pat) => {
+                    let ident = &pat.ident;
+                    let ty = &arg.ty;
+                    wrap(symbol_tree, config, &quote!(#ident), ty, domain_id).map(|wrapped| {
+                        quote! {
+                            let #ident: #ty = #wrapped;
+                        }
+                    })
+                }
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+/// Generates the statement that rebinds the returned value `rtn` to its wrapped version, if it
+/// contains interface trait objects.
+pub fn wrap_return(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    rtn: &TokenStream,
+    return_ty: &ReturnType,
+    domain_id: &TokenStream,
+) -> Option<TokenStream> {
+    match return_ty {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => wrap(symbol_tree, config, rtn, ty, domain_id).map(|wrapped| {
+            quote! {
+                let #rtn: #ty = #wrapped;
+            }
+        }),
+    }
+}
+
+/// Generates an expression that consumes `expr`, which is of type `ty`, and evaluates to the same
+/// value of the same type but with all interface trait objects in it wrapped in their proxies.
+/// Returns `None` if there's nothing to wrap.
+pub fn wrap(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    expr: &TokenStream,
+    ty: &Type,
+    domain_id: &TokenStream,
+) -> Option<TokenStream> {
+    wrap_recursive(symbol_tree, config, expr, ty, domain_id, 0)
+}
+
+fn wrap_recursive(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    expr: &TokenStream,
+    ty: &Type,
+    domain_id: &TokenStream,
+    depth: usize,
+) -> Option<TokenStream> {
+    match ty {
+        Type::Path(path) => {
+            let last_segment = path.path.segments.last().unwrap();
+            let inner_ty = first_type_argument(&last_segment.arguments)?;
+            match last_segment.ident.to_string().as_str() {
+                "Box" => {
+                    let proxy_path =
+                        interface_proxy_of_trait_object(symbol_tree, config, inner_ty)?;
+                    Some(quote! {
+                        (::alloc::boxed::Box::new(#proxy_path::new(#domain_id, #expr)) as #ty)
+                    })
+                }
+                "Option" | "Result" | "RpcResult" => {
+                    let binding = format_ident!("tr_obj_{}_", depth);
+                    let wrapped = wrap_recursive(
+                        symbol_tree,
+                        config,
+                        &quote!(#binding),
+                        inner_ty,
+                        domain_id,
+                        depth + 1,
+                    )?;
+                    Some(quote! {
+                        #expr.map(|#binding| #wrapped)
+                    })
+                }
+                _ => None,
+            }
+        }
+        Type::Tuple(tuple) => {
+            let bindings: Vec<_> = (0..tuple.elems.len())
+                .map(|i| format_ident!("tr_obj_{}_{}_", depth, i))
+                .collect();
+            let mut has_trait_object = false;
+            let elems: Vec<TokenStream> = tuple
+                .elems
+                .iter()
+                .zip(&bindings)
+                .map(|(elem, binding)| {
+                    match wrap_recursive(
+                        symbol_tree,
+                        config,
+                        &quote!(#binding),
+                        elem,
+                        domain_id,
+                        depth + 1,
+                    ) {
+                        Some(wrapped) => {
+                            has_trait_object = true;
+                            wrapped
+                        }
+                        None => quote!(#binding),
+                    }
+                })
+                .collect();
+            if !has_trait_object {
+                return None;
+            }
+            Some(quote! {
+                {
+                    let (#(#bindings,)*) = #expr;
+                    (#(#elems,)*)
+                }
+            })
+        }
+        Type::Paren(paren) => {
+            wrap_recursive(symbol_tree, config, expr, &paren.elem, domain_id, depth)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the path to the proxy of the trait object `ty` if the trait is an interface.
+fn interface_proxy_of_trait_object(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    ty: &Type,
+) -> Option<Path> {
+    let trait_object = match ty {
+        Type::TraitObject(trait_object) => trait_object,
+        _ => return None,
+    };
+
+    // Marker traits like `Send` may come along with the interface, e.g. `dyn Foo + Send`.
+    trait_object.bounds.iter().find_map(|bound| match bound {
+        syn::TypeParamBound::Trait(tr) => find_interface_proxy(symbol_tree, config, &tr.path),
+        syn::TypeParamBound::Lifetime(_) => None,
+    })
+}
+
+/// Locates the proxy of the interface trait `trait_path` points to.
+/// The proxy is generated in the same module as the interface, so its path is the fully-qualified
+/// path of the interface with the `Proxy` postfix, e.g. `crate::pci::PCIProxy` for
+/// `crate::pci::PCI`, no matter which module the interface is used in.
+pub fn find_interface_proxy(
+    symbol_tree: &SymbolTree,
+    config: &Config,
+    trait_path: &Path,
+) -> Option<Path> {
+    let interface_attr = &config.attributes.interface;
+    let idents: Vec<_> = trait_path
+        .segments
+        .iter()
+        .map(|seg| seg.ident.clone())
+        .collect();
+    let node = symbol_tree.lookup(&idents)?;
+    let node = node.borrow();
+    match &node.terminal.as_ref()?.definition {
+        Definition::Type(Item::Trait(tr)) if has_attribute!(tr, interface_attr) => {
+            let mut proxy_path = node.path.clone();
+            let proxy_ident = proxy_path.last_mut().unwrap();
+            *proxy_ident = format_ident!("{}Proxy", proxy_ident);
+            Some(parse_quote!(#(#proxy_path)::*))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,207 @@
+//! Resolution of the associated types, e.g. `Self::Buf` in an interface or `<Foo as Bar>::Buf`.
+//!
+//! An associated type is resolved to its definition in the impl of the trait for the type, or to
+//! its default in the trait if there's no such impl in the IDL. `Self` in an interface is the trait
+//! object, which is only known by the defaults of the trait.
+
+use syn::{Ident, ImplItem, Item, ItemTrait, Path, TraitItem, Type, TypePath};
+
+use super::symbol_tree::*;
+use super::utils::{path_to_string, walk_path};
+use crate::error::{Error, Result};
+
+/// The definition of an associated type.
+pub struct AssociatedType {
+    /// The type it is defined as. It is not resolved.
+    pub ty: Type,
+    /// The module where it is defined, which is where `ty` should be resolved.
+    pub module: Module,
+}
+
+/// Returns the definition of the associated type `path` points to, or `None` if `path` is not an
+/// associated type. `module` is where `path` is used, and `current_trait` is the node of the trait
+/// that `path` is used in, if any.
+pub fn find_associated_type(
+    symbol_tree: &SymbolTree,
+    module: &Module,
+    current_trait: Option<&SymbolTreeNode>,
+    path: &TypePath,
+) -> Result<Option<AssociatedType>> {
+    let segments = &path.path.segments;
+    let (trait_node, self_node, ident) = match &path.qself {
+        // e.g. `<Foo as Bar>::Buf`.
+        Some(qself) => {
+            if qself.position == 0 {
+                return Err(Error::new_spanned(
+                    path,
+                    "Qualified paths without a trait are not supported; use `<Type as Trait>::Assoc`",
+                ));
+            }
+            if segments.len() != qself.position + 1 {
+                return Err(Error::new_spanned(
+                    path,
+                    "Only associated types of the form `<Type as Trait>::Assoc` are supported",
+                ));
+            }
+            let trait_path = Path {
+                leading_colon: path.path.leading_colon,
+                segments: segments.iter().take(qself.position).cloned().collect(),
+            };
+            let trait_node = walk_path(module.clone(), &trait_path)?;
+            let self_node = match &*qself.ty {
+                Type::Path(ty) if ty.qself.is_none() && ty.path.is_ident("Self") => None,
+                Type::Path(ty) if ty.qself.is_none() => Some(walk_path(module.clone(), &ty.path)?),
+                ty => {
+                    return Err(Error::new_spanned(
+                        ty,
+                        "Only paths are supported as the type of a qualified path",
+                    ))
+                }
+            };
+            (trait_node, self_node, &segments.last().unwrap().ident)
+        }
+        // e.g. `Self::Buf`.
+        None if segments.len() == 2 && segments[0].ident == "Self" => {
+            let trait_node = current_trait.cloned().ok_or_else(|| {
+                Error::new_spanned(path, "`Self` is only supported in interface traits")
+            })?;
+            (trait_node, None, &segments[1].ident)
+        }
+        None => return Ok(None),
+    };
+
+    // `Self` must come from a trait.
+    if self_node.is_none() && current_trait.is_none() {
+        return Err(Error::new_spanned(
+            path,
+            "`Self` is only supported in interface traits",
+        ));
+    }
+
+    resolve(symbol_tree, &trait_node, self_node.as_ref(), ident).map(Some)
+}
+
+/// Returns the definition of the associated type `ident` of the trait `trait_node` for
+/// `self_node`, or for the trait object if `self_node` is `None`.
+fn resolve(
+    symbol_tree: &SymbolTree,
+    trait_node: &SymbolTreeNode,
+    self_node: Option<&SymbolTreeNode>,
+    ident: &Ident,
+) -> Result<AssociatedType> {
+    let trait_ref = trait_node.borrow();
+    let trait_name = path_to_string(&trait_ref.path);
+    let tr: &ItemTrait = match &trait_ref.terminal.as_ref().unwrap().definition {
+        Definition::Type(Item::Trait(tr)) => tr,
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                format!("`{}` is not a trait", trait_name),
+            ))
+        }
+    };
+    let declaration = tr
+        .items
+        .iter()
+        .find_map(|item| match item {
+            TraitItem::Type(ty) if ty.ident == *ident => Some(ty),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            Error::new_spanned(
+                ident,
+                format!("`{}` is not an associated type of `{}`", ident, trait_name),
+            )
+        })?;
+
+    // Look for the impl of the trait for the type.
+    if let Some(self_node) = self_node {
+        let self_path = self_node.borrow().path.clone();
+        for (module, imp) in &symbol_tree.impls {
+            if !implements(module, imp, &trait_ref.path, &self_path)? {
+                continue;
+            }
+            let definition = imp.items.iter().find_map(|item| match item {
+                ImplItem::Type(ty) if ty.ident == *ident => Some(ty.ty.clone()),
+                _ => None,
+            });
+            if let Some(ty) = definition {
+                return Ok(AssociatedType {
+                    ty,
+                    module: module.clone(),
+                });
+            }
+        }
+    }
+
+    // Fall back to the default in the trait.
+    match &declaration.default {
+        Some((_, ty)) => Ok(AssociatedType {
+            ty: ty.clone(),
+            module: trait_ref.get_parent_module()?,
+        }),
+        None => Err(Error::new_spanned(
+            ident,
+            match self_node {
+                Some(self_node) => format!(
+                    "Unable to resolve `<{} as {}>::{}`: there's no impl of the trait for the type in the IDL and the associated type has no default",
+                    path_to_string(&self_node.borrow().path),
+                    trait_name,
+                    ident
+                ),
+                None => format!(
+                    "Unable to resolve `Self::{}`: associated type `{}` of `{}` must have a default to be used in interfaces",
+                    ident, ident, trait_name
+                ),
+            },
+        )),
+    }
+}
+
+/// Returns true if `imp`, which is in `module`, is the impl of the trait `trait_path` for the type
+/// `self_path`. A generic impl of the trait that may be for the type is an error, since which
+/// definition applies depends on its bounds.
+fn implements(
+    module: &Module,
+    imp: &syn::ItemImpl,
+    trait_path: &[Ident],
+    self_path: &[Ident],
+) -> Result<bool> {
+    let resolves_to = |path: &Path, expected: &[Ident]| match walk_path(module.clone(), path) {
+        Ok(node) => node.borrow().path == expected,
+        Err(_) => false,
+    };
+    let (_, imp_trait, _) = imp.trait_.as_ref().unwrap();
+    if !resolves_to(imp_trait, trait_path) {
+        return Ok(false);
+    }
+    let imp_self = match &*imp.self_ty {
+        Type::Path(ty) if ty.qself.is_none() => Some(&ty.path),
+        _ => None,
+    };
+    if !imp.generics.params.is_empty() {
+        // A blanket impl, e.g. `impl<T> Device for T`, or one for the type, e.g.
+        // `impl<T> Device for Disk<T>`.
+        let may_apply = match imp_self {
+            Some(path) => {
+                imp.generics
+                    .type_params()
+                    .any(|param| path.is_ident(&param.ident))
+                    || resolves_to(path, self_path)
+            }
+            None => true,
+        };
+        if may_apply {
+            return Err(Error::new_spanned(
+                &imp.generics,
+                format!(
+                    "Unable to resolve the associated types of `{}` for `{}`: generic impls are not supported",
+                    path_to_string(trait_path),
+                    path_to_string(self_path)
+                ),
+            ));
+        }
+        return Ok(false);
+    }
+    Ok(matches!(imp_self, Some(path) if resolves_to(path, self_path)))
+}
@@ -0,0 +1,413 @@
+//! Evaluation of the constant expressions in types, e.g. the length of `[u8; BSIZE * 2]` or the
+//! `N` of `RRefDeque<BlkReq, { QUEUE * 2 }>`.
+//!
+//! Constants are evaluated to suffixed integer literals so that the same value spelled differently
+//! is the same type, e.g. `[u8; BSIZE]` and `[u8; 4096]` are both `[u8; 4096usize]`.
+
+use std::collections::HashMap;
+
+use proc_macro2::Span;
+use syn::{BinOp, Expr, Ident, Lit, LitInt, Path, Type, UnOp};
+
+use super::symbol_tree::*;
+use super::utils::{path_to_string, walk_path};
+use crate::error::{Error, Result};
+
+/// The maximum number of constants to follow when evaluating an expression. Anything deeper is a
+/// constant defined in terms of itself.
+const MAX_CONST_DEPTH: usize = 64;
+
+/// The integer types and their bit widths. `usize` and `isize` are 64-bit like the kernel.
+const INT_TYPES: &[(&str, u32, bool)] = &[
+    ("u8", 8, false),
+    ("u16", 16, false),
+    ("u32", 32, false),
+    ("u64", 64, false),
+    ("u128", 128, false),
+    ("usize", 64, false),
+    ("i8", 8, true),
+    ("i16", 16, true),
+    ("i32", 32, true),
+    ("i64", 64, true),
+    ("i128", 128, true),
+    ("isize", 64, true),
+];
+
+/// An integer value and its type, which is `None` for an unsuffixed literal until it meets a typed
+/// value.
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    value: i128,
+    ty: Option<&'static str>,
+}
+
+/// Evaluates the constant expressions used in a module.
+pub struct ConstEvaluator<'a> {
+    /// The module where the expressions are.
+    module: Module,
+    /// The values of the const generic parameters in scope.
+    generic_args: &'a HashMap<Ident, Lit>,
+    /// The number of constants followed so far.
+    depth: usize,
+}
+
+impl<'a> ConstEvaluator<'a> {
+    pub fn new(module: Module, generic_args: &'a HashMap<Ident, Lit>) -> Self {
+        Self {
+            module,
+            generic_args,
+            depth: 0,
+        }
+    }
+
+    /// Evaluates `expr` to an integer literal of type `ty`, e.g. `4096usize`.
+    pub fn eval_as(&self, expr: &Expr, ty: &str) -> Result<Lit> {
+        let ty = int_type(ty)
+            .ok_or_else(|| Error::new_spanned(expr, format!("`{}` is not an integer type", ty)))?;
+        let value = self.eval_expr(expr)?;
+        match value.ty {
+            Some(actual) if actual != ty => Err(Error::new_spanned(
+                expr,
+                format!("Expecting a `{}` constant, but found a `{}`", ty, actual),
+            )),
+            _ => to_lit(
+                expr,
+                Value {
+                    ty: Some(ty),
+                    ..value
+                },
+            ),
+        }
+    }
+
+    /// Evaluates `expr` to an integer literal. It's a `usize` if its type can't be told.
+    pub fn eval(&self, expr: &Expr) -> Result<Lit> {
+        let value = self.eval_expr(expr)?;
+        to_lit(
+            expr,
+            Value {
+                ty: Some(value.ty.unwrap_or("usize")),
+                ..value
+            },
+        )
+    }
+
+    /// Returns true if `path` is a constant or a const generic parameter rather than a type, e.g.
+    /// the `QUEUE` of `RRefDeque<BlkReq, QUEUE>`.
+    pub fn is_constant(&self, path: &Path) -> bool {
+        if let Some(ident) = path.get_ident() {
+            if self.generic_args.contains_key(ident) {
+                return true;
+            }
+        }
+        if self.module.borrow().get(&path.segments[0].ident).is_none() {
+            return false;
+        }
+        match walk_path(self.module.clone(), path) {
+            Ok(node) => matches!(
+                node.borrow()
+                    .terminal
+                    .as_ref()
+                    .map(|terminal| &terminal.definition),
+                Some(Definition::Constant(_))
+            ),
+            Err(_) => false,
+        }
+    }
+
+    fn eval_expr(&self, expr: &Expr) -> Result<Value> {
+        match expr {
+            Expr::Lit(lit) => match &lit.lit {
+                Lit::Int(int) => {
+                    let ty = match int.suffix() {
+                        "" => None,
+                        suffix => Some(int_type(suffix).ok_or_else(|| {
+                            Error::new_spanned(int, format!("Unknown integer suffix `{}`", suffix))
+                        })?),
+                    };
+                    let value = int
+                        .base10_parse::<i128>()
+                        .map_err(|_| Error::new_spanned(int, "Integer literal is too large"))?;
+                    check_range(expr, Value { value, ty })
+                }
+                lit => Err(Error::new_spanned(
+                    lit,
+                    "Only integer constants are supported in types",
+                )),
+            },
+            Expr::Path(path) if path.qself.is_none() => self.eval_path(&path.path),
+            Expr::Paren(paren) => self.eval_expr(&paren.expr),
+            Expr::Group(group) => self.eval_expr(&group.expr),
+            Expr::Block(block) if block.block.stmts.len() == 1 => match &block.block.stmts[0] {
+                syn::Stmt::Expr(expr) => self.eval_expr(expr),
+                stmt => Err(Error::new_spanned(
+                    stmt,
+                    "Only a single expression is supported in a constant block",
+                )),
+            },
+            Expr::Unary(unary) => {
+                let operand = self.eval_expr(&unary.expr)?;
+                let value = match unary.op {
+                    UnOp::Neg(_) => {
+                        if let Some(ty) = operand.ty {
+                            if !is_signed(ty) {
+                                return Err(Error::new_spanned(
+                                    unary,
+                                    format!("Cannot negate a `{}`", ty),
+                                ));
+                            }
+                        }
+                        -operand.value
+                    }
+                    UnOp::Not(_) => match operand.ty {
+                        Some(ty) => wrap(!operand.value, ty),
+                        None => {
+                            return Err(Error::new_spanned(
+                                unary,
+                                "The type of the operand of `!` must be known, e.g. `!0usize`",
+                            ))
+                        }
+                    },
+                    UnOp::Deref(_) => {
+                        return Err(Error::new_spanned(
+                            unary,
+                            "Dereference is not supported in constants",
+                        ))
+                    }
+                };
+                check_range(
+                    expr,
+                    Value {
+                        value,
+                        ty: operand.ty,
+                    },
+                )
+            }
+            Expr::Binary(binary) => {
+                let lhs = self.eval_expr(&binary.left)?;
+                let rhs = self.eval_expr(&binary.right)?;
+                self.eval_binary(expr, &binary.op, lhs, rhs)
+            }
+            Expr::Cast(cast) => {
+                let operand = self.eval_expr(&cast.expr)?;
+                let ty = match &*cast.ty {
+                    Type::Path(ty) => ty.path.get_ident().and_then(|ident| int_type(&ident.to_string())),
+                    _ => None,
+                }
+                .ok_or_else(|| {
+                    Error::new_spanned(&cast.ty, "Constants can only be cast to integer types")
+                })?;
+                Ok(Value {
+                    value: wrap(operand.value, ty),
+                    ty: Some(ty),
+                })
+            }
+            expr => Err(Error::new_spanned(
+                expr,
+                "Unsupported constant expression; expecting integers, constants, arithmetic, shifts or casts",
+            )),
+        }
+    }
+
+    fn eval_binary(&self, expr: &Expr, op: &BinOp, lhs: Value, rhs: Value) -> Result<Value> {
+        // The operands of a shift may have different types; the result has the type of the left.
+        let shift = matches!(op, BinOp::Shl(_) | BinOp::Shr(_));
+        let ty = match (lhs.ty, rhs.ty) {
+            _ if shift => lhs.ty,
+            (Some(lhs), Some(rhs)) if lhs != rhs => {
+                return Err(Error::new_spanned(
+                    expr,
+                    format!("Mismatched types `{}` and `{}`", lhs, rhs),
+                ))
+            }
+            (lhs, rhs) => lhs.or(rhs),
+        };
+
+        let overflow = || Error::new_spanned(expr, "Overflow in constant expression");
+        let (lhs, rhs) = (lhs.value, rhs.value);
+        let value = match op {
+            BinOp::Add(_) => lhs.checked_add(rhs),
+            BinOp::Sub(_) => lhs.checked_sub(rhs),
+            BinOp::Mul(_) => lhs.checked_mul(rhs),
+            BinOp::Div(_) | BinOp::Rem(_) if rhs == 0 => {
+                return Err(Error::new_spanned(
+                    expr,
+                    "Division by zero in constant expression",
+                ))
+            }
+            BinOp::Div(_) => lhs.checked_div(rhs),
+            BinOp::Rem(_) => lhs.checked_rem(rhs),
+            BinOp::BitAnd(_) => Some(lhs & rhs),
+            BinOp::BitOr(_) => Some(lhs | rhs),
+            BinOp::BitXor(_) => Some(lhs ^ rhs),
+            BinOp::Shl(_) | BinOp::Shr(_) => {
+                let bits = ty.map(int_bits).unwrap_or(64);
+                if rhs < 0 || rhs >= bits as i128 {
+                    return Err(overflow());
+                }
+                match op {
+                    BinOp::Shl(_) => lhs.checked_shl(rhs as u32),
+                    _ => lhs.checked_shr(rhs as u32),
+                }
+            }
+            op => {
+                return Err(Error::new_spanned(
+                    op,
+                    "Unsupported operator in constant expression",
+                ))
+            }
+        }
+        .ok_or_else(overflow)?;
+        check_range(expr, Value { value, ty })
+    }
+
+    /// Evaluates a const generic parameter or a constant.
+    fn eval_path(&self, path: &Path) -> Result<Value> {
+        if let Some(ident) = path.get_ident() {
+            if let Some(lit) = self.generic_args.get(ident) {
+                return self.eval_expr(&Expr::Lit(syn::ExprLit {
+                    attrs: vec![],
+                    lit: lit.clone(),
+                }));
+            }
+        }
+
+        if self.module.borrow().get(&path.segments[0].ident).is_none() {
+            return Err(Error::new_spanned(
+                path,
+                "Constants must not come from external crates",
+            ));
+        }
+        let node = walk_path(self.module.clone(), path)?;
+        let node = node.borrow();
+        let constant = match &node.terminal.as_ref().unwrap().definition {
+            Definition::Constant(constant) => constant,
+            _ => {
+                return Err(Error::new_spanned(
+                    path,
+                    format!(
+                        "`{}` must be resolved to a compile time constant",
+                        path_to_string(&node.path)
+                    ),
+                ))
+            }
+        };
+        if self.depth >= MAX_CONST_DEPTH {
+            return Err(Error::new_spanned(
+                path,
+                format!(
+                    "Constant `{}` is defined in terms of itself",
+                    path_to_string(&node.path)
+                ),
+            ));
+        }
+
+        // Evaluate the constant where it's defined, as its declared type.
+        let ty = match &*constant.ty {
+            Type::Path(ty) => ty
+                .path
+                .get_ident()
+                .and_then(|ident| int_type(&ident.to_string())),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            Error::new_spanned(
+                path,
+                format!(
+                    "`{}` must be an integer constant to be used in types",
+                    path_to_string(&node.path)
+                ),
+            )
+        })?;
+        let no_generic_args = HashMap::new();
+        let evaluator = ConstEvaluator {
+            module: node.get_parent_module()?,
+            generic_args: &no_generic_args,
+            depth: self.depth + 1,
+        };
+        let value = evaluator.eval_expr(&constant.expr)?;
+        match value.ty {
+            Some(actual) if actual != ty => Err(Error::new_spanned(
+                &constant.expr,
+                format!("Expecting a `{}` constant, but found a `{}`", ty, actual),
+            )),
+            _ => check_range(
+                &constant.expr,
+                Value {
+                    value: value.value,
+                    ty: Some(ty),
+                },
+            ),
+        }
+    }
+}
+
+/// Returns the static name of the integer type `ty`, if it is one.
+fn int_type(ty: &str) -> Option<&'static str> {
+    INT_TYPES
+        .iter()
+        .find(|(name, _, _)| *name == ty)
+        .map(|(name, _, _)| *name)
+}
+
+fn int_bits(ty: &str) -> u32 {
+    INT_TYPES.iter().find(|(name, _, _)| *name == ty).unwrap().1
+}
+
+fn is_signed(ty: &str) -> bool {
+    INT_TYPES.iter().find(|(name, _, _)| *name == ty).unwrap().2
+}
+
+/// Returns the range of values of the integer type `ty`.
+fn range(ty: &str) -> (i128, i128) {
+    let bits = int_bits(ty);
+    match (is_signed(ty), bits) {
+        (true, bits) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+        // `u128` doesn't fit in `i128`; values above `i128::MAX` are not supported.
+        (false, 128) => (0, i128::MAX),
+        (false, bits) => (0, (1 << bits) - 1),
+    }
+}
+
+/// Truncates `value` to the integer type `ty` like an `as` cast.
+fn wrap(value: i128, ty: &str) -> i128 {
+    let bits = int_bits(ty);
+    if bits == 128 {
+        return value;
+    }
+    let truncated = value & ((1 << bits) - 1);
+    if is_signed(ty) && truncated >= 1 << (bits - 1) {
+        truncated - (1 << bits)
+    } else {
+        truncated
+    }
+}
+
+/// Returns an error if `value` doesn't fit in its type.
+fn check_range(expr: impl quote::ToTokens, value: Value) -> Result<Value> {
+    if let Some(ty) = value.ty {
+        let (min, max) = range(ty);
+        if value.value < min || value.value > max {
+            return Err(Error::new_spanned(
+                expr,
+                format!("Constant `{}` overflows `{}`", value.value, ty),
+            ));
+        }
+    }
+    Ok(value)
+}
+
+/// Returns `value` as a suffixed literal.
+fn to_lit(expr: &Expr, value: Value) -> Result<Lit> {
+    let value = check_range(expr, value)?;
+    if value.value < 0 {
+        return Err(Error::new_spanned(
+            expr,
+            "Negative constants are not supported in types",
+        ));
+    }
+    Ok(Lit::Int(LitInt::new(
+        &format!("{}{}", value.value, value.ty.unwrap()),
+        Span::call_site(),
+    )))
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use quote::{format_ident, quote};
+use syn::{parse_quote, Expr, Lit};
+
+use super::const_eval::ConstEvaluator;
+use super::type_info_finder::TypeInfoFinder;
+
+const CONSTANTS: &str = "
+    pub const PAGE: usize = 4096;
+    pub const PAGES: usize = 4;
+    pub const BUFFER: usize = PAGE * PAGES;
+    pub const SHIFT: u32 = 3;
+    pub const NOT_INT: &str = \"\";
+    pub mod queue {
+        pub const QUEUE: usize = super::PAGES << 5;
+    }
+";
+
+/// Evaluates `expr` in the root module of `CONSTANTS` and returns the literal as a string.
+fn eval(expr: Expr, ty: Option<&str>) -> Result<String, String> {
+    let ast = syn::parse_file(CONSTANTS).unwrap();
+    let symbol_tree = TypeInfoFinder::new().find_type_info(&ast).unwrap();
+    let generic_args: HashMap<_, Lit> = vec![(format_ident!("N"), parse_quote!(8usize))]
+        .into_iter()
+        .collect();
+    let evaluator = ConstEvaluator::new(symbol_tree.root_module(), &generic_args);
+    let result = match ty {
+        Some(ty) => evaluator.eval_as(&expr, ty),
+        None => evaluator.eval(&expr),
+    };
+    result
+        .map(|lit| quote!(#lit).to_string())
+        .map_err(|err| err.message().to_owned())
+}
+
+#[test]
+fn test_eval() {
+    // Unsuffixed literals are `usize` unless told otherwise.
+    assert_eq!(
+        eval(parse_quote!(4096), None),
+        Ok(String::from("4096usize"))
+    );
+    assert_eq!(
+        eval(parse_quote!(4096), Some("u32")),
+        Ok(String::from("4096u32"))
+    );
+
+    // Arithmetic, shifts, casts and blocks.
+    assert_eq!(
+        eval(parse_quote!({ (PAGE + 1) * 2 - 2 / 2 % 5 }), None),
+        Ok(String::from("8193usize"))
+    );
+    assert_eq!(
+        eval(parse_quote!(1 << SHIFT), None),
+        Ok(String::from("8usize"))
+    );
+    assert_eq!(
+        eval(parse_quote!(0xff & 0x0f | 0x30 ^ 0x10), None),
+        Ok(String::from("47usize"))
+    );
+    assert_eq!(
+        eval(parse_quote!(300u32 as u8), None),
+        Ok(String::from("44u8"))
+    );
+    assert_eq!(
+        eval(parse_quote!(-1i8 as u8), None),
+        Ok(String::from("255u8"))
+    );
+
+    // Constants defined in terms of other constants, and const generic parameters.
+    assert_eq!(
+        eval(parse_quote!(BUFFER), None),
+        Ok(String::from("16384usize"))
+    );
+    assert_eq!(
+        eval(parse_quote!(queue::QUEUE * N), None),
+        Ok(String::from("1024usize"))
+    );
+}
+
+#[test]
+fn test_eval_errors() {
+    assert_eq!(
+        eval(parse_quote!(SHIFT), Some("usize")),
+        Err(String::from(
+            "Expecting a `usize` constant, but found a `u32`"
+        ))
+    );
+    assert_eq!(
+        eval(parse_quote!(PAGE + SHIFT), None),
+        Err(String::from("Mismatched types `usize` and `u32`"))
+    );
+    assert_eq!(
+        eval(parse_quote!(255u8 + 1), None),
+        Err(String::from("Constant `256` overflows `u8`"))
+    );
+    assert_eq!(
+        eval(parse_quote!(PAGE / (PAGES - 4)), None),
+        Err(String::from("Division by zero in constant expression"))
+    );
+    assert_eq!(
+        eval(parse_quote!(NOT_INT), None),
+        Err(String::from(
+            "`crate::NOT_INT` must be an integer constant to be used in types"
+        ))
+    );
+    assert_eq!(
+        eval(parse_quote!(core::mem::size_of::<u8>()), None),
+        Err(String::from(
+            "Unsupported constant expression; expecting integers, constants, arithmetic, shifts or casts"
+        ))
+    );
+}
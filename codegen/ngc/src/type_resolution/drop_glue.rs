@@ -0,0 +1,168 @@
+//! Drop glue of the `RRef`ed types.
+//!
+//! When a domain dies, the kernel reclaims the objects that the domain owns on the shared heap. It
+//! only knows the type ID of each object, so the `DropMap` maps the type ID to the drop glue of the
+//! type. The glue runs the `CustomCleanup` of the object and then releases the `RRef`s nested in
+//! it, which recursively release the `RRef`s nested in them.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{GenericArgument, Ident, ItemFn, Member, PathArguments, Type, TypePath};
+
+use super::rref_kind::RRefKind;
+use super::rrefed_finder::{Fields, TypeFields};
+use super::type_id;
+
+/// Generates the drop glue of `ty`, whose type ID is `id`. Returns the name and the definition of
+/// the glue.
+pub fn generate_drop_glue(ty: &Type, id: u64, type_fields: &TypeFields) -> (Ident, ItemFn) {
+    let ident = format_ident!("drop_{}", id);
+    let release = release_nested(&quote!((*value_)), ty, type_fields, 0);
+    let doc = format!(
+        " Drops the `{}` that `ptr` points to.",
+        type_id::canonical_name(ty)
+    );
+    let glue = syn::parse_quote! {
+        #[doc = #doc]
+        unsafe fn #ident(ptr: *mut u8) {
+            let value_ = ptr as *mut #ty;
+            // Run the custom cleanup of the object.
+            CustomCleanup::cleanup(&mut *value_);
+            // Release the nested `RRef`s.
+            #(#release)*
+        }
+    };
+    (ident, glue)
+}
+
+/// Generates the statements that release the `RRef`s nested in `place`, which is of type `ty`.
+/// Empty if there's nothing to release.
+fn release_nested(
+    place: &TokenStream,
+    ty: &Type,
+    type_fields: &TypeFields,
+    depth: usize,
+) -> Vec<TokenStream> {
+    // The `RRef`s drop their content and give the memory back to the shared heap.
+    if RRefKind::of_type(ty).is_some() {
+        return vec![quote! {
+            core::ptr::drop_in_place(&mut #place);
+        }];
+    }
+
+    match ty {
+        Type::Path(path) => {
+            // Release the fields of a struct or an enum.
+            match type_fields.get(ty) {
+                Some(Fields::Struct(fields)) => {
+                    return fields
+                        .iter()
+                        .flat_map(|(member, field_ty)| {
+                            release_nested(&quote!(#place.#member), field_ty, type_fields, depth)
+                        })
+                        .collect();
+                }
+                Some(Fields::Enum(variants)) => {
+                    return release_variants(place, path, variants, type_fields, depth);
+                }
+                // The active field of a union is unknown, so none of them can be released. The
+                // `RRef`s in a union have to be in `ManuallyDrop` anyway.
+                Some(Fields::Union(_)) => return vec![],
+                None => {}
+            }
+
+            // Release the content of an `Option`.
+            let last_segment = path.path.segments.last().unwrap();
+            if last_segment.ident != "Option" {
+                return vec![];
+            }
+            let inner_ty = match &last_segment.arguments {
+                PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(GenericArgument::Type(ty)) => ty,
+                    _ => return vec![],
+                },
+                _ => return vec![],
+            };
+            let binding = format_ident!("elem_{}_", depth);
+            let release = release_nested(&quote!((*#binding)), inner_ty, type_fields, depth + 1);
+            if release.is_empty() {
+                return vec![];
+            }
+            vec![quote! {
+                if let Some(#binding) = &mut #place {
+                    #(#release)*
+                }
+            }]
+        }
+        Type::Tuple(tuple) => tuple
+            .elems
+            .iter()
+            .enumerate()
+            .flat_map(|(i, elem)| {
+                let index = syn::Index::from(i);
+                release_nested(&quote!(#place.#index), elem, type_fields, depth)
+            })
+            .collect(),
+        Type::Array(array) => {
+            let binding = format_ident!("elem_{}_", depth);
+            let release = release_nested(&quote!((*#binding)), &array.elem, type_fields, depth + 1);
+            if release.is_empty() {
+                return vec![];
+            }
+            vec![quote! {
+                for #binding in #place.iter_mut() {
+                    #(#release)*
+                }
+            }]
+        }
+        Type::Paren(paren) => release_nested(place, &paren.elem, type_fields, depth),
+        _ => vec![],
+    }
+}
+
+/// Generates the statements that release the `RRef`s nested in the variant of the enum `place`,
+/// which is of type `ty`.
+fn release_variants(
+    place: &TokenStream,
+    ty: &TypePath,
+    variants: &[(Ident, Vec<(Member, Type)>)],
+    type_fields: &TypeFields,
+    depth: usize,
+) -> Vec<TokenStream> {
+    // The generic arguments are inferred in patterns.
+    let enum_path = ty.path.segments.iter().map(|segment| &segment.ident);
+    let mut arms = vec![];
+    for (variant, fields) in variants {
+        // Bind only the fields that have something to release.
+        let mut patterns = vec![];
+        let mut release = vec![];
+        for (i, (member, field_ty)) in fields.iter().enumerate() {
+            let binding = format_ident!("field_{}_{}_", depth, i);
+            let field_release =
+                release_nested(&quote!((*#binding)), field_ty, type_fields, depth + 1);
+            if !field_release.is_empty() {
+                patterns.push(quote!(#member: #binding));
+                release.extend(field_release);
+            }
+        }
+        if release.is_empty() {
+            continue;
+        }
+        let enum_path = enum_path.clone();
+        arms.push(quote! {
+            #(#enum_path)::*::#variant { #(#patterns,)* .. } => {
+                #(#release)*
+            }
+        });
+    }
+
+    if arms.is_empty() {
+        return vec![];
+    }
+    vec![quote! {
+        match &mut #place {
+            #(#arms)*
+            _ => {}
+        }
+    }]
+}
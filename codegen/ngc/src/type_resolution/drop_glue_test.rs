@@ -0,0 +1,256 @@
+use quote::{format_ident, quote};
+use syn::{Item, ItemFn};
+
+use super::type_id;
+
+const RREF_MOD: &str = "
+    pub mod rref {
+        pub mod rref {
+            pub struct RRef<T> {
+                value_pointer: *mut T,
+            }
+        }
+        pub mod rref_array {
+            pub struct RRefArray<T, const N: usize> {
+                arr: crate::rref::rref::RRef<[Option<crate::rref::rref::RRef<T>>; N]>,
+            }
+        }
+        pub mod rref_deque {
+            pub struct RRefDeque<T, const N: usize> {
+                arr: crate::rref::rref_array::RRefArray<T, N>,
+                head: usize,
+                tail: usize,
+            }
+        }
+    }
+";
+
+/// Generates the `typeid` module for `input` and returns the drop glue of `ty`.
+fn drop_glue_of(input: &str, ty: &str) -> ItemFn {
+    let mut ast = syn::parse_file(&format!("{}{}", RREF_MOD, input)).unwrap();
+    super::generate_typeid(&mut ast, &[]).unwrap();
+
+    let ty: syn::Type = syn::parse_str(ty).unwrap();
+    let ident = format!("drop_{}", type_id::type_id(&ty));
+    ast.items
+        .into_iter()
+        .find_map(|item| match item {
+            Item::Mod(md) if md.ident == "typeid" => {
+                md.content
+                    .unwrap()
+                    .1
+                    .into_iter()
+                    .find_map(|item| match item {
+                        Item::Fn(glue) if glue.sig.ident == ident => Some(glue),
+                        _ => None,
+                    })
+            }
+            _ => None,
+        })
+        .unwrap()
+}
+
+/// Returns the statements of `glue` after the cast and the cleanup.
+fn releases(glue: &ItemFn) -> String {
+    let stmts = &glue.block.stmts[2..];
+    quote!(#(#stmts)*).to_string()
+}
+
+#[test]
+fn test_cleanup_without_nested_rref() {
+    let input = "
+        pub struct Plain {
+            x: usize,
+            y: [u8; 4],
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn bar(&self, x: crate::rref::rref::RRef<Plain>) -> ();
+        }
+    ";
+
+    let glue = drop_glue_of(input, "crate::Plain");
+    let expected: syn::Block = syn::parse_quote! {{
+        let value_ = ptr as *mut crate::Plain;
+        CustomCleanup::cleanup(&mut *value_);
+    }};
+    let block = &glue.block;
+    assert_eq!(quote!(#expected).to_string(), quote!(#block).to_string());
+}
+
+#[test]
+fn test_release_nested_rrefs() {
+    let input = "
+        use crate::rref::rref::RRef;
+        use crate::rref::rref_array::RRefArray;
+        use crate::rref::rref_deque::RRefDeque;
+
+        pub struct Inner {
+            buf: RRef<[u8; 4]>,
+        }
+
+        pub struct Outer {
+            x: usize,
+            inner: Inner,
+            single: RRef<usize>,
+            maybe: Option<RRef<u8>>,
+            array: RRefArray<u8, 4>,
+            deque: RRefDeque<u8, 4>,
+            pair: (Inner, Option<Inner>),
+            many: [RRef<u8>; 2],
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn bar(&self, x: RRef<Outer>) -> ();
+        }
+    ";
+
+    let glue = drop_glue_of(input, "crate::Outer");
+    let expected = quote! {
+        core::ptr::drop_in_place(&mut (*value_).inner.buf);
+        core::ptr::drop_in_place(&mut (*value_).single);
+        if let Some(elem_0_) = &mut (*value_).maybe {
+            core::ptr::drop_in_place(&mut (*elem_0_));
+        }
+        core::ptr::drop_in_place(&mut (*value_).array);
+        core::ptr::drop_in_place(&mut (*value_).deque);
+        core::ptr::drop_in_place(&mut (*value_).pair.0.buf);
+        if let Some(elem_0_) = &mut (*value_).pair.1 {
+            core::ptr::drop_in_place(&mut (*elem_0_).buf);
+        }
+        for elem_0_ in (*value_).many.iter_mut() {
+            core::ptr::drop_in_place(&mut (*elem_0_));
+        }
+    };
+    assert_eq!(expected.to_string(), releases(&glue));
+}
+
+#[test]
+fn test_release_recursive_struct() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub struct Node {
+            value: usize,
+            next: Option<RRef<Node>>,
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn bar(&self, x: RRef<Node>) -> ();
+        }
+    ";
+
+    // Releasing the next node runs its drop glue, which releases the rest of the list.
+    let glue = drop_glue_of(input, "crate::Node");
+    let expected = quote! {
+        if let Some(elem_0_) = &mut (*value_).next {
+            core::ptr::drop_in_place(&mut (*elem_0_));
+        }
+    };
+    assert_eq!(expected.to_string(), releases(&glue));
+}
+
+#[test]
+fn test_drop_map() {
+    let input = "
+        #[interface]
+        pub trait Foo {
+            fn bar(&self, x: crate::rref::rref::RRef<usize>) -> crate::rref::rref::RRef<()>;
+        }
+    ";
+
+    let mut ast = syn::parse_file(&format!("{}{}", RREF_MOD, input)).unwrap();
+    super::generate_typeid(&mut ast, &[]).unwrap();
+    let get_drop =
+        ast.items
+            .into_iter()
+            .find_map(|item| match item {
+                Item::Mod(md) if md.ident == "typeid" => md
+                    .content
+                    .unwrap()
+                    .1
+                    .into_iter()
+                    .find_map(|item| match item {
+                        Item::Impl(imp) => imp.items.into_iter().find_map(|item| match item {
+                            syn::ImplItem::Method(method) if method.sig.ident == "get_drop" => {
+                                Some(method.block)
+                            }
+                            _ => None,
+                        }),
+                        _ => None,
+                    }),
+                _ => None,
+            })
+            .unwrap();
+
+    // The types are looked up in a `match` over their ids.
+    let unit = format_ident!("drop_{}", type_id::type_id(&syn::parse_quote!(())));
+    let usize = format_ident!("drop_{}", type_id::type_id(&syn::parse_quote!(usize)));
+    let expected = quote! {{
+        match type_id {
+            567769145981429338u64 => Some(#unit as unsafe fn(*mut u8) -> ()),
+            15670484296631260865u64 => Some(#usize as unsafe fn(*mut u8) -> ()),
+            _ => None,
+        }
+    }};
+    assert_eq!(expected.to_string(), quote!(#get_drop).to_string());
+}
+
+#[test]
+fn test_release_enum_variants() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub enum Packet<T> {
+            Empty,
+            Plain(usize),
+            Data(RRef<[u8; 64]>, T),
+            Framed { len: usize, payload: Option<RRef<T>> },
+        }
+
+        #[interface]
+        pub trait Net {
+            fn send(&self, packet: RRef<Packet<u16>>) -> ();
+        }
+    ";
+
+    // Only the variants with something to release get an arm.
+    let glue = drop_glue_of(input, "crate::Packet<u16>");
+    let expected = quote! {
+        match &mut (*value_) {
+            crate::Packet::Data { 0: field_0_0_, .. } => {
+                core::ptr::drop_in_place(&mut (*field_0_0_));
+            }
+            crate::Packet::Framed { payload: field_0_1_, .. } => {
+                if let Some(elem_1_) = &mut (*field_0_1_) {
+                    core::ptr::drop_in_place(&mut (*elem_1_));
+                }
+            }
+            _ => {}
+        }
+    };
+    assert_eq!(expected.to_string(), releases(&glue));
+}
+
+#[test]
+fn test_union_releases_nothing() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub union Raw {
+            word: u64,
+            buf: core::mem::ManuallyDrop<RRef<[u8; 8]>>,
+        }
+
+        #[interface]
+        pub trait Net {
+            fn send(&self, raw: RRef<Raw>) -> ();
+        }
+    ";
+
+    let glue = drop_glue_of(input, "crate::Raw");
+    assert_eq!("", releases(&glue));
+}
@@ -1,45 +1,82 @@
-use quote::quote;
+use quote::{format_ident, quote};
+use syn::{Item, Type};
 
-fn expect_to_generate_typeid(input: &str, types: Vec<&str>) {
+use super::{type_id, ExternCrate};
+
+const RREF_MOD: &str = "
+    pub mod rref {
+        pub mod rref {
+            pub struct RRef<T> {
+                value_pointer: *mut T,
+            }
+        }
+    }
+";
+
+/// Returns the `TypeIdentifiable` impls in the generated `typeid` module as `(type, id)` and the
+/// rest of `ast`.
+fn split_typeid(ast: syn::File) -> (Vec<(String, String)>, syn::File) {
+    let mut impls = vec![];
+    let mut rest = ast;
+    rest.items.retain(|item| match item {
+        Item::Mod(md) if md.ident == "typeid" => {
+            for item in &md.content.as_ref().unwrap().1 {
+                if let Item::Impl(imp) = item {
+                    if quote!(#imp).to_string().contains("TypeIdentifiable for") {
+                        let ty = &imp.self_ty;
+                        let id = match &imp.items[0] {
+                            syn::ImplItem::Method(method) => &method.block,
+                            _ => unreachable!(),
+                        };
+                        impls.push((quote!(#ty).to_string(), quote!(#id).to_string()));
+                    }
+                }
+            }
+            false
+        }
+        _ => true,
+    });
+    (impls, rest)
+}
+
+fn expect_to_generate_typeid(input: &str, resolved: &str, types: Vec<&str>) {
+    expect_to_generate_typeid_with(input, resolved, types, vec![]);
+}
+
+/// Same as `expect_to_generate_typeid` but with the sources of the external crates as
+/// `(name, source)`.
+fn expect_to_generate_typeid_with(
+    input: &str,
+    resolved: &str,
+    types: Vec<&str>,
+    extern_crates: Vec<(&str, &str)>,
+) {
     // Get expected output
-    let expected_output = format!(
-        "
-        {}
-
-        pub mod typeid {{
-            pub trait TypeIdentifiable {{
-                fn type_id() -> u64;
-            }}
-
-            {}
-        }}
-    ",
-        input,
-        types
-            .iter()
-            .enumerate()
-            .map(|(i, ty)| {
-                format!(
-                    "
-            impl TypeIdentifiable for {} {{
-                fn type_id() -> u64 {{
-                    {}u64
-                }}
-            }}
-        ",
-                    ty, i
-                )
-            })
-            .collect::<Vec<String>>()
-            .join("")
-    );
-    let expected_ast = syn::parse_file(&expected_output).unwrap();
+    let mut types: Vec<Type> = types.iter().map(|ty| syn::parse_str(ty).unwrap()).collect();
+    types.sort_by_key(type_id::canonical_name);
+    let expected_impls: Vec<(String, String)> = types
+        .iter()
+        .map(|ty| {
+            let id = type_id::type_id(ty);
+            (quote!(#ty).to_string(), quote!({ #id }).to_string())
+        })
+        .collect();
+    let expected_ast = syn::parse_file(&format!("{}{}", RREF_MOD, resolved)).unwrap();
 
     // Generate code.
-    let mut ast = syn::parse_file(input).unwrap();
-    super::generate_typeid(&mut ast);
+    let extern_crates: Vec<ExternCrate> = extern_crates
+        .into_iter()
+        .map(|(name, source)| ExternCrate {
+            name: format_ident!("{}", name),
+            ast: syn::parse_file(source).unwrap(),
+        })
+        .collect();
+    let mut ast = syn::parse_file(&format!("{}{}", RREF_MOD, input)).unwrap();
+    super::generate_typeid(&mut ast, &extern_crates).unwrap();
+    let (impls, ast) = split_typeid(ast);
 
     // Assert equality
+    assert_eq!(expected_impls, impls);
     assert_eq!(quote!(#expected_ast).to_string(), quote!(#ast).to_string());
 }
 
@@ -48,11 +85,11 @@ fn simple_test() {
     let input = "
         #[interface]
         pub trait Foo {
-            fn bar(&self, fd: usize) -> ();
+            fn bar(&self, fd: crate::rref::rref::RRef<usize>) -> crate::rref::rref::RRef<()>;
         }
     ";
 
-    expect_to_generate_typeid(input, vec!["usize", "()"]);
+    expect_to_generate_typeid(input, input, vec!["usize", "()"]);
 }
 
 #[test]
@@ -60,12 +97,820 @@ fn test_generic() {
     let input = "
         use alloc::vec::Vec;
         use asd::X;
+        use crate::rref::rref::RRef;
 
         #[interface]
         pub trait Foo {
-            fn bar(&self, car: Vec<X>) -> ();
+            fn bar(&self, car: RRef<Vec<X>>) -> RRef<X>;
+        }
+    ";
+    let resolved = "
+        use alloc::vec::Vec;
+        use asd::X;
+        use crate::rref::rref::RRef;
+
+        #[interface]
+        pub trait Foo {
+            fn bar(&self, car: crate::rref::rref::RRef<alloc::vec::Vec<asd::X>>) -> crate::rref::rref::RRef<asd::X>;
+        }
+    ";
+
+    expect_to_generate_typeid(input, resolved, vec!["asd::X", "alloc::vec::Vec<asd::X>"]);
+}
+
+#[test]
+fn test_stable_type_ids() {
+    let generate = |types: &str| {
+        let input = format!(
+            "{}
+            #[interface]
+            pub trait Foo {{
+                fn bar(&self, x: ({})) -> ();
+            }}",
+            RREF_MOD, types
+        );
+        let mut ast = syn::parse_file(&input).unwrap();
+        super::generate_typeid(&mut ast, &[]).unwrap();
+        split_typeid(ast).0
+    };
+
+    // The id of a type doesn't depend on the other types or the order they are found in.
+    let ids = generate("crate::rref::rref::RRef<usize>, crate::rref::rref::RRef<()>");
+    assert_eq!(
+        ids,
+        generate("crate::rref::rref::RRef<()>, crate::rref::rref::RRef<usize>")
+    );
+    assert!(
+        generate("crate::rref::rref::RRef<usize>, crate::rref::rref::RRef<u8>").contains(&ids[1])
+    );
+
+    // The ids must never change, or the domains built before the change can't talk to the ones
+    // built after.
+    assert_eq!(
+        ids,
+        vec![
+            (
+                String::from("()"),
+                String::from("{ 567769145981429338u64 }")
+            ),
+            (
+                String::from("usize"),
+                String::from("{ 15670484296631260865u64 }")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_report_errors_with_location() {
+    let input = "pub mod foo {
+    pub trait Foo {
+        fn bar(&self, x: crate::nope::X) -> ();
+        fn baz(&self, f: fn(u8)) -> ();
+    }
+}";
+
+    let mut ast = syn::parse_file(input).unwrap();
+    let errors = super::generate_typeid(&mut ast, &[]).unwrap_err();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|err| (err.line_column(), err.message().to_owned()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                Some((3, 33)),
+                String::from("Unable to resolve `crate::nope::X`: `nope` is not found in `crate`")
+            ),
+            (
+                Some((4, 26)),
+                String::from("Function pointer type is not supported in interfaces")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_glob_import() {
+    let input = "
+        pub mod rpc {
+            pub struct RpcError;
+            pub type RpcResult<T> = Result<T, RpcError>;
+        }
+
+        pub mod bdev {
+            use crate::rref::rref::*;
+            use super::rpc::*;
+
+            #[interface]
+            pub trait BDev {
+                fn read(&self, data: RRef<u8>) -> RRef<RpcError>;
+            }
+        }
+    ";
+    let resolved = "
+        pub mod rpc {
+            pub struct RpcError;
+            pub type RpcResult<T> = Result<T, RpcError>;
+        }
+
+        pub mod bdev {
+            use crate::rref::rref::*;
+            use super::rpc::*;
+
+            #[interface]
+            pub trait BDev {
+                fn read(&self, data: crate::rref::rref::RRef<u8>) -> crate::rref::rref::RRef<crate::rpc::RpcError>;
+            }
+        }
+    ";
+
+    expect_to_generate_typeid(input, resolved, vec!["u8", "crate::rpc::RpcError"]);
+}
+
+#[test]
+fn test_glob_import_reexport_and_shadowing() {
+    let input = "
+        pub mod types {
+            pub struct Block;
+            pub struct Request;
+        }
+
+        pub mod prelude {
+            pub use crate::rref::rref::RRef;
+            pub use crate::types::*;
+        }
+
+        pub mod bdev {
+            use crate::prelude::*;
+
+            // Shadows the glob imported `Request`.
+            pub struct Request;
+
+            #[interface]
+            pub trait BDev {
+                fn submit(&self, req: RRef<Request>) -> RRef<Block>;
+            }
+        }
+    ";
+    let resolved = "
+        pub mod types {
+            pub struct Block;
+            pub struct Request;
+        }
+
+        pub mod prelude {
+            pub use crate::rref::rref::RRef;
+            pub use crate::types::*;
+        }
+
+        pub mod bdev {
+            use crate::prelude::*;
+
+            // Shadows the glob imported `Request`.
+            pub struct Request;
+
+            #[interface]
+            pub trait BDev {
+                fn submit(&self, req: crate::rref::rref::RRef<crate::bdev::Request>) -> crate::rref::rref::RRef<crate::types::Block>;
+            }
+        }
+    ";
+
+    expect_to_generate_typeid(
+        input,
+        resolved,
+        vec!["crate::bdev::Request", "crate::types::Block"],
+    );
+}
+
+#[test]
+fn test_glob_import_private_from_ancestor() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        struct Block;
+
+        pub mod bdev {
+            use super::*;
+
+            struct Request;
+
+            pub mod blk {
+                use super::*;
+
+                #[interface]
+                pub trait Blk {
+                    fn submit(&self, req: RRef<Request>) -> RRef<Block>;
+                }
+            }
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        struct Block;
+
+        pub mod bdev {
+            use super::*;
+
+            struct Request;
+
+            pub mod blk {
+                use super::*;
+
+                #[interface]
+                pub trait Blk {
+                    fn submit(&self, req: crate::rref::rref::RRef<crate::bdev::Request>) -> crate::rref::rref::RRef<crate::Block>;
+                }
+            }
+        }
+    ";
+
+    expect_to_generate_typeid(
+        input,
+        resolved,
+        vec!["crate::Block", "crate::bdev::Request"],
+    );
+}
+
+#[test]
+fn test_glob_import_skips_private_of_other_modules() {
+    let input = "
+        pub mod types {
+            struct Block;
+        }
+
+        pub mod blk {
+            pub struct Block;
+        }
+
+        pub mod bdev {
+            use crate::rref::rref::RRef;
+            // Doesn't import the private `Block`, so `Block` is not ambiguous.
+            use crate::types::*;
+            use crate::blk::*;
+
+            #[interface]
+            pub trait BDev {
+                fn read(&self) -> RRef<Block>;
+            }
         }
     ";
+    let resolved = "
+        pub mod types {
+            struct Block;
+        }
+
+        pub mod blk {
+            pub struct Block;
+        }
+
+        pub mod bdev {
+            use crate::rref::rref::RRef;
+            // Doesn't import the private `Block`, so `Block` is not ambiguous.
+            use crate::types::*;
+            use crate::blk::*;
 
-    expect_to_generate_typeid(input, vec!["asd::X", "alloc::vec::Vec<asd::X>", "()"]);
+            #[interface]
+            pub trait BDev {
+                fn read(&self) -> crate::rref::rref::RRef<crate::blk::Block>;
+            }
+        }
+    ";
+
+    expect_to_generate_typeid(input, resolved, vec!["crate::blk::Block"]);
+}
+
+#[test]
+fn test_ambiguous_glob_import() {
+    let input = "
+        pub mod a {
+            pub struct Block;
+        }
+        pub mod b {
+            pub struct Block;
+        }
+        pub mod c {
+            pub use crate::a::Block;
+        }
+
+        pub mod ok {
+            use crate::a::*;
+            use crate::c::*;
+        }
+
+        // Unused, so the conflict is fine.
+        pub mod unused {
+            use crate::a::*;
+            use crate::b::*;
+        }
+
+        pub mod ambiguous {
+            use crate::a::*;
+            use crate::b::*;
+
+            #[interface]
+            pub trait BDev {
+                fn read(&self) -> Block;
+            }
+        }
+    ";
+
+    let mut ast = syn::parse_file(input).unwrap();
+    let errors = super::generate_typeid(&mut ast, &[]).unwrap_err();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|err| (err.line_column(), err.message().to_owned()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![(
+            Some((29, 35)),
+            String::from(
+                "`Block` is ambiguous: it is glob imported as both `crate::a::Block` and `crate::b::Block`"
+            )
+        )]
+    );
+}
+
+#[test]
+fn test_associated_types() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub const BSIZE: usize = 4096usize;
+        pub type Sector = u8;
+
+        pub struct Disk;
+        pub trait Device {
+            type Block;
+        }
+        impl Device for Disk {
+            type Block = [u8; BSIZE];
+        }
+
+        #[interface]
+        pub trait BDev {
+            type Buf = RRef<<Disk as Device>::Block>;
+            fn read(&self, block: Self::Buf) -> RRef<<Self as BDev>::Buf>;
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        pub const BSIZE: usize = 4096usize;
+        pub type Sector = u8;
+
+        pub struct Disk;
+        pub trait Device {
+            type Block;
+        }
+        impl Device for Disk {
+            type Block = [u8; BSIZE];
+        }
+
+        #[interface]
+        pub trait BDev {
+            type Buf = RRef<<Disk as Device>::Block>;
+            fn read(&self, block: crate::rref::rref::RRef<[u8; 4096usize]>) -> crate::rref::rref::RRef<crate::rref::rref::RRef<[u8; 4096usize]> >;
+        }
+    ";
+
+    expect_to_generate_typeid(
+        input,
+        resolved,
+        vec![
+            "[u8; 4096usize]",
+            "crate::rref::rref::RRef<[u8; 4096usize]>",
+        ],
+    );
+}
+
+#[test]
+fn test_unresolvable_associated_types() {
+    let input = "pub struct Disk;
+pub trait Device {
+    type Block;
+}
+
+pub trait BDev {
+    type Buf;
+    fn read(&self, x: Self::Buf) -> ();
+    fn write(&self) -> <Disk as Device>::Block;
+}";
+
+    let mut ast = syn::parse_file(input).unwrap();
+    let errors = super::generate_typeid(&mut ast, &[]).unwrap_err();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|err| (err.line_column(), err.message().to_owned()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                Some((8, 29)),
+                String::from("Unable to resolve `Self::Buf`: associated type `Buf` of `crate::BDev` must have a default to be used in interfaces")
+            ),
+            (
+                Some((9, 42)),
+                String::from("Unable to resolve `<crate::Disk as crate::Device>::Block`: there's no impl of the trait for the type in the IDL and the associated type has no default")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_associated_types_of_generic_impls() {
+    let input = "pub struct Disk<T>(T);
+pub struct Tape;
+pub trait Device {
+    type Block = u8;
+}
+impl<T> Device for Disk<T> {
+    type Block = [u8; 512];
+}
+impl<T> Device for Option<T> {
+    type Block = [u8; 64];
+}
+
+pub trait BDev {
+    fn read(&self) -> <Disk<u8> as Device>::Block;
+    fn write(&self) -> <Tape as Device>::Block;
+}";
+
+    // A generic impl for the type is an error rather than falling back to the default, but one
+    // for another type is not.
+    let mut ast = syn::parse_file(input).unwrap();
+    let errors = super::generate_typeid(&mut ast, &[]).unwrap_err();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|err| (err.line_column(), err.message().to_owned()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![(
+            Some((6, 5)),
+            String::from("Unable to resolve the associated types of `crate::Device` for `crate::Disk`: generic impls are not supported")
+        )]
+    );
+}
+
+#[test]
+fn test_constant_expressions() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub const BSIZE: usize = 4096;
+        pub const QUEUE: usize = 32;
+
+        pub struct Queue<T, const N: usize> {
+            reqs: [T; N],
+        }
+
+        #[interface]
+        pub trait BDev {
+            fn read(&self, a: RRef<[u8; BSIZE]>, b: RRef<[u8; 4096]>, c: RRef<[u8; BSIZE / 2 * 2]>) -> ();
+            fn submit(&self, a: RRef<Queue<u8, { QUEUE * 2 }>>, b: RRef<Queue<u8, 64>>) -> ();
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        pub const BSIZE: usize = 4096;
+        pub const QUEUE: usize = 32;
+
+        pub struct Queue<T, const N: usize> {
+            reqs: [T; N],
+        }
+
+        #[interface]
+        pub trait BDev {
+            fn read(&self, a: crate::rref::rref::RRef<[u8; 4096usize]>, b: crate::rref::rref::RRef<[u8; 4096usize]>, c: crate::rref::rref::RRef<[u8; 4096usize]>) -> ();
+            fn submit(&self, a: crate::rref::rref::RRef<crate::Queue<u8, 64usize> >, b: crate::rref::rref::RRef<crate::Queue<u8, 64usize> >) -> ();
+        }
+    ";
+
+    // Equal lengths spelled differently are the same type.
+    expect_to_generate_typeid(
+        input,
+        resolved,
+        vec!["[u8; 4096usize]", "crate::Queue<u8, 64usize>"],
+    );
+}
+
+#[test]
+fn test_type_aliases() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub struct RpcError;
+        pub type RpcResult<T> = Result<T, RpcError>;
+        pub type Block = [u8; 4096];
+        pub type Buf<T> = RRef<T>;
+
+        #[interface]
+        pub trait BDev {
+            fn read(&self, a: RRef<Block>, b: RRef<[u8; 4096]>, c: Buf<Block>) -> RpcResult<RRef<u8>>;
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        pub struct RpcError;
+        pub type RpcResult<T> = Result<T, RpcError>;
+        pub type Block = [u8; 4096];
+        pub type Buf<T> = RRef<T>;
+
+        #[interface]
+        pub trait BDev {
+            fn read(&self, a: crate::rref::rref::RRef<crate::Block>, b: crate::rref::rref::RRef<[u8; 4096usize]>, c: crate::Buf<crate::Block>) -> crate::RpcResult<crate::rref::rref::RRef<u8> >;
+        }
+    ";
+
+    // The aliases are the same types as what they stand for.
+    expect_to_generate_typeid(input, resolved, vec!["[u8; 4096usize]", "u8"]);
+}
+
+#[test]
+fn test_enums_and_unions() {
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub struct Header {
+            len: usize,
+        }
+
+        pub enum Packet<T> {
+            Empty,
+            Data(RRef<[u8; 64]>, T),
+            Framed { header: RRef<Header>, payload: Option<RRef<T>> },
+        }
+
+        pub union Raw {
+            word: u64,
+            bytes: core::mem::ManuallyDrop<RRef<[u8; 8]>>,
+        }
+
+        #[interface]
+        pub trait Net {
+            fn send(&self, packet: RRef<Packet<u16>>, raw: RRef<Raw>) -> ();
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        pub struct Header {
+            len: usize,
+        }
+
+        pub enum Packet<T> {
+            Empty,
+            Data(RRef<[u8; 64]>, T),
+            Framed { header: RRef<Header>, payload: Option<RRef<T>> },
+        }
+
+        pub union Raw {
+            word: u64,
+            bytes: core::mem::ManuallyDrop<RRef<[u8; 8]>>,
+        }
+
+        #[interface]
+        pub trait Net {
+            fn send(&self, packet: crate::rref::rref::RRef<crate::Packet<u16> >, raw: crate::rref::rref::RRef<crate::Raw>) -> ();
+        }
+    ";
+
+    expect_to_generate_typeid(
+        input,
+        resolved,
+        vec![
+            "crate::Packet<u16>",
+            "[u8; 64usize]",
+            "crate::Header",
+            "u16",
+            "crate::Raw",
+            "[u8; 8usize]",
+        ],
+    );
+}
+
+#[test]
+fn test_lifetimes() {
+    // The lifetimes are kept in the interface but don't make different types.
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub struct Block {
+            data: [u8; 512],
+        }
+
+        pub struct Request<'a, const N: usize> {
+            blocks: [&'a RRef<Block>; N],
+            next: Option<RRef<Request<'a, N>>>,
+        }
+
+        #[interface]
+        pub trait Disk {
+            fn peek<'a>(&self, req: &'a Request<'a, 2>) -> ();
+            fn submit(&self, req: RRef<Request<'static, 2>>, elided: RRef<Request<2>>) -> ();
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        pub struct Block {
+            data: [u8; 512],
+        }
+
+        pub struct Request<'a, const N: usize> {
+            blocks: [&'a RRef<Block>; N],
+            next: Option<RRef<Request<'a, N>>>,
+        }
+
+        #[interface]
+        pub trait Disk {
+            fn peek<'a>(&self, req: &'a crate::Request<'a, 2usize>) -> ();
+            fn submit(&self, req: crate::rref::rref::RRef<crate::Request<'static, 2usize> >, elided: crate::rref::rref::RRef<crate::Request<2usize> >) -> ();
+        }
+    ";
+
+    expect_to_generate_typeid(
+        input,
+        resolved,
+        vec!["crate::Request<'_, 2usize>", "crate::Block"],
+    );
+}
+
+#[test]
+fn test_generic_interfaces() {
+    // The types in the generic traits are found in the instantiations, including `Pool<u64>`,
+    // which is only used by `Queue<u64, 4>`.
+    let input = "
+        use crate::rref::rref::RRef;
+
+        const DEPTH: usize = 4;
+
+        #[interface]
+        pub trait Queue<T, const N: usize> {
+            fn push(&self, items: RRef<[T; N]>) -> ();
+            fn pool(&self) -> Box<dyn Pool<T>>;
+        }
+
+        #[interface]
+        pub trait Pool<T> {
+            fn take(&self) -> RRef<T>;
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn queue(&self) -> Box<dyn Queue<u64, DEPTH>>;
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        const DEPTH: usize = 4;
+
+        #[interface]
+        pub trait Queue<T, const N: usize> {
+            fn push(&self, items: crate::rref::rref::RRef<[T; N]>) -> ();
+            fn pool(&self) -> Box<dyn crate::Pool<T> >;
+        }
+
+        #[interface]
+        pub trait Pool<T> {
+            fn take(&self) -> crate::rref::rref::RRef<T>;
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn queue(&self) -> Box<dyn crate::Queue<u64, 4usize> >;
+        }
+    ";
+
+    expect_to_generate_typeid(input, resolved, vec!["[u64; 4usize]", "u64"]);
+}
+
+#[test]
+fn test_associated_type_bindings() {
+    // The bound associated types of the trait objects are resolved like the generic arguments.
+    let input = "
+        use crate::rref::rref::RRef;
+
+        pub struct Block {
+            pub data: [u8; 512],
+        }
+
+        #[interface]
+        pub trait Disk {
+            type Buf = RRef<Block>;
+            fn read(&self, block: u32) -> Self::Buf;
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn disk(&self) -> Box<dyn Disk<Buf = RRef<Block>>>;
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+
+        pub struct Block {
+            pub data: [u8; 512],
+        }
+
+        #[interface]
+        pub trait Disk {
+            type Buf = RRef<Block>;
+            fn read(&self, block: u32) -> crate::rref::rref::RRef<crate::Block>;
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn disk(&self) -> Box<dyn crate::Disk<Buf = crate::rref::rref::RRef<crate::Block> > >;
+        }
+    ";
+
+    expect_to_generate_typeid(input, resolved, vec!["crate::Block"]);
+}
+
+#[test]
+fn test_extern_crates() {
+    // `disk` re-exports the `Block` of `common`, which re-exports it from a submodule.
+    let common = "
+        pub mod block {
+            pub struct Block {
+                pub data: [u8; 512],
+            }
+        }
+        pub use block::Block;
+    ";
+    let disk = "
+        pub use common::Block;
+        pub mod types {
+            pub use crate::Block as Sector;
+        }
+    ";
+    let input = "
+        use crate::rref::rref::RRef;
+        use disk::types::Sector;
+        use common::*;
+
+        #[interface]
+        pub trait BDev {
+            fn read(&self, a: RRef<Sector>, b: RRef<disk::Block>, c: RRef<Block>, d: RRef<other::Opaque>) -> ();
+        }
+    ";
+    let resolved = "
+        use crate::rref::rref::RRef;
+        use disk::types::Sector;
+        use common::*;
+
+        #[interface]
+        pub trait BDev {
+            fn read(&self, a: crate::rref::rref::RRef<common::block::Block>, b: crate::rref::rref::RRef<common::block::Block>, c: crate::rref::rref::RRef<common::block::Block>, d: crate::rref::rref::RRef<other::Opaque>) -> ();
+        }
+    ";
+
+    // All the paths to the block are the same type. The crates without sources stay opaque.
+    expect_to_generate_typeid_with(
+        input,
+        resolved,
+        vec!["common::block::Block", "other::Opaque"],
+        vec![("disk", disk), ("common", common)],
+    );
+}
+
+#[test]
+fn test_errors_in_extern_crates() {
+    let common = "
+        pub use crate::missing::Block;
+    ";
+    let input = "
+        #[interface]
+        pub trait BDev {
+            fn read(&self) -> common::Block;
+        }
+    ";
+
+    let mut ast = syn::parse_file(input).unwrap();
+    let extern_crates = vec![ExternCrate {
+        name: format_ident!("common"),
+        ast: syn::parse_file(common).unwrap(),
+    }];
+    let errors = super::generate_typeid(&mut ast, &extern_crates).unwrap_err();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|err| (err.line_column(), err.message().to_owned()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![(
+            None,
+            String::from("In external crate `common`: Unable to resolve `crate::missing::Block`: `missing` is not found in `common`")
+        )]
+    );
 }
@@ -0,0 +1,325 @@
+//! Instantiations of the generic traits, e.g. `Queue<u64>` of `pub trait Queue<T: RRefable>`.
+//!
+//! The proxy of an interface forwards the calls through `extern` trampolines, which can't be
+//! generic, so every instantiation of a generic interface gets its own impl and trampolines. The
+//! `TypeResolver` records the instantiations used in the trait objects of the IDL, e.g.
+//! `Box<dyn Queue<u64>>`, and the generators instantiate the interface by substituting the generic
+//! arguments in its resolved methods.
+
+use std::collections::HashMap;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    Expr, FnArg, GenericArgument, GenericParam, Generics, Ident, ItemTrait, Path, PathArguments,
+    ReturnType, TraitItem, Type, TypeParamBound,
+};
+
+use super::symbol_tree::{Module, SymbolTree};
+use super::type_id;
+use crate::error::{Error, Result};
+
+/// Returns the type and const parameters in `generics`. Lifetime parameters don't make different
+/// instantiations.
+pub fn generic_params(generics: &Generics) -> Vec<&Ident> {
+    generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(&param.ident),
+            GenericParam::Const(param) => Some(&param.ident),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect()
+}
+
+/// Returns true if `tr` has type or const parameters.
+pub fn is_generic(tr: &ItemTrait) -> bool {
+    !generic_params(&tr.generics).is_empty()
+}
+
+/// Returns the methods of the generic trait `tr`, whose types are resolved, instantiated as `path`,
+/// e.g. with `T` replaced by `u64` for `crate::queue::Queue<u64>`. The generic parameters of the
+/// returned trait are the lifetimes only.
+pub fn instantiate(tr: &ItemTrait, path: &Path) -> Result<ItemTrait> {
+    let args = map_generic_args(tr, path)?;
+    let mut instance = tr.clone();
+    instance.generics.params = instance
+        .generics
+        .params
+        .into_iter()
+        .filter(|param| matches!(param, GenericParam::Lifetime(_)))
+        .collect();
+    instance.generics.where_clause = None;
+    for item in &mut instance.items {
+        match item {
+            TraitItem::Method(method) => {
+                for arg in &mut method.sig.inputs {
+                    if let FnArg::Typed(arg) = arg {
+                        substitute(&mut arg.ty, &args);
+                    }
+                }
+                if let ReturnType::Type(_, ty) = &mut method.sig.output {
+                    substitute(ty, &args);
+                }
+            }
+            TraitItem::Type(ty) => {
+                if let Some((_, default)) = &mut ty.default {
+                    substitute(default, &args);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(instance)
+}
+
+/// Returns the recorded instantiations of the generic trait `tr`, whose types are resolved and
+/// which is defined in `module`, with the instantiated traits.
+pub fn instances(
+    symbol_tree: &SymbolTree,
+    module: &Module,
+    tr: &ItemTrait,
+) -> Result<Vec<(Path, ItemTrait)>> {
+    let mut trait_path = module.borrow().path.clone();
+    trait_path.push(tr.ident.clone());
+    symbol_tree
+        .instantiations
+        .iter()
+        .filter(|path| path.segments.iter().map(|seg| &seg.ident).eq(&trait_path))
+        .map(|path| Ok((path.clone(), instantiate(tr, path)?)))
+        .collect()
+}
+
+/// Maps the type and const parameters of the trait `tr` to the arguments of its instantiation
+/// `path`.
+fn map_generic_args(tr: &ItemTrait, path: &Path) -> Result<HashMap<Ident, GenericArgument>> {
+    let arguments = &path.segments.last().unwrap().arguments;
+    let args: Vec<&GenericArgument> = match arguments {
+        PathArguments::None => vec![],
+        PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter(|arg| !is_lifetime_or_binding(arg))
+            .collect(),
+        PathArguments::Parenthesized(args) => {
+            return Err(Error::new_spanned(
+                args,
+                format!(
+                    "Unexpected parenthesized arguments for trait `{}`",
+                    tr.ident
+                ),
+            ))
+        }
+    };
+    let params = generic_params(&tr.generics);
+    if params.len() != args.len() {
+        return Err(Error::new_spanned(
+            path,
+            format!(
+                "Trait `{}` takes {} generic arguments but {} are given",
+                tr.ident,
+                params.len(),
+                args.len()
+            ),
+        ));
+    }
+    Ok(params
+        .into_iter()
+        .cloned()
+        .zip(args.into_iter().cloned())
+        .collect())
+}
+
+/// Replaces the generic parameters in `ty` with their arguments in `args`.
+fn substitute(ty: &mut Type, args: &HashMap<Ident, GenericArgument>) {
+    match ty {
+        Type::Path(path) => {
+            if path.qself.is_none() {
+                if let Some(GenericArgument::Type(arg)) =
+                    path.path.get_ident().and_then(|ident| args.get(ident))
+                {
+                    *ty = arg.clone();
+                    return;
+                }
+            }
+            if let Some(qself) = &mut path.qself {
+                substitute(&mut qself.ty, args);
+            }
+            substitute_in_path(&mut path.path, args);
+        }
+        Type::Array(arr) => {
+            substitute(&mut arr.elem, args);
+            if let Expr::Path(len) = &arr.len {
+                if let Some(GenericArgument::Const(arg)) =
+                    len.path.get_ident().and_then(|ident| args.get(ident))
+                {
+                    arr.len = arg.clone();
+                }
+            }
+        }
+        Type::Group(group) => substitute(&mut group.elem, args),
+        Type::Paren(paren) => substitute(&mut paren.elem, args),
+        Type::Ptr(ptr) => substitute(&mut ptr.elem, args),
+        Type::Reference(reference) => substitute(&mut reference.elem, args),
+        Type::Slice(slice) => substitute(&mut slice.elem, args),
+        Type::TraitObject(trait_object) => {
+            for bound in &mut trait_object.bounds {
+                if let TypeParamBound::Trait(bound) = bound {
+                    substitute_in_path(&mut bound.path, args);
+                }
+            }
+        }
+        Type::Tuple(tuple) => {
+            for elem in &mut tuple.elems {
+                substitute(elem, args);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the generic parameters in the generic arguments of `path`, e.g. `N` of
+/// `RRefArray<T, N>`, with their arguments in `args`.
+fn substitute_in_path(path: &mut Path, args: &HashMap<Ident, GenericArgument>) {
+    for segment in &mut path.segments {
+        let arguments = match &mut segment.arguments {
+            PathArguments::AngleBracketed(arguments) => arguments,
+            _ => continue,
+        };
+        for arg in &mut arguments.args {
+            // A const parameter is parsed as a type if it's not in braces.
+            let param = match arg {
+                GenericArgument::Type(Type::Path(ty)) if ty.qself.is_none() => ty.path.get_ident(),
+                GenericArgument::Const(Expr::Path(expr)) => expr.path.get_ident(),
+                _ => None,
+            };
+            match param.and_then(|param| args.get(param)) {
+                Some(replacement) => *arg = replacement.clone(),
+                None => match arg {
+                    GenericArgument::Type(ty) => substitute(ty, args),
+                    GenericArgument::Binding(binding) => substitute(&mut binding.ty, args),
+                    _ => {}
+                },
+            }
+        }
+    }
+}
+
+/// Returns the paths of the traits of the trait objects in `ty`, e.g. `crate::Foo<u8>` of
+/// `Option<Box<dyn crate::Foo<u8> + Send>>`.
+pub fn trait_objects(ty: &Type) -> Vec<&Path> {
+    let mut paths = vec![];
+    find_trait_objects(ty, &mut paths);
+    paths
+}
+
+fn find_trait_objects<'a>(ty: &'a Type, paths: &mut Vec<&'a Path>) {
+    match ty {
+        Type::Path(path) => {
+            for segment in &path.path.segments {
+                if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                    for arg in &arguments.args {
+                        if let GenericArgument::Type(ty) = arg {
+                            find_trait_objects(ty, paths);
+                        }
+                    }
+                }
+            }
+        }
+        Type::Array(arr) => find_trait_objects(&arr.elem, paths),
+        Type::Group(group) => find_trait_objects(&group.elem, paths),
+        Type::Paren(paren) => find_trait_objects(&paren.elem, paths),
+        Type::Ptr(ptr) => find_trait_objects(&ptr.elem, paths),
+        Type::Reference(reference) => find_trait_objects(&reference.elem, paths),
+        Type::Slice(slice) => find_trait_objects(&slice.elem, paths),
+        Type::TraitObject(trait_object) => {
+            for bound in &trait_object.bounds {
+                if let TypeParamBound::Trait(bound) = bound {
+                    paths.push(&bound.path);
+                }
+            }
+        }
+        Type::Tuple(tuple) => {
+            for elem in &tuple.elems {
+                find_trait_objects(elem, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns true if `tokens` mention any of `idents`, e.g. the generic parameters of a trait.
+pub fn mentions<T: ToTokens>(tokens: &T, idents: &[&Ident]) -> bool {
+    fn mentions_in(tokens: TokenStream, idents: &[&Ident]) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => idents.contains(&&ident),
+            TokenTree::Group(group) => mentions_in(group.stream(), idents),
+            _ => false,
+        })
+    }
+    mentions_in(tokens.to_token_stream(), idents)
+}
+
+/// Returns the name of the instantiation `path` for the generated identifiers, e.g. `Queue_u64`
+/// for `crate::queue::Queue<u64>`, or the name of the trait if it's not generic.
+///
+/// The words of the arguments alone don't tell apart e.g. `Queue<[u8; 4]>` and `Queue<u8, 4>`, so
+/// unless the only argument is one word, the name ends with the hash of the arguments, e.g.
+/// `Queue_u8_4usize_12f420ae` for `Queue<u8, 4usize>`.
+pub fn instance_ident(path: &Path) -> Ident {
+    let segment = path.segments.last().unwrap();
+    let arguments = match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => arguments,
+        _ => return segment.ident.clone(),
+    };
+    let args: Vec<&GenericArgument> = arguments
+        .args
+        .iter()
+        .filter(|arg| !is_lifetime_or_binding(arg))
+        .collect();
+    let tokens = quote!(#(#args),*);
+    let words = tokens.to_string();
+    let words: Vec<&str> = words
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        return segment.ident.clone();
+    }
+    let mut tokens = tokens.into_iter();
+    if let (Some(TokenTree::Ident(_)), None) | (Some(TokenTree::Literal(_)), None) =
+        (tokens.next(), tokens.next())
+    {
+        return format_ident!("{}_{}", segment.ident, words.join("_"));
+    }
+    let hash = type_id::fnv1a(type_id::canonical_tokens(quote!(#(#args),*)).as_bytes());
+    format_ident!("{}_{}_{:08x}", segment.ident, words.join("_"), hash as u32)
+}
+
+/// Returns `path` without the associated type bindings, e.g. `crate::Disk` of
+/// `crate::Disk<Buf = [u8; 512usize]>`, which is the trait that the proxy implements.
+pub fn without_bindings(path: &Path) -> Path {
+    let mut path = path.clone();
+    let segment = path.segments.last_mut().unwrap();
+    if let PathArguments::AngleBracketed(arguments) = &mut segment.arguments {
+        arguments.args = arguments
+            .args
+            .iter()
+            .filter(|arg| !matches!(arg, GenericArgument::Binding(_)))
+            .cloned()
+            .collect();
+        if arguments.args.is_empty() {
+            segment.arguments = PathArguments::None;
+        }
+    }
+    path
+}
+
+/// Lifetimes and associated type bindings don't make different instantiations.
+fn is_lifetime_or_binding(arg: &GenericArgument) -> bool {
+    matches!(
+        arg,
+        GenericArgument::Lifetime(_) | GenericArgument::Binding(_)
+    )
+}
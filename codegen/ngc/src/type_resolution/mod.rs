@@ -1,50 +1,101 @@
 use log::info;
-use syn::{parse_quote, Item, Type};
+use syn::{parse_quote, Ident, Item, ItemFn};
 
+use crate::error::Diagnostics;
+
+mod assoc_type;
+mod const_eval;
+mod drop_glue;
+pub mod instantiation;
+pub mod rref_kind;
 pub mod rrefed_finder;
+pub mod supertraits;
 pub mod symbol_tree;
+pub mod type_id;
 pub mod type_info_finder;
 pub mod type_resolver;
 mod utils;
 
+#[cfg(test)]
+mod const_eval_test;
+#[cfg(test)]
+mod drop_glue_test;
 #[cfg(test)]
 mod e2e_test;
 #[cfg(test)]
 mod rrefed_finder_test;
+#[cfg(test)]
+mod type_id_test;
+
+/// The source of an external crate that the IDL depends on, e.g. a vendored or path dependency.
+pub struct ExternCrate {
+    /// The name of the crate as it's used in the IDL.
+    pub name: Ident,
+    /// The crate root with its out-of-line modules inlined.
+    pub ast: syn::File,
+}
 
-pub fn generate_typeid(ast: &mut syn::File) {
+/// Resolves all the types in the interfaces of `ast` to their fully-qualified paths in place and
+/// returns the symbol tree of `ast` for the later generations to look up the resolved types.
+/// The symbols imported from `extern_crates` are resolved to their definitions in the crates.
+pub fn resolve_types(
+    ast: &mut syn::File,
+    extern_crates: &[ExternCrate],
+) -> Result<symbol_tree::SymbolTree, Diagnostics> {
     // Resolve types
     info!("Finding type info");
-    let type_info_finder = type_info_finder::TypeInfoFinder::new();
-    let symbol_tree = type_info_finder.find_type_info(ast);
+    let mut type_info_finder = type_info_finder::TypeInfoFinder::new();
+    for extern_crate in extern_crates {
+        type_info_finder.add_extern_crate(extern_crate);
+    }
+    let mut symbol_tree = type_info_finder.find_type_info(ast)?;
 
     // Resolve types
     info!("Resolving types");
     let type_resolver = type_resolver::TypeResolver::new(symbol_tree.clone());
-    type_resolver.resolve_types(ast);
+    symbol_tree.instantiations = type_resolver.resolve_types(ast)?;
+
+    Ok(symbol_tree)
+}
+
+/// Same as `resolve_types` but also generates the `typeid` module.
+pub fn generate_typeid(
+    ast: &mut syn::File,
+    extern_crates: &[ExternCrate],
+) -> Result<symbol_tree::SymbolTree, Diagnostics> {
+    let symbol_tree = resolve_types(ast, extern_crates)?;
 
     // Find all `RRef`ed types
     info!("Finding `RRef`ed types");
-    let rrefed_finder = rrefed_finder::RRefedFinder::new(symbol_tree);
-    let rrefed_types: Vec<Type> = rrefed_finder.find_rrefed(ast).into_iter().collect();
+    let rrefed_finder = rrefed_finder::RRefedFinder::new(symbol_tree.clone());
+    let (rrefed_types, type_fields) = rrefed_finder.find_rrefed(ast)?;
+
+    // Derive the type ids from the types so they are the same across builds.
+    let type_ids = type_id::assign_type_ids(&rrefed_types)?;
 
     // Generate code
     info!("Generating `TypeIdentifiable`");
-    let impls: Vec<Item> = rrefed_types
+    let impls: Vec<Item> = type_ids
         .iter()
-        .enumerate()
-        .map(|(i, ty)| {
-            let i = i as u64;
+        .map(|(id, ty)| {
             Item::Impl(parse_quote! {
                 impl TypeIdentifiable for #ty {
                     fn type_id() -> u64 {
-                        #i
+                        #id
                     }
                 }
             })
         })
         .collect();
 
+    // Generate the drop glue of each type for the `DropMap`.
+    info!("Generating drop glue");
+    let (drop_fns, drop_glues): (Vec<Ident>, Vec<ItemFn>) = type_ids
+        .iter()
+        .map(|(id, ty)| drop_glue::generate_drop_glue(ty, *id, &type_fields))
+        .unzip();
+    let ids = type_ids.iter().map(|(id, _)| id);
+
     // Remove the existing typeid module
     ast.items.retain(|item| {
         if let Item::Mod(item) = item {
@@ -64,43 +115,29 @@ pub fn generate_typeid(ast: &mut syn::File) {
             /// END Generated TypeIdentifiable
 
             // BEGIN Generated DropMap
-            use hashbrown::HashMap;
             use crate::rref::traits::{CustomCleanup};
 
-            /// Drops the pointer, assumes it is of type T
-            unsafe fn drop_t<T: CustomCleanup + TypeIdentifiable>(ptr: *mut u8) {
-                // Cast the pointer to a pointer of type T
-                let ptr_t: *mut T = core::mem::transmute(ptr);
-                // Drop the object pointed by the buffer. This should recursively drop all the
-                // nested fields of type T, if there's any.
-                core::mem::drop(ptr_t);
-            }
-
-            pub struct DropMap(HashMap<u64, unsafe fn(*mut u8) -> ()>);
+            #(#drop_glues)*
 
-            impl DropMap {
-                pub fn new() -> Self {
-                    let mut drop_map = Self(HashMap::new());
-                    drop_map.populate_drop_map();
-                    drop_map
-                }
+            /// Maps the type IDs to the drop glues. The map is static and the lookup is a `match`,
+            /// so it needs neither an allocator nor any initialization.
+            pub struct DropMap;
 
-                fn add_type<T: 'static + CustomCleanup + TypeIdentifiable>(&mut self) {
-                    let type_id = T::type_id();
-                    let type_erased_drop = drop_t::<T>;
-                    self.0.insert(type_id, type_erased_drop);
-                }
+            /// The map of all `RRef`ed types.
+            pub static DROP_MAP: DropMap = DropMap;
 
-                pub fn get_drop(&self, type_id: u64) -> Option<&unsafe fn(*mut u8) -> ()> {
-                    self.0.get(&type_id)
-                }
-
-                fn populate_drop_map(&mut self) {
-                    #(self.add_type::<#rrefed_types>();)*
+            impl DropMap {
+                pub fn get_drop(&self, type_id: u64) -> Option<unsafe fn(*mut u8) -> ()> {
+                    match type_id {
+                        #(#ids => Some(#drop_fns as unsafe fn(*mut u8) -> ()),)*
+                        _ => None,
+                    }
                 }
             }
             // END Generated DropMap
         }
     };
     ast.items.push(Item::Mod(md));
+
+    Ok(symbol_tree)
 }
@@ -0,0 +1,38 @@
+use syn::{Path, Type};
+
+/// The shared heap types that the proxy is responsible for.
+/// All of them have the same `move_to`, `borrow` and `forfeit` interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RRefKind {
+    RRef,
+    RRefArray,
+    RRefDeque,
+}
+
+impl RRefKind {
+    /// Returns the kind of shared heap type `path` points to, if it points to one.
+    /// The path must already be resolved by the `TypeResolver` so that a user defined type that
+    /// happens to be named `RRef` will not be mistaken as the real one.
+    pub fn of_path(path: &Path) -> Option<Self> {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|seg| seg.ident.to_string())
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        match segments.as_slice() {
+            ["crate", "rref", "rref", "RRef"] => Some(Self::RRef),
+            ["crate", "rref", "rref_array", "RRefArray"] => Some(Self::RRefArray),
+            ["crate", "rref", "rref_deque", "RRefDeque"] => Some(Self::RRefDeque),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of shared heap type `ty` is, if it is one.
+    pub fn of_type(ty: &Type) -> Option<Self> {
+        match ty {
+            Type::Path(ty) => Self::of_path(&ty.path),
+            _ => None,
+        }
+    }
+}
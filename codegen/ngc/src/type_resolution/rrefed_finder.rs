@@ -6,12 +6,17 @@ use std::{
     collections::{HashMap, HashSet},
 };
 use syn::{
-    Expr, ExprLit, File, FnArg, GenericArgument, Ident, Item, ItemTrait, Lit, Path, PathArguments,
-    PathSegment, ReturnType, TraitItem, TraitItemMethod, Type,
+    punctuated::Punctuated, Expr, ExprLit, Field, File, FnArg, GenericArgument, Generics, Ident,
+    Item, ItemTrait, Lifetime, Lit, Member, Path, PathArguments, ReturnType, Token, TraitItem,
+    TraitItemMethod, Type,
 };
 
+use super::assoc_type::find_associated_type;
+use super::const_eval::ConstEvaluator;
+use super::instantiation::{instances, is_generic};
 use super::symbol_tree::*;
 use super::utils::*;
+use crate::error::{Diagnostics, Error, Result};
 
 lazy_static! {
     static ref RREF_PATH: Vec<String> = vec![
@@ -22,13 +27,31 @@ lazy_static! {
     ];
 }
 
+/// The fields of a struct, an enum or a union. The field types are fully-qualified, with the generic
+/// arguments of the type substituted.
+#[derive(Debug, Clone)]
+pub enum Fields {
+    Struct(Vec<(Member, Type)>),
+    /// The variants and their fields. Unit variants have no fields.
+    Enum(Vec<(Ident, Vec<(Member, Type)>)>),
+    Union(Vec<(Member, Type)>),
+}
+
+/// The fields of the structs, enums and unions used in the interfaces, keyed by their
+/// fully-qualified types.
+pub type TypeFields = HashMap<Type, Fields>;
+
 pub struct RRefedFinder {
     /// All the fully qualified path of all `RRef`ed types.
     type_list: HashSet<Type>,
+    /// The fields of all the structs, enums and unions found so far.
+    type_fields: TypeFields,
     /// The root module node, i.e. the `crate` node.
     symbol_tree: SymbolTree,
     /// The current module node that's used in recursive calls.
     current_module: Module,
+    /// Errors found so far.
+    errors: Diagnostics,
 }
 
 impl RRefedFinder {
@@ -36,48 +59,71 @@ impl RRefedFinder {
         let symbol_tree_node = symbol_tree.root_module();
         Self {
             type_list: HashSet::new(),
+            type_fields: HashMap::new(),
             symbol_tree,
             current_module: symbol_tree_node,
+            errors: Diagnostics::new(),
         }
     }
 
-    /// Takes a AST and returns a list of fully-qualified paths of all `RRef`ed types.
-    pub fn find_rrefed(mut self, ast: &File) -> HashSet<Type> {
+    /// Takes a AST and returns a list of fully-qualified paths of all `RRef`ed types, and the
+    /// fields of the structs that are used in them.
+    pub fn find_rrefed(
+        mut self,
+        ast: &File,
+    ) -> std::result::Result<(HashSet<Type>, TypeFields), Diagnostics> {
         self.find_rrefed_recursive(&ast.items);
-        self.type_list
-            .into_iter()
-            .filter_map(|ty| {
-                // Returns the type `T` of `RRef<T>`.
-                if let Type::Path(path) = ty {
-                    let path = &path.path;
-                    if path
-                        .segments
-                        .iter()
-                        .map(|seg| seg.ident.to_string())
-                        .eq(RREF_PATH.iter().cloned())
-                    {
-                        match &path.segments.last().unwrap().arguments {
-                            PathArguments::AngleBracketed(argumnents) => {
-                                assert_eq!(argumnents.args.len(), 1);
-                                let generic_arg = argumnents.args.first().unwrap();
-                                match generic_arg {
-                                    GenericArgument::Type(ty) => {
-                                        return Some(ty.clone());
-                                    }
-                                    _ => panic!(
-                                        "Expecting a generic type parameter in rref: {:?}",
-                                        path
-                                    ),
-                                }
+        let mut rrefed_types = HashSet::new();
+        for ty in &self.type_list {
+            // Returns the type `T` of `RRef<T>`.
+            if let Type::Path(path) = ty {
+                let path = &path.path;
+                if path
+                    .segments
+                    .iter()
+                    .map(|seg| seg.ident.to_string())
+                    .eq(RREF_PATH.iter().cloned())
+                {
+                    let rrefed_type = match &path.segments.last().unwrap().arguments {
+                        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+                            match arguments.args.first().unwrap() {
+                                GenericArgument::Type(ty) => Some(ty.clone()),
+                                _ => None,
                             }
-                            _ => panic!("Cannot find generic argument in rref: {:?}", path),
                         }
+                        _ => None,
+                    };
+                    match rrefed_type {
+                        Some(rrefed_type) => {
+                            rrefed_types.insert(rrefed_type);
+                        }
+                        None => self.errors.push(Error::new_spanned(
+                            path,
+                            "Expecting exactly one generic type argument in `RRef`",
+                        )),
                     }
                 }
+            }
+        }
+        let type_fields = std::mem::take(&mut self.type_fields);
+        self.errors.finish((rrefed_types, type_fields))
+    }
 
-                None
-            })
-            .collect()
+    /// Resolves `ty`, which is used in `module`, to its canonical type: fully-qualified, with the
+    /// type aliases and associated types expanded and the constants evaluated. The fields of the
+    /// structs, enums and unions in it are recorded for `fields`.
+    pub fn resolve_type(&mut self, module: Module, ty: &Type) -> Result<Type> {
+        let original_module = std::mem::replace(&mut self.current_module, module);
+        let result = self
+            .find_rrefed_in_type(ty, None)
+            .and_then(GenericResult::ty);
+        self.current_module = original_module;
+        result
+    }
+
+    /// Returns the fields of the struct, enum or union `ty` if it's found in a resolved type.
+    pub fn fields(&self, ty: &Type) -> Option<&Fields> {
+        self.type_fields.get(ty)
     }
 
     fn find_rrefed_recursive(&mut self, items: &[syn::Item]) {
@@ -87,35 +133,17 @@ impl RRefedFinder {
                     info!("Finding `RRef`ed for module {:?}", md.ident);
                     if let Some((_, items)) = &md.content {
                         // Push a frame
-                        let current_node = self.current_module.borrow();
-                        let next_frame = current_node.get(&md.ident);
-                        let next_frame = crate::expect!(
-                            next_frame,
-                            "Module {:?} not found in {:#?}",
-                            md.ident,
-                            current_node
-                        );
-                        let next_frame = match &next_frame
-                            .borrow()
-                            .terminal
-                            .as_ref()
-                            .expect("Expecting a module; found unresolved.")
-                            .definition
-                        {
-                            Definition::Module(md) => md.clone(),
-                            _ => panic!("Expecting a module, not a symbol."),
+                        let next_frame = match find_child_module(&self.current_module, &md.ident) {
+                            Ok(next_frame) => next_frame,
+                            Err(err) => {
+                                self.errors.push(err);
+                                continue;
+                            }
                         };
-                        drop(current_node);
-                        self.current_module = next_frame;
+                        let parent_module = std::mem::replace(&mut self.current_module, next_frame);
                         // Recurse into the new frame.
                         self.find_rrefed_recursive(items);
                         // Pop a frame
-                        let parent_module = self
-                            .current_module
-                            .borrow()
-                            .node
-                            .borrow()
-                            .get_parent_module();
                         self.current_module = parent_module;
                     }
                 }
@@ -143,30 +171,46 @@ impl RRefedFinder {
     }
 
     fn find_rrefed_in_trait(&mut self, tr: &ItemTrait) {
+        // The types in a generic trait are known in its instantiations only.
+        if is_generic(tr) {
+            match instances(&self.symbol_tree, &self.current_module, tr) {
+                Ok(instances) => {
+                    for (_, instance) in instances {
+                        self.find_rrefed_in_trait(&instance);
+                    }
+                }
+                Err(err) => self.errors.push(err),
+            }
+            return;
+        }
         for item in &tr.items {
             if let TraitItem::Method(method) = item {
-                self.find_rrefed_in_method(&method);
+                if let Err(err) = self.find_rrefed_in_method(&method) {
+                    self.errors.push(err);
+                }
             }
         }
     }
 
-    fn find_rrefed_in_method(&mut self, method: &TraitItemMethod) {
+    fn find_rrefed_in_method(&mut self, method: &TraitItemMethod) -> Result<()> {
         for arg in &method.sig.inputs {
-            self.find_rrefed_in_fnarg(&arg);
+            self.find_rrefed_in_fnarg(&arg)?;
         }
-        self.find_rrefed_in_returntype(&method.sig.output);
+        self.find_rrefed_in_returntype(&method.sig.output)
     }
 
-    fn find_rrefed_in_fnarg(&mut self, arg: &FnArg) {
+    fn find_rrefed_in_fnarg(&mut self, arg: &FnArg) -> Result<()> {
         if let FnArg::Typed(ty) = arg {
-            self.find_rrefed_in_type(&ty.ty, None);
+            self.find_rrefed_in_type(&ty.ty, None)?;
         }
+        Ok(())
     }
 
-    fn find_rrefed_in_returntype(&mut self, rtn: &ReturnType) {
+    fn find_rrefed_in_returntype(&mut self, rtn: &ReturnType) -> Result<()> {
         if let ReturnType::Type(_, ty) = rtn {
-            self.find_rrefed_in_type(ty, None);
+            self.find_rrefed_in_type(ty, None)?;
         }
+        Ok(())
     }
 
     /// Resolve type, put the type and the nested types, if there's any, into the typelist, and
@@ -175,69 +219,39 @@ impl RRefedFinder {
         &mut self,
         ty: &Type,
         generic_args: Option<&HashMap<Ident, GenericResult>>,
-    ) -> GenericResult {
+    ) -> Result<GenericResult> {
+        // Find in the definition of an associated type, in the module where it is defined. The
+        // ones in the interfaces are already rewritten by the `TypeResolver`.
+        if let Type::Path(path) = ty {
+            if let Some(assoc) =
+                find_associated_type(&self.symbol_tree, &self.current_module, None, path)?
+            {
+                let original_module = std::mem::replace(&mut self.current_module, assoc.module);
+                let result = self.find_rrefed_in_type(&assoc.ty, None);
+                self.current_module = original_module;
+                return result;
+            }
+        }
+
         match ty {
             Type::Array(ty) => {
                 // Resolve the type.
                 let mut resolved_type = ty.clone();
-                resolved_type.elem = box self.find_rrefed_in_type(&ty.elem, generic_args).ty();
-
-                // Resolve the length to a literal
-                resolved_type.len = match &ty.len {
-                    Expr::Lit(lit) => Expr::Lit(lit.clone()),
-                    Expr::Path(path) => {
-                        let path = &path.path;
-                        let mut rtn = None;
-
-                        // If the path is a generic argument, return it's mapping
-                        if let Some(ident) = path.get_ident() {
-                            if let Some(generic_args) = generic_args {
-                                if let Some(generic_param) = generic_args.get(ident) {
-                                    match generic_param {
-                                        GenericResult::Type(x) => panic!(
-                                            "Expecting a generic literal, not a type. {:?}",
-                                            x
-                                        ),
-                                        GenericResult::Literal(lit) => {
-                                            rtn = Some(Expr::Lit(ExprLit {
-                                                attrs: vec![],
-                                                lit: lit.clone(),
-                                            }))
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                resolved_type.elem =
+                    box self.find_rrefed_in_type(&ty.elem, generic_args)?.ty()?;
 
-                        // Otherwise, walk the path and resolve it to a literal.
-                        if rtn.is_none() {
-                            let (path, node) = self.resolve_path(&path, generic_args);
-                            let node = crate::expect!(node, "Array length experssion must not contains paths from external crates.\nType: {:?}\nForeign path: {:?}", ty, path);
-                            let lit = match &node
-                                .borrow()
-                                .terminal
-                                .as_ref()
-                                .expect("expecting a literal; found unresolved")
-                                .definition
-                            {
-                                Definition::Literal(lit) => Expr::Lit(ExprLit {
-                                    attrs: vec![],
-                                    lit: lit.clone(),
-                                }),
-                                _ => panic!(),
-                            };
-                            rtn = Some(lit);
-                        }
-
-                        rtn.unwrap()
-                    }
-                    _ => unimplemented!(),
-                };
+                // Evaluate the length to a literal.
+                let const_args = const_args(generic_args);
+                let evaluator = ConstEvaluator::new(self.current_module.clone(), &const_args);
+                resolved_type.len = Expr::Lit(ExprLit {
+                    attrs: vec![],
+                    lit: evaluator.eval_as(&ty.len, "usize")?,
+                });
 
                 // Put the resolved type into the type list.
                 let resolved_type = Type::Array(resolved_type);
                 self.type_list.insert(resolved_type.clone());
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
             Type::Path(ty) => {
                 // Use the type parameter to resolve the path, if there's a matching one.
@@ -245,182 +259,203 @@ impl RRefedFinder {
                     // The path is one single ident, which could be a generic argument.
                     if let Some(path) = ty.path.get_ident() {
                         if let Some(resolved_type) = generic_args.get(path) {
-                            return resolved_type.clone();
+                            return Ok(resolved_type.clone());
                         }
                     }
                 }
 
                 // Resolve the path and insert resolved type into the type_list.
                 let mut resolved_type = ty.clone();
-                // TODO(tianjiao): resolve generic here
-                resolved_type.path = self.resolve_path(&ty.path, generic_args).0;
+                let (path, node) = self.resolve_path(&ty.path, generic_args)?;
+                if let Some(node) = node {
+                    if let Some(expanded) = self.expand_type_alias(&node, &path)? {
+                        return Ok(expanded);
+                    }
+                }
+                resolved_type.path = path;
                 let resolved_type = Type::Path(resolved_type);
                 self.type_list.insert(resolved_type.clone());
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
             Type::Tuple(ty) => {
                 let mut resolved_type = ty.clone();
                 for elem in &mut resolved_type.elems {
-                    *elem = self.find_rrefed_in_type(&elem, generic_args).ty();
+                    *elem = self.find_rrefed_in_type(&elem, generic_args)?.ty()?;
                 }
                 let resolved_type = Type::Tuple(resolved_type);
                 self.type_list.insert(resolved_type.clone());
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
-            Type::BareFn(x) => unimplemented!("{:#?}", x),
-            Type::Group(x) => unimplemented!("{:#?}", x),
-            Type::ImplTrait(x) => unimplemented!("{:#?}", x),
-            Type::Infer(x) => unimplemented!("{:#?}", x),
-            Type::Macro(_) => panic!("There's shouldn't be unexpended any macro at this point."),
-            Type::Never(x) => unimplemented!("{:#?}", x),
-            Type::Paren(x) => unimplemented!("{:#?}", x),
+            Type::Macro(x) => Err(Error::new_spanned(
+                x,
+                "Macros in types must be expanded before running ngc",
+            )),
+            // Pointers and references are kept so that the validation can tell them apart from
+            // the values they point to.
             Type::Ptr(ptr) => {
-                GenericResult::Type(self.find_rrefed_in_type(&ptr.elem, generic_args).ty())
+                let mut resolved_type = ptr.clone();
+                *resolved_type.elem = self.find_rrefed_in_type(&ptr.elem, generic_args)?.ty()?;
+                Ok(GenericResult::Type(Type::Ptr(resolved_type)))
             }
             Type::Reference(reference) => {
-                GenericResult::Type(self.find_rrefed_in_type(&reference.elem, generic_args).ty())
+                let mut resolved_type = reference.clone();
+                resolved_type.lifetime = None;
+                *resolved_type.elem = self
+                    .find_rrefed_in_type(&reference.elem, generic_args)?
+                    .ty()?;
+                Ok(GenericResult::Type(Type::Reference(resolved_type)))
             }
+            // Function pointers can't cross domains, which is reported by the validation.
+            Type::BareFn(bare_fn) => Ok(GenericResult::Type(Type::BareFn(bare_fn.clone()))),
             Type::Slice(slice) => {
                 let mut resolved_type = slice.clone();
                 *resolved_type.elem = self
-                    .find_rrefed_in_type(&resolved_type.elem, generic_args)
-                    .ty();
+                    .find_rrefed_in_type(&resolved_type.elem, generic_args)?
+                    .ty()?;
                 let resolved_type = Type::Slice(resolved_type);
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
             Type::TraitObject(tr) => {
                 let mut resolved_type = tr.clone();
                 for bound in resolved_type.bounds.iter_mut() {
                     match bound {
                         syn::TypeParamBound::Trait(tr) => {
-                            tr.path = self.resolve_path(&tr.path, generic_args).0;
+                            tr.path = self.resolve_path(&tr.path, generic_args)?.0;
                         }
                         syn::TypeParamBound::Lifetime(_) => {}
                     }
                 }
                 let resolved_type = Type::TraitObject(resolved_type);
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
-            Type::Verbatim(x) => unimplemented!("{:#?}", x),
-            Type::__Nonexhaustive => unimplemented!(),
+            ty => Err(unsupported_type(ty)),
         }
     }
 
-    fn find_rrefed_in_struct(&mut self, node: &SymbolTreeNode, args: &PathArguments) {
-        // Noop if the node is not a terminal struct node.
+    /// Finds the `RRef`ed types in the fields of the struct, enum or union `node`, which is used as
+    /// `path`, and records the fields.
+    fn find_rrefed_in_fields(&mut self, node: &SymbolTreeNode, path: &Path) -> Result<()> {
+        // Noop if the node is not a terminal struct, enum or union node.
         let node_ref = node.borrow();
-        let (terminal, st) = if let Some(terminal) = &node_ref.terminal {
-            let st = if let Definition::Type(Item::Struct(st)) = &terminal.definition {
-                st
-            } else {
-                return;
-            };
-            (terminal, st)
-        } else {
-            return;
+        let (terminal, item) = match &node_ref.terminal {
+            Some(terminal) => match &terminal.definition {
+                Definition::Type(item) => (terminal, item),
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        let (kind, ident, generics) = match item {
+            Item::Struct(st) => ("Struct", &st.ident, &st.generics),
+            Item::Enum(en) => ("Enum", &en.ident, &en.generics),
+            Item::Union(un) => ("Union", &un.ident, &un.generics),
+            _ => return Ok(()),
         };
 
-        debug!("Finding nested `RRef`ed in struct {:?}", st.ident);
+        // Noop if the type is visited already. This also stops the recursion on recursive
+        // types, e.g. a linked list node with an `Option<RRef<Node>>` field.
+        let ty: Type = syn::parse_quote!(#path);
+        if self.type_fields.contains_key(&ty) {
+            return Ok(());
+        }
+        self.type_fields.insert(ty.clone(), Fields::Struct(vec![]));
 
-        // Sanity checks.
-        let args = match &args {
-            PathArguments::None => return,
-            PathArguments::Parenthesized(x) => unimplemented!("{:#?}", x),
-            PathArguments::AngleBracketed(args) => args,
-        };
-        let args = &args.args;
-        assert_eq!(st.generics.params.len(), args.len());
-
-        // Change the scope to where the struct is defined
-        let original_scope = self.current_module.clone();
-        // TODO: walk the path and find struct module;
-        let struct_scope = terminal.node.borrow().get_parent_module();
-        self.current_module = struct_scope;
+        debug!(
+            "Finding nested `RRef`ed in {} {:?}",
+            kind.to_lowercase(),
+            ident
+        );
+
+        let generic_map = map_generic_args(
+            kind,
+            ident,
+            generics,
+            &path.segments.last().unwrap().arguments,
+        )?;
+
+        // Change the scope to where the type is defined
+        let scope = terminal.node.borrow().get_parent_module()?;
+        let original_scope = std::mem::replace(&mut self.current_module, scope);
         trace!(
-            "To find `RRef`ed in struct {:?}, the scope is changed to {:?}",
-            st.ident,
+            "To find `RRef`ed in {:?}, the scope is changed to {:?}",
+            ident,
             self.current_module.borrow().path
         );
 
-        // Create a mapping between the generic params and their corresponding arguments.
-        // For example, for definition `RRef<T>` and usage `RRef<u8>`, we map `T` to `u8`.
-        let generic_map: HashMap<Ident, GenericResult> = st
-            .generics
-            .params
-            .iter()
-            .zip(args)
-            .map(|(param, arg)| {
-                let param = match param {
-                    syn::GenericParam::Lifetime(x) => unimplemented!("{:#?}", x),
-                    syn::GenericParam::Const(c) => c.ident.clone(),
-                    syn::GenericParam::Type(param) => param.ident.clone(),
-                };
+        let result = match item {
+            Item::Struct(st) => self
+                .find_rrefed_in_field_list(&st.fields, &generic_map)
+                .map(Fields::Struct),
+            Item::Enum(en) => en
+                .variants
+                .iter()
+                .map(|variant| {
+                    let fields = self.find_rrefed_in_field_list(&variant.fields, &generic_map)?;
+                    Ok((variant.ident.clone(), fields))
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(Fields::Enum),
+            Item::Union(un) => self
+                .find_rrefed_in_field_list(&un.fields.named, &generic_map)
+                .map(Fields::Union),
+            _ => unreachable!(),
+        };
 
-                let arg = match arg {
-                    syn::GenericArgument::Lifetime(x) => unimplemented!("{:#?}", x),
-                    syn::GenericArgument::Binding(x) => unimplemented!("{:#?}", x),
-                    syn::GenericArgument::Constraint(x) => unimplemented!("{:#?}", x),
-                    syn::GenericArgument::Const(expr) => match expr {
-                        Expr::Array(x) => unimplemented!("{:#?}", x),
-                        Expr::Assign(x) => unimplemented!("{:#?}", x),
-                        Expr::AssignOp(x) => unimplemented!("{:#?}", x),
-                        Expr::Async(x) => unimplemented!("{:#?}", x),
-                        Expr::Await(x) => unimplemented!("{:#?}", x),
-                        Expr::Binary(x) => unimplemented!("{:#?}", x),
-                        Expr::Block(x) => unimplemented!("{:#?}", x),
-                        Expr::Box(x) => unimplemented!("{:#?}", x),
-                        Expr::Break(x) => unimplemented!("{:#?}", x),
-                        Expr::Call(x) => unimplemented!("{:#?}", x),
-                        Expr::Cast(x) => unimplemented!("{:#?}", x),
-                        Expr::Closure(x) => unimplemented!("{:#?}", x),
-                        Expr::Continue(x) => unimplemented!("{:#?}", x),
-                        Expr::Field(x) => unimplemented!("{:#?}", x),
-                        Expr::ForLoop(x) => unimplemented!("{:#?}", x),
-                        Expr::Group(x) => unimplemented!("{:#?}", x),
-                        Expr::If(x) => unimplemented!("{:#?}", x),
-                        Expr::Index(x) => unimplemented!("{:#?}", x),
-                        Expr::Let(x) => unimplemented!("{:#?}", x),
-                        Expr::Lit(lit) => GenericResult::Literal(lit.lit.clone()),
-                        Expr::Loop(x) => unimplemented!("{:#?}", x),
-                        Expr::Macro(x) => unimplemented!("{:#?}", x),
-                        Expr::Match(x) => unimplemented!("{:#?}", x),
-                        Expr::MethodCall(x) => unimplemented!("{:#?}", x),
-                        Expr::Paren(x) => unimplemented!("{:#?}", x),
-                        Expr::Path(x) => unimplemented!("{:#?}", x),
-                        Expr::Range(x) => unimplemented!("{:#?}", x),
-                        Expr::Reference(x) => unimplemented!("{:#?}", x),
-                        Expr::Repeat(x) => unimplemented!("{:#?}", x),
-                        Expr::Return(x) => unimplemented!("{:#?}", x),
-                        Expr::Struct(x) => unimplemented!("{:#?}", x),
-                        Expr::Try(x) => unimplemented!("{:#?}", x),
-                        Expr::TryBlock(x) => unimplemented!("{:#?}", x),
-                        Expr::Tuple(x) => unimplemented!("{:#?}", x),
-                        Expr::Type(x) => unimplemented!("{:#?}", x),
-                        Expr::Unary(x) => unimplemented!("{:#?}", x),
-                        Expr::Unsafe(x) => unimplemented!("{:#?}", x),
-                        Expr::Verbatim(x) => unimplemented!("{:#?}", x),
-                        Expr::While(x) => unimplemented!("{:#?}", x),
-                        Expr::Yield(x) => unimplemented!("{:#?}", x),
-                        Expr::__Nonexhaustive => unimplemented!(),
-                    },
-                    syn::GenericArgument::Type(arg) => GenericResult::Type(arg.clone()),
-                };
+        // Restore back to the current scope.
+        self.current_module = original_scope;
+        self.type_fields.insert(ty, result?);
+        Ok(())
+    }
 
-                (param, arg)
+    /// Finds the `RRef`ed types in `fields` and returns the members and their resolved types.
+    fn find_rrefed_in_field_list<'a>(
+        &mut self,
+        fields: impl IntoIterator<Item = &'a Field>,
+        generic_map: &HashMap<Ident, GenericResult>,
+    ) -> Result<Vec<(Member, Type)>> {
+        fields
+            .into_iter()
+            .enumerate()
+            .map(|(i, field)| {
+                // Resolve field type
+                let resolved_type = self
+                    .find_rrefed_in_type(&field.ty, Some(generic_map))?
+                    .ty()?;
+                debug!("Field {:?} is resolved to {:?}", field.ident, resolved_type);
+                self.type_list.insert(resolved_type.clone());
+                let member = match &field.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(i.into()),
+                };
+                Ok((member, resolved_type))
             })
-            .collect();
-        for field in &st.fields {
-            // Resolve field type
-            let resolved_type = self.find_rrefed_in_type(&field.ty, Some(&generic_map)).ty();
-            debug!(
-                "Field {:?} of struct {:?} is resolved to {:?}",
-                field.ident, st.ident, resolved_type
-            );
-            self.type_list.insert(resolved_type);
-        }
+            .collect()
+    }
 
-        // Restore back to the current scope.
-        self.current_module = original_scope;
+    /// Returns the type that the type alias `node`, which is used as `path`, stands for, or `None` if
+    /// `node` is not a type alias. The generic arguments of the alias are substituted.
+    fn expand_type_alias(
+        &mut self,
+        node: &SymbolTreeNode,
+        path: &Path,
+    ) -> Result<Option<GenericResult>> {
+        let alias = match &node.borrow().terminal.as_ref().unwrap().definition {
+            Definition::Type(Item::Type(alias)) => alias.clone(),
+            _ => return Ok(None),
+        };
+        let generic_map = map_generic_args(
+            "Type alias",
+            &alias.ident,
+            &alias.generics,
+            &path.segments.last().unwrap().arguments,
+        )?;
+        trace!("Expanding type alias {:?} to {:?}", path, alias.ty);
+
+        // Find in the aliased type where the alias is defined.
+        let alias_module = node.borrow().get_parent_module()?;
+        let original_module = std::mem::replace(&mut self.current_module, alias_module);
+        let result = self.find_rrefed_in_type(&alias.ty, Some(&generic_map));
+        self.current_module = original_module;
+        result.map(Some)
     }
 
     /// Resolve path in the current module and return the resolved path and its corresponding node,
@@ -433,130 +468,112 @@ impl RRefedFinder {
         &mut self,
         path: &Path,
         generic_args: Option<&HashMap<Ident, GenericResult>>,
-    ) -> (Path, Option<SymbolTreeNode>) {
+    ) -> Result<(Path, Option<SymbolTreeNode>)> {
         trace!(
             "Resolving path {:?} with generic_args {:?}",
             path,
             generic_args
         );
-        let mut current_node = self.current_module.clone();
-        let mut path_segments: Vec<PathSegment> = path.segments.iter().cloned().collect();
+        let current_node = self.current_module.clone();
 
         // If the path starts with `::` and doesn't come from `crate` or `super, or it comes from
         // some unknown module(external module), we know that it's already fully qualified.
         if path.leading_colon.is_some()
             && PATH_MODIFIERS.contains(&path.segments.first().unwrap().ident.to_string())
-            || current_node.borrow().get(&path_segments[0].ident).is_none()
+            || current_node
+                .borrow()
+                .get(&path.segments.first().unwrap().ident)
+                .is_none()
         {
-            return (path.clone(), None);
+            // The generic arguments may still come from the IDL, e.g. `T` of `Result<T, RpcError>`
+            // in a type alias.
+            let mut resolved_path = path.clone();
+            let final_segment = resolved_path.segments.last_mut().unwrap();
+            final_segment.arguments =
+                self.resolve_path_arguments(&final_segment.arguments, generic_args, &[])?;
+            return Ok((resolved_path, None));
         }
 
         // Walk the module tree and resolve the type.
-        let final_segment = path_segments.remove(path_segments.len() - 1);
-        for path_segment in path_segments {
-            if path_segment.arguments != PathArguments::None {
-                panic!("Path arguments(e.g. generics) is not supported in the inner path segments.\nViolating path: {:?}\nPath segment: {:?}", path, path_segment);
-            }
-
-            let current_node_ref = current_node.borrow();
-            let next_node = current_node_ref.get(&path_segment.ident);
-            let next_node = crate::expect!(
-                next_node,
-                "Unable to find {:?} in {:#?}",
-                path_segment.ident,
-                current_node
-            )
-            .clone();
-            drop(current_node_ref);
-            let next_node = next_node.borrow();
-            current_node = match &next_node
-                .terminal
-                .as_ref()
-                .expect("Expecting module, found unresolved")
-                .definition
-            {
-                Definition::Module(md) => {
-                    assert!(next_node.public);
-                    md.clone()
-                }
-                _ => panic!(
-                    "Resolving {:#?} for {:#?}. Node {:#?} is a symbol and cannot have child.",
-                    path_segment,
-                    current_node.borrow().node.borrow().path,
-                    next_node
-                ),
-            };
-        }
-
-        let current_node_ref = current_node.borrow();
-        let final_node = current_node_ref.get(&final_segment.ident);
-        let final_node = crate::expect!(
-            final_node,
-            "Unable to find {:?} in {:#?}",
-            final_segment.ident,
-            current_node_ref
-        )
-        .clone();
-        drop(current_node_ref);
-        let final_node_ref = final_node.borrow();
-        let mut resolved_path = match &final_node_ref.terminal.as_ref().unwrap().definition {
-            Definition::Module(md) => panic!("Expecting a type, but found a module. {:?}", md),
-            _ => idents_to_path(&final_node_ref.path),
-        };
-        drop(final_node_ref);
+        let final_node = walk_path(current_node, path)?;
+        let mut resolved_path = idents_to_path(&final_node.borrow().path);
+        let final_segment = path.segments.last().unwrap();
 
         // Resolve the generic arguments.
-        resolved_path.segments.last_mut().unwrap().arguments =
-            self.resolve_path_arguments(&final_segment.arguments, generic_args);
+        let mut arguments = self.resolve_path_arguments(
+            &final_segment.arguments,
+            generic_args,
+            &const_param_types(&final_node),
+        )?;
+        spell_out_elided_lifetimes(&mut arguments, lifetime_param_count(&final_node));
+        resolved_path.segments.last_mut().unwrap().arguments = arguments;
 
         // Find nested `RRef`ed types
-        self.find_rrefed_in_struct(
-            &final_node,
-            &resolved_path.segments.last().unwrap().arguments,
-        );
+        self.find_rrefed_in_fields(&final_node, &resolved_path)?;
 
         trace!("Path {:?} is resolved to {:?}.", path, resolved_path);
-        (resolved_path, Some(final_node.clone()))
+        Ok((resolved_path, Some(final_node)))
     }
 
     /// Resolve any types or constants in the path argument and returns the resolved path arguments back.
+    /// `const_types` are the declared types of the const generic parameters.
     fn resolve_path_arguments(
         &mut self,
         arguments: &PathArguments,
         generic_args: Option<&HashMap<Ident, GenericResult>>,
-    ) -> PathArguments {
+        const_types: &[Option<String>],
+    ) -> Result<PathArguments> {
         let mut resolved_arguments = arguments.clone();
+        let const_args = const_args(generic_args);
+        let evaluator = ConstEvaluator::new(self.current_module.clone(), &const_args);
         if let PathArguments::AngleBracketed(generic) = &mut resolved_arguments {
+            // Lifetimes don't make different types, so they are all erased to `'_`, which is
+            // also what the generated impls need. They don't count in the positions of
+            // `const_types`.
             for arg in generic.args.iter_mut() {
+                if let GenericArgument::Lifetime(lifetime) = arg {
+                    *lifetime = Lifetime::new("'_", lifetime.apostrophe);
+                }
+            }
+            let args = generic
+                .args
+                .iter_mut()
+                .filter(|arg| !matches!(arg, GenericArgument::Lifetime(_)));
+            for (i, arg) in args.enumerate() {
                 trace!("Resolving generic argument {:?}", arg);
                 let resolved_arg: Option<GenericArgument> = match arg {
-                    syn::GenericArgument::Lifetime(_) => {
-                        /* noop */
-                        None
+                    syn::GenericArgument::Lifetime(_) => unreachable!(),
+                    // A constant without braces, e.g. `QUEUE` of `RRefDeque<BlkReq, QUEUE>`.
+                    syn::GenericArgument::Type(Type::Path(ty))
+                        if ty.qself.is_none() && evaluator.is_constant(&ty.path) =>
+                    {
+                        let expr: Expr = syn::parse_quote!(#ty);
+                        Some(GenericArgument::Const(Expr::Lit(ExprLit {
+                            attrs: vec![],
+                            lit: eval_const_arg(&evaluator, &expr, const_types.get(i))?,
+                        })))
                     }
                     syn::GenericArgument::Type(ty) => {
                         // It is possible that `ty` is resolved into a constant literal.
-                        Some(self.find_rrefed_in_type(ty, generic_args).into())
+                        Some(self.find_rrefed_in_type(ty, generic_args)?.into())
+                    }
+                    // An associated type of a trait object, e.g. `Buf` of `dyn Disk<Buf = Block>`.
+                    syn::GenericArgument::Binding(binding) => {
+                        let mut resolved_binding = binding.clone();
+                        resolved_binding.ty =
+                            self.find_rrefed_in_type(&binding.ty, generic_args)?.ty()?;
+                        Some(GenericArgument::Binding(resolved_binding))
+                    }
+                    syn::GenericArgument::Constraint(x) => {
+                        return Err(Error::new_spanned(
+                            x,
+                            "Trait constraints are not supported in generic arguments",
+                        ))
                     }
-                    syn::GenericArgument::Binding(x) => unimplemented!("{:#?}", x),
-                    syn::GenericArgument::Constraint(x) => unimplemented!("{:#?}", x),
                     syn::GenericArgument::Const(expr) => {
-                        let lit = match expr {
-                            syn::Expr::Lit(lit) => lit.lit.clone(),
-                            syn::Expr::Path(path) => {
-                                let (path, node) = self.resolve_path(&path.path, generic_args);
-                                let node = crate::expect!(node, "Generic experssion must not contains paths from external crates.\nPath arguments: {:?}\nForeign path: {:?}", arguments, path);
-                                let lit = match &node.borrow().terminal.as_ref().unwrap().definition {
-                                    Definition::Literal(lit) => lit.clone(),
-                                    _ => panic!("All generic constants must be able to resolved to a compile time constant.\nPath: {:?}\nNode: {:?}", path, node.borrow()),
-                                };
-                                lit
-                            }
-                            _ => unimplemented!(),
-                        };
                         Some(GenericArgument::Const(Expr::Lit(ExprLit {
                             attrs: vec![],
-                            lit,
+                            lit: eval_const_arg(&evaluator, expr, const_types.get(i))?,
                         })))
                     }
                 };
@@ -566,7 +583,33 @@ impl RRefedFinder {
                 }
             }
         }
-        resolved_arguments
+        Ok(resolved_arguments)
+    }
+}
+
+/// Adds `lifetimes` `'_`s to `arguments` if the lifetime arguments are elided, so that the elided
+/// lifetimes and the explicit ones make the same type.
+fn spell_out_elided_lifetimes(arguments: &mut PathArguments, lifetimes: usize) {
+    if lifetimes == 0 {
+        return;
+    }
+    if let PathArguments::None = arguments {
+        *arguments = PathArguments::AngleBracketed(syn::parse_quote!(<>));
+    }
+    if let PathArguments::AngleBracketed(args) = arguments {
+        if args
+            .args
+            .iter()
+            .any(|arg| matches!(arg, GenericArgument::Lifetime(_)))
+        {
+            return;
+        }
+        let mut spelled_out: Punctuated<GenericArgument, Token![,]> =
+            std::iter::repeat(GenericArgument::Lifetime(syn::parse_quote!('_)))
+                .take(lifetimes)
+                .collect();
+        spelled_out.extend(std::mem::take(&mut args.args));
+        args.args = spelled_out;
     }
 }
 
@@ -580,17 +623,23 @@ enum GenericResult {
 
 #[allow(dead_code)]
 impl GenericResult {
-    fn ty(self) -> Type {
+    fn ty(self) -> Result<Type> {
         match self {
-            GenericResult::Type(ty) => ty,
-            GenericResult::Literal(lit) => panic!("Expecting a type, but found {:#?}", lit),
+            GenericResult::Type(ty) => Ok(ty),
+            GenericResult::Literal(lit) => Err(Error::new_spanned(
+                lit,
+                "Expecting a type, but found a constant",
+            )),
         }
     }
 
-    fn lit(self) -> Lit {
+    fn lit(self) -> Result<Lit> {
         match self {
-            GenericResult::Literal(lit) => lit,
-            GenericResult::Type(ty) => panic!("Expecting a literal, but found {:#?}", ty),
+            GenericResult::Literal(lit) => Ok(lit),
+            GenericResult::Type(ty) => Err(Error::new_spanned(
+                ty,
+                "Expecting a constant, but found a type",
+            )),
         }
     }
 }
@@ -605,3 +654,109 @@ impl Into<GenericArgument> for GenericResult {
         }
     }
 }
+
+/// Returns the values of the const generic parameters in `generic_args`.
+fn const_args(generic_args: Option<&HashMap<Ident, GenericResult>>) -> HashMap<Ident, Lit> {
+    generic_args
+        .into_iter()
+        .flatten()
+        .filter_map(|(ident, arg)| match arg {
+            GenericResult::Literal(lit) => Some((ident.clone(), lit.clone())),
+            GenericResult::Type(_) => None,
+        })
+        .collect()
+}
+
+/// Evaluates the const generic argument `expr` as the declared type of its parameter, if known.
+fn eval_const_arg(
+    evaluator: &ConstEvaluator,
+    expr: &Expr,
+    ty: Option<&Option<String>>,
+) -> Result<Lit> {
+    match ty {
+        Some(Some(ty)) => evaluator.eval_as(expr, ty),
+        _ => evaluator.eval(expr),
+    }
+}
+
+/// Maps the generic parameters in `generics` of the item `ident`, which is a `kind` like `Struct`,
+/// to their arguments in `arguments`. For example, for definition `RRef<T>` and usage `RRef<u8>`, we map `T`
+/// to `u8`. The arguments must be resolved already.
+fn map_generic_args(
+    kind: &str,
+    ident: &Ident,
+    generics: &Generics,
+    arguments: &PathArguments,
+) -> Result<HashMap<Ident, GenericResult>> {
+    let empty_args: syn::AngleBracketedGenericArguments = syn::parse_quote!(<>);
+    let args = match arguments {
+        PathArguments::None => &empty_args,
+        PathArguments::Parenthesized(x) => {
+            return Err(Error::new_spanned(
+                x,
+                format!(
+                    "Unexpected parenthesized arguments for {} `{}`",
+                    kind.to_lowercase(),
+                    ident
+                ),
+            ))
+        }
+        PathArguments::AngleBracketed(args) => args,
+    };
+
+    // Lifetimes don't change the layout, so they are not mapped. Lifetime arguments may be elided.
+    let params: Vec<&Ident> = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            syn::GenericParam::Lifetime(_) => None,
+            syn::GenericParam::Const(c) => Some(&c.ident),
+            syn::GenericParam::Type(param) => Some(&param.ident),
+        })
+        .collect();
+    let args: Vec<&GenericArgument> = args
+        .args
+        .iter()
+        .filter(|arg| !matches!(arg, GenericArgument::Lifetime(_)))
+        .collect();
+    if params.len() != args.len() {
+        return Err(Error::new_spanned(
+            arguments,
+            format!(
+                "{} `{}` takes {} generic arguments but {} are given",
+                kind,
+                ident,
+                params.len(),
+                args.len()
+            ),
+        ));
+    }
+
+    params
+        .into_iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            let param = param.clone();
+
+            let arg = match arg {
+                syn::GenericArgument::Const(Expr::Lit(lit)) => {
+                    GenericResult::Literal(lit.lit.clone())
+                }
+                syn::GenericArgument::Type(arg) => GenericResult::Type(arg.clone()),
+                arg => {
+                    return Err(Error::new_spanned(
+                        arg,
+                        format!(
+                            "Unsupported generic argument for `{}` of {} `{}`; expecting a type or a literal",
+                            param,
+                            kind.to_lowercase(),
+                            ident
+                        ),
+                    ))
+                }
+            };
+
+            Ok((param, arg))
+        })
+        .collect()
+}
@@ -0,0 +1,121 @@
+//! Resolution of the supertraits of the interfaces, e.g. `BDev` of `pub trait NvmeBDev: BDev`.
+//!
+//! The proxy of an interface implements the supertraits as well, so it needs their definitions.
+//! Marker traits like `Send` have no methods and are implemented by the proxies anyway, so they
+//! are skipped.
+
+use std::collections::HashSet;
+
+use syn::{Item, ItemTrait, Path, TypeParamBound};
+
+use super::symbol_tree::*;
+use super::utils::{idents_to_path, path_to_string, walk_path};
+use crate::error::{Error, Result};
+
+/// The marker traits that may be the supertraits of an interface.
+const MARKER_TRAITS: &[&str] = &["Send", "Sync", "Unpin"];
+
+/// A supertrait of an interface.
+pub struct Supertrait {
+    /// The fully-qualified path of the trait, e.g. `crate::bdev::BDev`.
+    pub path: Path,
+    /// The definition of the trait. Its types are not resolved.
+    pub definition: ItemTrait,
+    /// The module where the trait is defined, which is where its types should be resolved.
+    pub module: Module,
+}
+
+/// Returns the supertraits of the trait `tr`, which is defined in `module`, and theirs,
+/// recursively. Each trait appears once, before its own supertraits.
+pub fn find_supertraits(module: &Module, tr: &ItemTrait) -> Result<Vec<Supertrait>> {
+    let mut supertraits = vec![];
+    let mut visited = HashSet::new();
+    find_recursive(module, tr, &mut supertraits, &mut visited)?;
+    Ok(supertraits)
+}
+
+fn find_recursive(
+    module: &Module,
+    tr: &ItemTrait,
+    supertraits: &mut Vec<Supertrait>,
+    visited: &mut HashSet<Path>,
+) -> Result<()> {
+    for bound in &tr.supertraits {
+        let path = match bound {
+            TypeParamBound::Trait(bound) => &bound.path,
+            TypeParamBound::Lifetime(_) => continue,
+        };
+        let supertrait = match find_supertrait(module, tr, path)? {
+            Some(supertrait) => supertrait,
+            None => continue,
+        };
+        if !visited.insert(supertrait.path.clone()) {
+            continue;
+        }
+        let (definition, module) = (supertrait.definition.clone(), supertrait.module.clone());
+        supertraits.push(supertrait);
+        find_recursive(&module, &definition, supertraits, visited)?;
+    }
+    Ok(())
+}
+
+/// Returns the supertrait `path` of `tr`, which is defined in `module`, or `None` if it's a marker
+/// trait.
+fn find_supertrait(module: &Module, tr: &ItemTrait, path: &Path) -> Result<Option<Supertrait>> {
+    let is_marker =
+        MARKER_TRAITS.contains(&path.segments.last().unwrap().ident.to_string().as_str());
+    let unsupported = || {
+        Error::new_spanned(
+            path,
+            format!(
+                "Unsupported supertrait of `{}`; expecting a trait in the IDL or one of {}",
+                tr.ident,
+                MARKER_TRAITS
+                    .iter()
+                    .map(|marker| format!("`{}`", marker))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )
+    };
+
+    // Traits from the crates without sources, e.g. `core::marker::Send`.
+    let first_segment = &path.segments.first().unwrap().ident;
+    if module.borrow().get(first_segment).is_none() {
+        return match is_marker {
+            true => Ok(None),
+            false => Err(unsupported()),
+        };
+    }
+
+    let node = walk_path(module.clone(), path)?;
+    let node = node.borrow();
+    let terminal = node.terminal.as_ref().unwrap();
+    match &terminal.definition {
+        Definition::Type(Item::Trait(definition)) => {
+            if !definition.generics.params.is_empty()
+                || !path.segments.last().unwrap().arguments.is_empty()
+            {
+                return Err(Error::new_spanned(
+                    path,
+                    format!(
+                        "Generic supertrait `{}` of `{}` is not supported",
+                        path_to_string(&node.path),
+                        tr.ident
+                    ),
+                ));
+            }
+            Ok(Some(Supertrait {
+                path: idents_to_path(&node.path),
+                definition: definition.clone(),
+                module: terminal.node.borrow().get_parent_module()?,
+            }))
+        }
+        Definition::Builtin | Definition::ForeignType(_) if is_marker => Ok(None),
+        Definition::Builtin | Definition::ForeignType(_) => Err(unsupported()),
+        _ => Err(Error::new_spanned(
+            path,
+            format!("`{}` is not a trait", path_to_string(&node.path)),
+        )),
+    }
+}
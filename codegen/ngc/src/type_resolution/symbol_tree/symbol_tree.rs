@@ -1,12 +1,25 @@
 use super::module::Module;
 use super::symbol_tree_node::*;
 use quote::format_ident;
+use syn::{Ident, ItemImpl, Path};
 
 /// A tree that contains all the symbols in the AST.
 /// Each node is a module
 #[derive(Debug, Clone)]
 pub struct SymbolTree {
     pub root: SymbolTreeNode,
+    /// The module of `root`.
+    root_module: Module,
+    /// The trait impls in the AST and the modules they are in. They are used to resolve the
+    /// associated types, e.g. `<Foo as Bar>::Baz`.
+    pub impls: Vec<(Module, ItemImpl)>,
+    /// The roots of the external crates whose sources are given. Their paths start with the names
+    /// of the crates instead of `crate`.
+    pub extern_crates: Vec<SymbolTreeNode>,
+    /// The instantiations of the generic traits that are used as trait objects, e.g.
+    /// `crate::queue::Queue<u64>` of `Box<dyn Queue<u64>>`, with the arguments resolved. They are
+    /// found by the `TypeResolver`.
+    pub instantiations: Vec<Path>,
 }
 
 impl SymbolTree {
@@ -20,16 +33,62 @@ impl SymbolTree {
             true,
             vec![format_ident!("crate")],
         );
-        let definition = Definition::Module(Module::new(root_ident, root.clone()));
+        let root_module = Module::new(root_ident, root.clone());
+        let definition = Definition::Module(root_module.clone());
         root.borrow_mut().terminal = Some(Terminal::new(root.clone(), definition));
-        Self { root }
+        Self {
+            root,
+            root_module,
+            impls: vec![],
+            extern_crates: vec![],
+            instantiations: vec![],
+        }
     }
 
     /// Returns the root of the tree in as a `SymbolTreeNode`.
     pub fn root_module(&self) -> Module {
-        match &self.root.borrow().terminal.as_ref().unwrap().definition {
-            Definition::Module(md) => md.clone(),
-            _ => panic!(),
+        self.root_module.clone()
+    }
+
+    /// Returns the root of the external crate `ident`, if its source is given.
+    pub fn extern_crate(&self, ident: &Ident) -> Option<SymbolTreeNode> {
+        self.extern_crates
+            .iter()
+            .find(|root| root.borrow().ident == *ident)
+            .cloned()
+    }
+
+    /// Returns the node of a fully-qualified path, e.g. `crate::foo::Bar` or `extern_lib::Baz`, if it's
+    /// in the tree.
+    pub fn lookup(&self, path: &[Ident]) -> Option<SymbolTreeNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = match first == "crate" {
+            true => self.root.clone(),
+            false => self.extern_crate(first)?,
+        };
+        for ident in rest {
+            let module = match &node.borrow().terminal.as_ref()?.definition {
+                Definition::Module(md) => md.clone(),
+                _ => return None,
+            };
+            let next_node = module.borrow().get(ident)?.clone();
+            node = next_node;
+        }
+        Some(node)
+    }
+
+    /// Resolves `path` as if it's used in the module `module`, e.g. `crate::foo`, and returns
+    /// the node it points to. Relative paths and imports are followed.
+    pub fn resolve(&self, module: &[Ident], path: &syn::Path) -> Option<SymbolTreeNode> {
+        let mut node = self.lookup(module)?;
+        for segment in &path.segments {
+            let module = match &node.borrow().terminal.as_ref()?.definition {
+                Definition::Module(md) => md.clone(),
+                _ => return None,
+            };
+            let next_node = module.borrow().get(&segment.ident)?.clone();
+            node = next_node;
         }
+        Some(node)
     }
 }
@@ -1,11 +1,13 @@
 use super::module::Module;
+use crate::error::{Error, Result};
+use crate::type_resolution::utils::path_to_string;
 
 use std::{
     cell::{Ref, RefCell, RefMut},
     rc::Rc,
 };
 
-use syn::{Ident, Item, Lit};
+use syn::{Ident, Item, ItemConst};
 
 #[derive(Derivative)]
 #[derivative(Debug)]
@@ -24,23 +26,44 @@ pub struct SymbolTreeNodeInner {
     pub leading_colon: bool,
     /// Current best known absolute path of the symbol.
     pub path: Vec<Ident>,
+    /// If the symbol is imported by two globs that point to different definitions, the path of
+    /// the other one. Using the symbol is an error, but declaring it is not, like in rustc.
+    pub ambiguous_with: Option<Vec<Ident>>,
 }
 
 impl SymbolTreeNodeInner {
+    /// Returns an error if the symbol is ambiguous, which `ident` uses.
+    pub fn check_ambiguous(&self, ident: &Ident) -> Result<()> {
+        match &self.ambiguous_with {
+            Some(other) => Err(Error::new_spanned(
+                ident,
+                format!(
+                    "`{}` is ambiguous: it is glob imported as both `{}` and `{}`",
+                    ident,
+                    path_to_string(&self.path),
+                    path_to_string(other)
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
     /// Get the parent module from this node.
-    pub fn get_parent_module(&self) -> Module {
-        match &self
-            .parent
-            .as_ref()
-            .unwrap()
-            .borrow()
+    pub fn get_parent_module(&self) -> Result<Module> {
+        let not_in_module = || {
+            Error::new_spanned(
+                &self.ident,
+                format!("`{}` is not in a module", path_to_string(&self.path)),
+            )
+        };
+        let parent = self.parent.as_ref().ok_or_else(not_in_module)?.borrow();
+        match parent
             .terminal
             .as_ref()
-            .unwrap()
-            .definition
+            .map(|terminal| &terminal.definition)
         {
-            Definition::Module(md) => md.clone(),
-            _ => panic!("Expecting the parent of a module to be a module"),
+            Some(Definition::Module(md)) => Ok(md.clone()),
+            _ => Err(not_in_module()),
         }
     }
 }
@@ -67,10 +90,15 @@ impl SymbolTreeNode {
                 terminal,
                 leading_colon,
                 path,
+                ambiguous_with: None,
             })),
         }
     }
 
+    pub fn same(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+
     pub fn borrow(&self) -> Ref<SymbolTreeNodeInner> {
         RefCell::borrow(&self.inner)
     }
@@ -79,6 +107,16 @@ impl SymbolTreeNode {
         RefCell::borrow_mut(&self.inner)
     }
 
+    /// Returns true if the node is the root module of a crate.
+    pub fn is_crate_root(&self) -> bool {
+        let node = self.borrow();
+        node.parent.is_none()
+            && matches!(
+                node.terminal.as_ref().map(|terminal| &terminal.definition),
+                Some(Definition::Module(_))
+            )
+    }
+
     /// Returns the root node of the tree.
     pub fn root(&self) -> Self {
         match self.borrow().parent.as_ref() {
@@ -117,6 +155,6 @@ pub enum Definition {
     ForeignType(Vec<Ident>),
     /// A module.
     Module(Module),
-    /// A literal.
-    Literal(Lit),
+    /// A constant. Its value is evaluated when it's used in a type.
+    Constant(ItemConst),
 }
@@ -0,0 +1,115 @@
+//! Stable type IDs of the `RRef`ed types.
+//!
+//! Domains are built separately and must agree on the ID of every type on the shared heap, so the
+//! ID of a type is derived from the type itself instead of the order the types are found in. The
+//! ID is the 64-bit FNV-1a hash of the canonical name of the fully-resolved type.
+
+use std::collections::BTreeMap;
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::Type;
+
+use crate::error::{Diagnostics, Error};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Returns the canonical name of `ty`, e.g. `crate::foo::Bar<[u8;4096usize]>`. Whitespaces are
+/// dropped except between two words, so the name doesn't depend on how `ty` is formatted.
+/// `ty` must be fully resolved so that the same type always has the same name.
+pub fn canonical_name(ty: &Type) -> String {
+    canonical_tokens(ty.to_token_stream())
+}
+
+/// Same as `canonical_name` but for any `tokens`, e.g. the generic arguments of a trait.
+pub fn canonical_tokens(tokens: TokenStream) -> String {
+    let mut name = String::new();
+    write_tokens(&mut name, tokens);
+    name
+}
+
+fn write_tokens(name: &mut String, tokens: TokenStream) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                name.push_str(open);
+                write_tokens(name, group.stream());
+                name.push_str(close);
+            }
+            TokenTree::Ident(ident) => {
+                push_word(name, &ident.to_string());
+            }
+            TokenTree::Literal(literal) => {
+                push_word(name, &literal.to_string());
+            }
+            TokenTree::Punct(punct) => name.push(punct.as_char()),
+        }
+    }
+}
+
+/// Appends a word, separated from the previous one with a space.
+fn push_word(name: &mut String, word: &str) {
+    if name.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+        name.push(' ');
+    }
+    name.push_str(word);
+}
+
+/// Returns the ID of `ty`.
+pub fn type_id(ty: &Type) -> u64 {
+    fnv1a(canonical_name(ty).as_bytes())
+}
+
+pub(super) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Assigns the IDs to `types`. Returns the IDs and the types sorted by the canonical names of the
+/// types, or an error if two different types end up with the same ID.
+pub fn assign_type_ids<'a>(
+    types: impl IntoIterator<Item = &'a Type>,
+) -> Result<Vec<(u64, &'a Type)>, Diagnostics> {
+    assign_type_ids_with(types, fnv1a)
+}
+
+/// Same as `assign_type_ids` but hashes the canonical names with `hash`.
+pub(super) fn assign_type_ids_with<'a>(
+    types: impl IntoIterator<Item = &'a Type>,
+    hash: fn(&[u8]) -> u64,
+) -> Result<Vec<(u64, &'a Type)>, Diagnostics> {
+    // Different spellings of the same type are the same type.
+    let types: BTreeMap<String, &Type> = types
+        .into_iter()
+        .map(|ty| (canonical_name(ty), ty))
+        .collect();
+
+    let mut errors = Diagnostics::new();
+    let mut assigned: BTreeMap<u64, &str> = BTreeMap::new();
+    for name in types.keys() {
+        if let Some(other) = assigned.insert(hash(name.as_bytes()), name) {
+            errors.push(Error::new_spanned(
+                types[name],
+                format!(
+                    "Type ID collision between `{}` and `{}`; rename one of the types",
+                    other, name
+                ),
+            ));
+        }
+    }
+
+    errors.finish(
+        types
+            .iter()
+            .map(|(name, ty)| (hash(name.as_bytes()), *ty))
+            .collect(),
+    )
+}
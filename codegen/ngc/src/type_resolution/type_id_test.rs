@@ -0,0 +1,45 @@
+use super::type_id::{assign_type_ids, assign_type_ids_with, canonical_name, type_id};
+use syn::{parse_quote, Type};
+
+#[test]
+fn test_canonical_name() {
+    let ty: Type = parse_quote!(crate::rref::rref::RRef<[u8; 4096usize]>);
+    assert_eq!(
+        canonical_name(&ty),
+        "crate::rref::rref::RRef<[u8;4096usize]>"
+    );
+
+    // Only the words are separated.
+    let ty: Type = parse_quote!(&'static mut dyn crate::foo::Foo);
+    assert_eq!(canonical_name(&ty), "&'static mut dyn crate::foo::Foo");
+
+    // Formatting doesn't matter.
+    let ty: Type = syn::parse_str("( usize ,crate :: Foo < u8 > , )").unwrap();
+    assert_eq!(canonical_name(&ty), "(usize,crate::Foo<u8>)");
+}
+
+#[test]
+fn test_assign_type_ids() {
+    let types: Vec<Type> = vec![
+        parse_quote!(crate::Foo),
+        parse_quote!(usize),
+        parse_quote!(crate::Foo),
+    ];
+    let ids = assign_type_ids(&types).unwrap();
+    assert_eq!(ids.len(), 2);
+    assert_eq!(ids[0], (type_id(&types[0]), &types[0]));
+    assert_eq!(ids[1], (type_id(&types[1]), &types[1]));
+}
+
+#[test]
+fn test_type_id_collision() {
+    let types: Vec<Type> = vec![parse_quote!(crate::Foo), parse_quote!(crate::Bar)];
+    let errors = assign_type_ids_with(&types, |_| 42).unwrap_err();
+    let errors: Vec<_> = errors.iter().map(|err| err.message().to_owned()).collect();
+    assert_eq!(
+        errors,
+        vec![String::from(
+            "Type ID collision between `crate::Bar` and `crate::Foo`; rename one of the types"
+        )]
+    );
+}
@@ -1,18 +1,52 @@
-use super::{symbol_tree::*, utils::is_public};
-use crate::expect;
+use super::{
+    symbol_tree::*,
+    utils::{is_public, path_to_string},
+    ExternCrate,
+};
+use crate::error::{Diagnostics, Error, Result};
 use crate::type_resolution::symbol_tree::PATH_MODIFIERS;
 use log::{debug, info, trace};
 
-use syn::{File, Ident, Item, UseTree, Visibility};
+use std::collections::{HashMap, HashSet};
+use syn::{File, Ident, Item, UseGlob, UseTree, Visibility};
 
 const RELATIVE_PATH_TARGET: &str = "relative_path_resolution";
 
+/// The maximum number of imports to follow when looking for the module of a glob import. Anything
+/// deeper is an import cycle.
+const MAX_IMPORT_DEPTH: usize = 64;
+
+/// A glob import, e.g. `use crate::rref::*`.
+struct GlobImport {
+    /// The module where the glob is imported.
+    module: Module,
+    /// The path of the glob without the `*`, e.g. `crate::rref`.
+    path: Vec<Ident>,
+    /// Whether the path has a leading colon.
+    leading_colon: bool,
+    /// The visibility of the imported symbols.
+    vis: Visibility,
+    /// The glob itself, for error reporting.
+    glob: UseGlob,
+}
+
 /// Walks the AST, find type information and put it in `self.symbol_tree`.
 pub struct TypeInfoFinder {
     /// A stack of maps a PathSegment to its fully qualified path.
     symbol_tree: SymbolTree,
     /// The current module(aka current frame).
     current_module: Module,
+    /// Errors found so far.
+    errors: Diagnostics,
+    /// Glob imports to be expanded after all the other symbols are added.
+    globs: Vec<GlobImport>,
+    /// Maps a symbol imported by a glob, as `(module path, ident)`, to the index of the glob.
+    glob_imported: HashMap<(Vec<Ident>, Ident), usize>,
+    /// Symbols imported by more than one glob, as `(imported node, other node)`. They are fine as
+    /// long as both nodes resolve to the same definition or the symbol is not used.
+    glob_conflicts: Vec<(SymbolTreeNode, SymbolTreeNode)>,
+    /// The root modules of the external crates and their items, to be added after the IDL.
+    extern_items: Vec<(Module, Vec<Item>)>,
 }
 
 impl TypeInfoFinder {
@@ -22,18 +56,127 @@ impl TypeInfoFinder {
         Self {
             symbol_tree,
             current_module: root,
+            errors: Diagnostics::new(),
+            globs: vec![],
+            glob_imported: HashMap::new(),
+            glob_conflicts: vec![],
+            extern_items: vec![],
         }
     }
 
+    /// Adds the symbols of an external crate to the tree. Its root module is named after the crate
+    /// and is visible in every module, like the crates in the extern prelude of rustc.
+    pub fn add_extern_crate(&mut self, extern_crate: &ExternCrate) {
+        let name = &extern_crate.name;
+        if name == "crate" || self.symbol_tree.extern_crate(name).is_some() {
+            self.errors.push(Error::new(format!(
+                "External crate `{}` is given more than once",
+                name
+            )));
+            return;
+        }
+
+        let root = SymbolTreeNode::new(name.clone(), true, None, None, true, vec![name.clone()]);
+        let module = Module::new(name, root.clone());
+        root.borrow_mut().terminal = Some(Terminal::new(
+            root.clone(),
+            Definition::Module(module.clone()),
+        ));
+        self.symbol_tree.extern_crates.push(root);
+        // The crate may import the crates that are added after it, so its items are added after
+        // all the crates are known.
+        self.extern_items
+            .push((module, extern_crate.ast.items.clone()));
+    }
+
     /// Takes a AST and returns a list of fully-qualified paths of all `RRef`ed types.
-    pub fn find_type_info(mut self, ast: &File) -> SymbolTree {
+    pub fn find_type_info(mut self, ast: &File) -> std::result::Result<SymbolTree, Diagnostics> {
         self.resolve_types_recursive(&ast.items);
-        self.resolve_relative_paths_recursive_for_module(self.current_module.clone());
-        self.symbol_tree
+        for (module, items) in std::mem::take(&mut self.extern_items) {
+            info!(
+                "Finding type info in external crate {:?}",
+                module.borrow().path
+            );
+            let old_frame = std::mem::replace(&mut self.current_module, module);
+            self.resolve_types_recursive(&items);
+            self.current_module = old_frame;
+        }
+        self.add_extern_prelude();
+        self.expand_globs();
+        self.mark_glob_conflicts();
+        for root in self.crate_roots() {
+            self.current_module = root.clone();
+            self.resolve_relative_paths_recursive_for_module(root);
+        }
+        self.current_module = self.symbol_tree.root_module();
+        self.errors.finish(self.symbol_tree)
+    }
+
+    /// Returns the root modules of the IDL and the external crates.
+    fn crate_roots(&self) -> Vec<Module> {
+        std::iter::once(&self.symbol_tree.root)
+            .chain(&self.symbol_tree.extern_crates)
+            .map(
+                |root| match &root.borrow().terminal.as_ref().unwrap().definition {
+                    Definition::Module(md) => md.clone(),
+                    _ => unreachable!("Expecting the root of a crate to be a module"),
+                },
+            )
+            .collect()
+    }
+
+    /// Makes the external crates visible in every module of every crate. Symbols with the same
+    /// names as the crates shadow them.
+    fn add_extern_prelude(&mut self) {
+        if self.symbol_tree.extern_crates.is_empty() {
+            return;
+        }
+
+        let mut modules = self.crate_roots();
+        while let Some(module) = modules.pop() {
+            // Only the modules defined here; the imported ones are not resolved yet.
+            modules.extend(module.borrow().iter().filter_map(|(ident, node)| {
+                let node = node.borrow();
+                match node.terminal.as_ref().map(|terminal| &terminal.definition) {
+                    Some(Definition::Module(md))
+                        if node.parent.is_some()
+                            && !PATH_MODIFIERS.contains(&ident.to_string()) =>
+                    {
+                        Some(md.clone())
+                    }
+                    _ => None,
+                }
+            }));
+            for root in &self.symbol_tree.extern_crates {
+                let ident = root.borrow().ident.clone();
+                let mut module = module.borrow_mut();
+                if module.get(&ident).is_none() {
+                    module.insert(ident, root.clone());
+                }
+            }
+        }
+    }
+
+    /// Records `err` found in `module`. The external crates are not in the input file, so their
+    /// errors name the crate instead of pointing at a location.
+    fn push_error(&mut self, module: &Module, err: Error) {
+        let root = module.borrow().node.root();
+        let err = match root.borrow().ident == "crate" {
+            true => err,
+            false => Error::new(format!(
+                "In external crate `{}`: {}",
+                root.borrow().ident,
+                err.message()
+            )),
+        };
+        self.errors.push(err);
     }
 
     /// Returns the resolved/terminal path of `module_item`.
-    fn resolve_relative_paths_recursive_for_symbol_tree_node(&mut self, node: SymbolTreeNode) {
+    fn resolve_relative_paths_recursive_for_symbol_tree_node(
+        &mut self,
+        node: SymbolTreeNode,
+    ) -> Result<()> {
         let node_ref = node.borrow();
         trace!(
             target: RELATIVE_PATH_TARGET,
@@ -46,74 +189,120 @@ impl TypeInfoFinder {
         // If the node is a module, recursively go into the module and resolve relative paths there.
         if let Some(terminal) = &node_ref.terminal {
             match &terminal.definition {
+                // An imported module, e.g. one imported by `use super::*`, is resolved where it's
+                // defined.
+                Definition::Module(_) if !terminal.node.same(&node) => {
+                    return Ok(());
+                }
                 Definition::Module(item) => {
                     // Go to the children frame and do recursive call.
-                    let old_frame = self.current_module.clone();
-                    self.current_module = item.clone();
+                    let old_frame = std::mem::replace(&mut self.current_module, item.clone());
                     self.resolve_relative_paths_recursive_for_module(self.current_module.clone());
                     // Pop the frame and return back to the old frame.
-                    let parent_frame = self
-                        .current_module
-                        .borrow()
-                        .node
-                        .borrow()
-                        .get_parent_module();
-
-                    self.current_module = parent_frame;
-                    // Sanity check that we are actually restoring to the correct frame.
-                    assert!(old_frame.same(&self.current_module));
+                    self.current_module = old_frame;
 
                     // We don't need to update the path
-                    return;
+                    return Ok(());
                 }
                 Definition::Builtin
                 | Definition::Type(_)
                 | Definition::ForeignType(_)
-                | Definition::Literal(_) => {
+                | Definition::Constant(_) => {
                     // noop. Non-module terminal node; no further resolution is needed.
-                    return;
+                    return Ok(());
                 }
             }
         }
 
-        // Walk the relative path and try resolving the path.
-        // Save the previous node because we there's no way to know the parent of a type currently.
+        // Walk the relative path and try resolving the path. The path is relative to the module
+        // where the symbol is imported, which is not necessarily the current module when the
+        // symbol is a re-export that is resolved on demand.
         let mut current_node = match node_ref.leading_colon {
             true => self.symbol_tree.root.clone(),
-            false => self.current_module.borrow().node.clone(),
+            false => node_ref
+                .parent
+                .clone()
+                .unwrap_or_else(|| self.current_module.borrow().node.clone()),
         };
 
+        // If the path doesn't start with "crate" or "super" and it's not relative to the module,
+        // this means that it comes from an external library, which means we should mark it as
+        // terminal. The libraries with sources are in the module as well.
+        let first_segment = &node_ref.path[0];
+        let is_relative = match &current_node.borrow().terminal.as_ref().unwrap().definition {
+            Definition::Module(md) => md.borrow().get(first_segment).is_some(),
+            _ => false,
+        };
+        if PATH_MODIFIERS.get(&first_segment.to_string()).is_none() && !is_relative {
+            let path = node_ref.path.clone();
+            drop(node_ref);
+            node.borrow_mut().terminal =
+                Some(Terminal::new(node.clone(), Definition::ForeignType(path)));
+            return Ok(());
+        }
+
+        let importing_module = match &node_ref.parent {
+            Some(parent) => parent.borrow().path.clone(),
+            None => vec![],
+        };
         for path_segment in &node_ref.path {
             trace!("Resolving path segment {:?}", path_segment);
+            // Resolve the imported modules first, e.g. `b` of `use a as b; use b::Baz;`.
+            if current_node.borrow().terminal.is_none() {
+                self.resolve_relative_paths_recursive_for_symbol_tree_node(current_node.clone())?;
+            }
             // Borrow it seperately so that we can assign to `current_node` later.
             let terminal = current_node.borrow().terminal.clone();
+            let terminal = terminal.ok_or_else(|| {
+                Error::new_spanned(
+                    path_segment,
+                    format!(
+                        "Unable to resolve `{}`: `{}` can't be resolved",
+                        path_to_string(&node_ref.path),
+                        path_to_string(&current_node.borrow().path)
+                    ),
+                )
+            })?;
 
             // Resolve the path_segment into a node.
-            match &expect!(
-                terminal.as_ref(),
-                "Expecting a module, found non-terminal: {:?}",
-                current_node
-            )
-            .definition
-            {
-                Definition::Type(_) => panic!(
-                    "Resolving {:#?} for {:#?}. Node {:#?} is a symbol and cannot have child.",
-                    path_segment, node_ref.path, current_node
-                ),
+            match &terminal.definition {
                 Definition::Module(md) => {
                     let md = md.borrow();
-                    let next_node = md.get(path_segment);
-                    let next_node = crate::expect!(
-                        next_node,
-                        "When resolving {:?}, ident {:?} is not found in {:#?}",
-                        node_ref.path,
-                        path_segment,
-                        md
-                    );
-                    assert!(next_node.borrow().public);
+                    let next_node = md.get(path_segment).ok_or_else(|| {
+                        Error::new_spanned(
+                            path_segment,
+                            format!(
+                                "Unable to resolve `{}`: `{}` is not found in `{}`",
+                                path_to_string(&node_ref.path),
+                                path_segment,
+                                path_to_string(&md.path)
+                            ),
+                        )
+                    })?;
+                    next_node.borrow().check_ambiguous(path_segment)?;
+                    // Private symbols are visible in their module and its submodules.
+                    if !next_node.borrow().public && !importing_module.starts_with(&md.path) {
+                        return Err(Error::new_spanned(
+                            path_segment,
+                            format!(
+                                "Unable to resolve `{}`: `{}` is private",
+                                path_to_string(&node_ref.path),
+                                path_segment
+                            ),
+                        ));
+                    }
                     current_node = next_node.clone();
                 }
-                _ => panic!(),
+                _ => {
+                    return Err(Error::new_spanned(
+                        path_segment,
+                        format!(
+                            "Unable to resolve `{}`: `{}` is not a module",
+                            path_to_string(&node_ref.path),
+                            path_to_string(&current_node.borrow().path)
+                        ),
+                    ))
+                }
             }
         }
 
@@ -122,7 +311,7 @@ impl TypeInfoFinder {
         // If the node is a module, we can treat it as terminal. It's up to the user to
         // resolve their types that in the module.
         if current_node.borrow().terminal.is_none() {
-            self.resolve_relative_paths_recursive_for_symbol_tree_node(current_node.clone());
+            self.resolve_relative_paths_recursive_for_symbol_tree_node(current_node.clone())?;
         }
         assert!(
             current_node.borrow().terminal.is_some(),
@@ -143,6 +332,7 @@ impl TypeInfoFinder {
         let resolved_node = current_node.borrow();
         node.terminal = resolved_node.terminal.clone();
         node.path = resolved_node.path.clone();
+        Ok(())
     }
 
     /// Resolve all relative paths generated `resolve_types_recursive` by into terminal
@@ -159,12 +349,16 @@ impl TypeInfoFinder {
                 ident,
                 module_path
             );
-            // If the module is a path modifier, noop.
-            if PATH_MODIFIERS.get(&ident.to_string()).is_some() {
+            // If the module is a path modifier or an external crate, noop.
+            if PATH_MODIFIERS.get(&ident.to_string()).is_some() || child.is_crate_root() {
                 trace!("Encountered path modifiler {:?}; noop", ident);
                 continue;
             }
-            self.resolve_relative_paths_recursive_for_symbol_tree_node(child.clone());
+            if let Err(err) =
+                self.resolve_relative_paths_recursive_for_symbol_tree_node(child.clone())
+            {
+                self.push_error(&module, err);
+            }
         }
     }
 
@@ -176,33 +370,22 @@ impl TypeInfoFinder {
                 Item::Const(item) => {
                     let mut path = self.current_module.borrow().node.borrow().path.clone();
                     path.push(item.ident.clone());
-                    match &*item.expr {
-                        syn::Expr::Lit(lit) => {
-                            // Create a new node.
-                            let node = SymbolTreeNode::new(
-                                item.ident.clone(),
-                                is_public(&item.vis),
-                                Some(self.current_module.borrow().node.clone()),
-                                None,
-                                true,
-                                path,
-                            );
-                            // Update its terminal
-                            node.borrow_mut().terminal = Some(Terminal::new(
-                                node.clone(),
-                                Definition::Literal(lit.lit.clone()),
-                            ));
-                            // Insert the node into the current module.
-                            assert!(
-                                self.current_module
-                                    .borrow_mut()
-                                    .insert(item.ident.clone(), node)
-                                    .is_none(),
-                                "type node shouldn't appear more than once"
-                            );
-                        }
-                        _ => self.add_definition_symbol(&item.ident, &item.vis, &og_item),
-                    }
+                    // Create a new node.
+                    let node = SymbolTreeNode::new(
+                        item.ident.clone(),
+                        is_public(&item.vis),
+                        Some(self.current_module.borrow().node.clone()),
+                        None,
+                        true,
+                        path,
+                    );
+                    // Update its terminal
+                    node.borrow_mut().terminal = Some(Terminal::new(
+                        node.clone(),
+                        Definition::Constant(item.clone()),
+                    ));
+                    // Insert the node into the current module.
+                    self.insert_symbol(&item.ident, node);
                 }
                 Item::Enum(item) => {
                     self.add_definition_symbol(&item.ident, &item.vis, &og_item);
@@ -210,17 +393,11 @@ impl TypeInfoFinder {
                 Item::Mod(item) => {
                     if let Some((_, items)) = &item.content {
                         // Push a frame.
-                        self.current_module =
-                            self.current_module.create_module(&item.ident, &item.vis);
+                        let next_frame = self.current_module.create_module(&item.ident, &item.vis);
+                        let old_frame = std::mem::replace(&mut self.current_module, next_frame);
                         // Recurse into the new frame.
                         self.resolve_types_recursive(items);
                         // Pop a frame.
-                        let old_frame = self
-                            .current_module
-                            .borrow()
-                            .node
-                            .borrow()
-                            .get_parent_module();
                         self.current_module = old_frame;
                     }
                 }
@@ -239,6 +416,11 @@ impl TypeInfoFinder {
                 Item::Fn(item) => {
                     self.add_definition_symbol(&item.sig.ident, &item.vis, &og_item);
                 }
+                Item::Impl(item) if item.trait_.is_some() => {
+                    self.symbol_tree
+                        .impls
+                        .push((self.current_module.clone(), item.clone()));
+                }
                 Item::Use(item) => {
                     let path = vec![];
                     self.resolve_types_in_usetree_recursive(
@@ -280,11 +462,15 @@ impl TypeInfoFinder {
                 path.push(tree.ident.clone());
                 self.add_use_symbol(&tree.rename, vis, path, leading_colon);
             }
-            // e.g. `a::*`. This is banned because pulling the depencies in and analyze them is not
-            // within the scope of this project.
-            syn::UseTree::Glob(tree) => {
-                panic!("Use globe is disallowed in IDL. For example, you cannot do `use foo::*`. Violating code: {:#?}, {:?}", tree, path)
-            }
+            // e.g. `a::*`. Expanded after all the other symbols are added, since the glob may
+            // import symbols defined later in the file.
+            syn::UseTree::Glob(tree) => self.globs.push(GlobImport {
+                module: self.current_module.clone(),
+                path,
+                leading_colon,
+                vis: vis.clone(),
+                glob: tree.clone(),
+            }),
             // e.g. `{b::{Barc}, Car}`.
             syn::UseTree::Group(tree) => {
                 for tree in &tree.items {
@@ -299,6 +485,134 @@ impl TypeInfoFinder {
         }
     }
 
+    /// Expands the glob imports into `use` symbols of the public symbols in the globbed modules.
+    /// A symbol that is defined or explicitly imported in the module shadows the glob imported one.
+    fn expand_globs(&mut self) {
+        // A glob may import symbols that are imported by other globs, e.g. a glob of a module that
+        // re-exports another module with a glob, so keep expanding until nothing changes.
+        let mut targets: Vec<Option<Module>> = vec![None; self.globs.len()];
+        let mut reported: HashSet<(Vec<Ident>, Ident, usize)> = HashSet::new();
+        loop {
+            let mut changed = false;
+            for (index, glob) in self.globs.iter().enumerate() {
+                let target = match &targets[index] {
+                    Some(target) => target.clone(),
+                    None => match find_module(
+                        &self.symbol_tree,
+                        &glob.module,
+                        &glob.path,
+                        glob.leading_colon,
+                        0,
+                    ) {
+                        Some(target) => {
+                            targets[index] = Some(target.clone());
+                            target
+                        }
+                        None => continue,
+                    },
+                };
+
+                // The private symbols of a module are visible to itself and its descendants, e.g.
+                // through `use super::*`.
+                let sees_private = glob.module.borrow().path.starts_with(&target.borrow().path);
+                // Collect the symbols first in case the glob imports its own module.
+                let symbols: Vec<(Ident, bool)> = target
+                    .borrow()
+                    .iter()
+                    .filter(|(ident, node)| {
+                        !PATH_MODIFIERS.contains(&ident.to_string()) && !node.is_crate_root()
+                    })
+                    .map(|(ident, node)| (ident.clone(), node.borrow().public))
+                    .collect();
+                for (ident, public) in symbols {
+                    if !public && !sees_private {
+                        continue;
+                    }
+                    let mut path = glob.path.clone();
+                    path.push(ident.clone());
+                    let node = SymbolTreeNode::new(
+                        ident.clone(),
+                        is_public(&glob.vis),
+                        Some(glob.module.borrow().node.clone()),
+                        None,
+                        glob.leading_colon,
+                        path,
+                    );
+
+                    let module_path = glob.module.borrow().path.clone();
+                    let key = (module_path.clone(), ident.clone());
+                    let existing = glob.module.borrow().get(&ident).cloned();
+                    match existing {
+                        None => {
+                            trace!("Glob imported {:?} into {:?}", ident, module_path);
+                            glob.module.borrow_mut().insert(ident.clone(), node);
+                            self.glob_imported.insert(key, index);
+                            changed = true;
+                        }
+                        // Imported by another glob. Check that both point to the same thing later.
+                        Some(existing) => match self.glob_imported.get(&key) {
+                            Some(other) if *other != index => {
+                                if reported.insert((module_path, ident, index)) {
+                                    self.glob_conflicts.push((existing, node));
+                                }
+                            }
+                            // Shadowed by an explicit symbol or imported by this glob already.
+                            _ => {}
+                        },
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        // The globs of external crates without sources can't be expanded because we don't analyze
+        // them. Their symbols are treated as external, like any other symbol that's not in the
+        // tree.
+        for (index, target) in targets.into_iter().enumerate() {
+            let glob = &self.globs[index];
+            let external = !PATH_MODIFIERS.contains(&glob.path[0].to_string())
+                && self.symbol_tree.extern_crate(&glob.path[0]).is_none();
+            if target.is_none() && !external {
+                let err = Error::new_spanned(
+                    &glob.glob,
+                    format!(
+                        "Unable to resolve `{}::*`: `{}` is not a module in the IDL",
+                        path_to_string(&glob.path),
+                        path_to_string(&glob.path)
+                    ),
+                );
+                let module = glob.module.clone();
+                self.push_error(&module, err);
+            }
+        }
+    }
+
+    /// Marks the symbols imported by more than one glob but pointing to different definitions as
+    /// ambiguous. They are reported where they are used, so it runs before the `use` paths are
+    /// resolved.
+    fn mark_glob_conflicts(&mut self) {
+        for (existing, node) in std::mem::take(&mut self.glob_conflicts) {
+            // A candidate that can't be resolved is reported where it's imported, if at all.
+            if self
+                .resolve_relative_paths_recursive_for_symbol_tree_node(existing.clone())
+                .is_err()
+                || self
+                    .resolve_relative_paths_recursive_for_symbol_tree_node(node.clone())
+                    .is_err()
+            {
+                continue;
+            }
+            let path = node.borrow().path.clone();
+            let mut existing = existing.borrow_mut();
+            if existing.path != path {
+                existing.ambiguous_with = Some(path);
+            }
+        }
+    }
+
     /// Add a symbol from a `use` statement to the corrent scope.
     fn add_use_symbol(
         &mut self,
@@ -307,32 +621,19 @@ impl TypeInfoFinder {
         path: Vec<Ident>,
         leading_colon: bool,
     ) {
-        // Create a new node for the symbol.
+        // Create a new node for the symbol. Whether it comes from an external library is known
+        // after all the symbols are added.
         let node = SymbolTreeNode::new(
             ident.clone(),
             is_public(vis),
             Some(self.current_module.borrow().node.clone()),
             None,
             leading_colon,
-            path.clone(),
+            path,
         );
 
-        // If the path doesn't start with "crate" or "super", this means that it comes from
-        // an external library, which means we should mark it as terminal.
-        let first_segment = &path[0];
-        if PATH_MODIFIERS.get(&first_segment.to_string()).is_none() {
-            node.borrow_mut().terminal =
-                Some(Terminal::new(node.clone(), Definition::ForeignType(path)));
-        };
-
         // Add the symbol to the module.
-        assert!(
-            self.current_module
-                .borrow_mut()
-                .insert(ident.clone(), node)
-                .is_none(),
-            "type node shouldn't apprear more than once"
-        );
+        self.insert_symbol(ident, node);
     }
 
     /// Add a symbol that's defined in the current scope. The symbol is terminal.
@@ -354,13 +655,64 @@ impl TypeInfoFinder {
             node.clone(),
             Definition::Type(definition.clone()),
         ));
-        assert!(
-            self.current_module
-                .borrow_mut()
-                .insert(ident.clone(), node.clone())
-                .is_none(),
-            "Trying to insert {:?} but already exist. Type node shouldn't appear more than once",
-            ident.clone()
-        );
+        self.insert_symbol(ident, node);
+    }
+
+    /// Insert a symbol into the current module. A symbol shouldn't appear more than once.
+    fn insert_symbol(&mut self, ident: &Ident, node: SymbolTreeNode) {
+        let current_module = self.current_module.clone();
+        if current_module
+            .borrow_mut()
+            .insert(ident.clone(), node)
+            .is_some()
+        {
+            let err = Error::new_spanned(
+                ident,
+                format!(
+                    "`{}` is defined more than once in `{}`",
+                    ident,
+                    path_to_string(&current_module.borrow().path)
+                ),
+            );
+            self.push_error(&current_module, err);
+        }
+    }
+}
+
+/// Returns the module `path` points to when it's used in `module`, following the imports, or `None`
+/// if it can't be found. It may be found later after more glob imports are expanded.
+fn find_module(
+    symbol_tree: &SymbolTree,
+    module: &Module,
+    path: &[Ident],
+    leading_colon: bool,
+    depth: usize,
+) -> Option<Module> {
+    if depth > MAX_IMPORT_DEPTH {
+        return None;
+    }
+
+    let mut current = match leading_colon {
+        true => symbol_tree.root_module(),
+        false => module.clone(),
+    };
+    for ident in path {
+        let node = current.borrow().get(ident)?.clone();
+        let node = node.borrow();
+        current = match &node.terminal {
+            Some(terminal) => match &terminal.definition {
+                Definition::Module(md) => md.clone(),
+                _ => return None,
+            },
+            // An import. Follow it from the module where it's imported.
+            None => find_module(
+                symbol_tree,
+                &node.get_parent_module().ok()?,
+                &node.path,
+                node.leading_colon,
+                depth + 1,
+            )?,
+        };
     }
+    Some(current)
 }
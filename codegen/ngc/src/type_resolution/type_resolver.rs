@@ -4,11 +4,15 @@ use log::{info, trace};
 use std::collections::HashMap;
 use syn::{
     Expr, ExprLit, File, FnArg, GenericArgument, Ident, Item, ItemTrait, Lit, Path, PathArguments,
-    PathSegment, ReturnType, TraitItem, TraitItemMethod, Type,
+    ReturnType, TraitItem, TraitItemMethod, Type,
 };
 
+use super::assoc_type::find_associated_type;
+use super::const_eval::ConstEvaluator;
+use super::instantiation::{self, generic_params, is_generic};
 use super::symbol_tree::*;
 use super::utils::*;
+use crate::error::{Diagnostics, Error, Result};
 
 lazy_static! {
     static ref RREF_PATH: Vec<String> = vec![
@@ -24,6 +28,14 @@ pub struct TypeResolver {
     symbol_tree: SymbolTree,
     /// The current module node that's used in recursive calls.
     current_module: Module,
+    /// The node of the trait whose methods are being resolved, which is what `Self` refers to.
+    current_trait: Option<SymbolTreeNode>,
+    /// The generic traits whose methods are resolved, with their fully-qualified paths.
+    generic_traits: Vec<(Vec<Ident>, ItemTrait)>,
+    /// The instantiations of the generic traits in the trait objects, e.g. `crate::Queue<u64>`.
+    instantiations: Vec<Path>,
+    /// Errors found so far.
+    errors: Diagnostics,
 }
 
 impl TypeResolver {
@@ -32,6 +44,10 @@ impl TypeResolver {
         Self {
             symbol_tree,
             current_module: symbol_tree_node,
+            current_trait: None,
+            generic_traits: vec![],
+            instantiations: vec![],
+            errors: Diagnostics::new(),
         }
     }
 
@@ -39,8 +55,25 @@ impl TypeResolver {
     /// The types that we are interested in right now are the types in the trait methods.
     /// If the type-in-interest contains generic, the generic will be resolved.
     /// If the generic contains a constant, the constant will be resolved to a literal.
-    pub fn resolve_types(mut self, ast: &mut File) {
+    /// Returns the instantiations of the generic traits that are used as trait objects, including
+    /// the ones used by the other instantiations.
+    pub fn resolve_types(mut self, ast: &mut File) -> std::result::Result<Vec<Path>, Diagnostics> {
         self.resolve_type_in_items(&mut ast.items);
+        self.find_nested_instantiations();
+        let instantiations = std::mem::take(&mut self.instantiations);
+        self.errors.finish(instantiations)
+    }
+
+    /// Resolves the types in the methods of the trait `tr`, which is defined in `module`, like the
+    /// ones in the interfaces, e.g. for a supertrait of an interface.
+    pub fn resolve_trait(
+        mut self,
+        module: Module,
+        tr: &mut ItemTrait,
+    ) -> std::result::Result<(), Diagnostics> {
+        self.current_module = module;
+        self.resolve_type_in_trait(tr);
+        self.errors.finish(())
     }
 
     fn resolve_type_in_items(&mut self, items: &mut [syn::Item]) {
@@ -55,35 +88,17 @@ impl TypeResolver {
                 info!("Finding `RRef`ed for module {:?}", md.ident);
                 if let Some((_, items)) = &mut md.content {
                     // Push a frame
-                    let current_node = self.current_module.borrow();
-                    let next_frame = current_node.get(&md.ident);
-                    let next_frame = crate::expect!(
-                        next_frame,
-                        "Module {:?} not found in {:#?}",
-                        md.ident,
-                        current_node
-                    );
-                    let next_frame = match &next_frame
-                        .borrow()
-                        .terminal
-                        .as_ref()
-                        .expect("Expecting a module; found unresolved.")
-                        .definition
-                    {
-                        Definition::Module(md) => md.clone(),
-                        _ => panic!("Expecting a module, not a symbol."),
+                    let next_frame = match find_child_module(&self.current_module, &md.ident) {
+                        Ok(next_frame) => next_frame,
+                        Err(err) => {
+                            self.errors.push(err);
+                            return;
+                        }
                     };
-                    drop(current_node);
-                    self.current_module = next_frame;
+                    let parent_module = std::mem::replace(&mut self.current_module, next_frame);
                     // Recurse into the new frame.
                     self.resolve_type_in_items(items);
                     // Pop a frame
-                    let parent_module = self
-                        .current_module
-                        .borrow()
-                        .node
-                        .borrow()
-                        .get_parent_module();
                     self.current_module = parent_module;
                 }
             }
@@ -110,29 +125,150 @@ impl TypeResolver {
     }
 
     fn resolve_type_in_trait(&mut self, tr: &mut ItemTrait) {
+        self.current_trait = self.current_module.borrow().get(&tr.ident).cloned();
+        // The generic parameters of the trait stay as they are until the trait is instantiated.
+        let trait_params: HashMap<Ident, GenericResult> = generic_params(&tr.generics)
+            .into_iter()
+            .map(|param| {
+                (
+                    param.clone(),
+                    GenericResult::Type(syn::parse_quote!(#param)),
+                )
+            })
+            .collect();
+        let generic_args = Some(&trait_params).filter(|params| !params.is_empty());
         for item in &mut tr.items {
             if let TraitItem::Method(method) = item {
-                self.resolve_type_in_method(method);
+                if let Err(err) = self.resolve_type_in_method(method, generic_args) {
+                    self.errors.push(err);
+                }
             }
         }
+        if is_generic(tr) {
+            let mut path = self.current_module.borrow().path.clone();
+            path.push(tr.ident.clone());
+            self.generic_traits.push((path, tr.clone()));
+        }
+        self.current_trait = None;
     }
 
-    fn resolve_type_in_method(&mut self, method: &mut TraitItemMethod) {
+    fn resolve_type_in_method(
+        &mut self,
+        method: &mut TraitItemMethod,
+        generic_args: Option<&HashMap<Ident, GenericResult>>,
+    ) -> Result<()> {
         for arg in &mut method.sig.inputs {
-            self.resolve_type_in_fnarg(arg);
+            self.resolve_type_in_fnarg(arg, generic_args)?;
         }
-        self.resolve_type_in_returntype(&mut method.sig.output);
+        self.resolve_type_in_returntype(&mut method.sig.output, generic_args)
     }
 
-    fn resolve_type_in_fnarg(&mut self, arg: &mut FnArg) {
+    fn resolve_type_in_fnarg(
+        &mut self,
+        arg: &mut FnArg,
+        generic_args: Option<&HashMap<Ident, GenericResult>>,
+    ) -> Result<()> {
         if let FnArg::Typed(ty) = arg {
-            self.resolve_type_in_type(&mut ty.ty, None);
+            self.resolve_type_in_type(&mut ty.ty, generic_args)?;
         }
+        Ok(())
     }
 
-    fn resolve_type_in_returntype(&mut self, rtn: &mut ReturnType) {
+    fn resolve_type_in_returntype(
+        &mut self,
+        rtn: &mut ReturnType,
+        generic_args: Option<&HashMap<Ident, GenericResult>>,
+    ) -> Result<()> {
         if let ReturnType::Type(_, ty) = rtn {
-            self.resolve_type_in_type(ty, None);
+            self.resolve_type_in_type(ty, generic_args)?;
+        }
+        Ok(())
+    }
+
+    /// Records the instantiation `path` of the trait `node` if the trait is generic and `path`
+    /// doesn't depend on the generic parameters in `generic_args`, e.g. `T` of `Queue<T>` in the
+    /// methods of another generic trait.
+    fn record_instantiation(
+        &mut self,
+        path: &Path,
+        node: &SymbolTreeNode,
+        generic_args: Option<&HashMap<Ident, GenericResult>>,
+    ) {
+        let is_generic_trait = match &node.borrow().terminal {
+            Some(terminal) => matches!(
+                &terminal.definition,
+                Definition::Type(Item::Trait(tr)) if is_generic(tr)
+            ),
+            None => false,
+        };
+        let params: Vec<&Ident> = generic_args
+            .into_iter()
+            .flat_map(|args| args.keys())
+            .collect();
+        let path = instantiation::without_bindings(path);
+        if is_generic_trait
+            && !instantiation::mentions(&path, &params)
+            && !self.instantiations.contains(&path)
+        {
+            self.instantiations.push(path);
+        }
+    }
+
+    /// Adds the instantiations that are used in the methods of the recorded instantiations, e.g.
+    /// `Queue<u64>` if `Pool<u64>` is used and `Pool<T>` has a method returning
+    /// `Box<dyn Queue<T>>`.
+    fn find_nested_instantiations(&mut self) {
+        let mut i = 0;
+        while i < self.instantiations.len() {
+            let path = self.instantiations[i].clone();
+            i += 1;
+            let tr = self.generic_traits.iter().find_map(|(trait_path, tr)| {
+                match path.segments.iter().map(|seg| &seg.ident).eq(trait_path) {
+                    true => Some(tr),
+                    false => None,
+                }
+            });
+            // The traits from external crates are not resolved.
+            let tr = match tr {
+                Some(tr) => tr,
+                None => continue,
+            };
+            let instance = match instantiation::instantiate(tr, &path) {
+                Ok(instance) => instance,
+                Err(err) => {
+                    self.errors.push(err);
+                    continue;
+                }
+            };
+            for item in &instance.items {
+                let sig = match item {
+                    TraitItem::Method(method) => &method.sig,
+                    _ => continue,
+                };
+                let tys = sig
+                    .inputs
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        FnArg::Typed(arg) => Some(&*arg.ty),
+                        FnArg::Receiver(_) => None,
+                    })
+                    .chain(match &sig.output {
+                        ReturnType::Type(_, ty) => Some(&**ty),
+                        ReturnType::Default => None,
+                    });
+                for ty in tys {
+                    for trait_path in instantiation::trait_objects(ty) {
+                        let idents: Vec<Ident> = trait_path
+                            .segments
+                            .iter()
+                            .map(|seg| seg.ident.clone())
+                            .collect();
+                        if let Some(node) = self.symbol_tree.lookup(&idents) {
+                            self.record_instantiation(trait_path, &node, None);
+                        }
+                    }
+                }
+            }
         }
     }
 
@@ -142,70 +278,55 @@ impl TypeResolver {
         &mut self,
         ty: &mut Type,
         generic_args: Option<&HashMap<Ident, GenericResult>>,
-    ) -> GenericResult {
+    ) -> Result<GenericResult> {
+        // Rewrite an associated type to its definition, which is resolved in the module where it
+        // is defined.
+        if let Type::Path(path) = ty {
+            if let Some(assoc) = find_associated_type(
+                &self.symbol_tree,
+                &self.current_module,
+                self.current_trait.as_ref(),
+                path,
+            )? {
+                let mut resolved_type = assoc.ty;
+                let original_module = std::mem::replace(&mut self.current_module, assoc.module);
+                let result = self.resolve_type_in_type(&mut resolved_type, None);
+                self.current_module = original_module;
+                *ty = resolved_type;
+                return result;
+            }
+        }
+
         match ty {
             Type::Array(arr) => {
                 // Resolve the type.
                 let mut resolved_type = arr.clone();
                 resolved_type.elem =
-                    box self.resolve_type_in_type(&mut arr.elem, generic_args).ty();
-
-                // Resolve the length to a literal
-                resolved_type.len = match &mut arr.len {
-                    Expr::Lit(lit) => Expr::Lit(lit.clone()),
-                    Expr::Path(path) => {
-                        let path = &mut path.path;
-                        let mut rtn = None;
-
-                        // If the path is a generic argument, return it's mapping
-                        if let Some(ident) = path.get_ident() {
-                            if let Some(generic_args) = generic_args {
-                                if let Some(generic_param) = generic_args.get(ident) {
-                                    match generic_param {
-                                        GenericResult::Type(x) => panic!(
-                                            "Expecting a generic literal, not a type. {:?}",
-                                            x
-                                        ),
-                                        GenericResult::Literal(lit) => {
-                                            rtn = Some(Expr::Lit(ExprLit {
-                                                attrs: vec![],
-                                                lit: lit.clone(),
-                                            }))
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        // Otherwise, walk the path and resolve it to a literal.
-                        if rtn.is_none() {
-                            let (path, node) = self.resolve_path(path, generic_args);
-                            let node = crate::expect!(node, "Array length experssion must not contains paths from external crates.\nType: {:?}\nForeign path: {:?}", ty, path);
-                            let lit = match &node
-                                .borrow()
-                                .terminal
-                                .as_ref()
-                                .expect("expecting a literal; found unresolved")
-                                .definition
-                            {
-                                Definition::Literal(lit) => Expr::Lit(ExprLit {
-                                    attrs: vec![],
-                                    lit: lit.clone(),
-                                }),
-                                _ => panic!(),
-                            };
-                            rtn = Some(lit);
-                        }
-
-                        rtn.unwrap()
-                    }
-                    _ => unimplemented!(),
+                    box self.resolve_type_in_type(&mut arr.elem, generic_args)?.ty();
+
+                // Evaluate the length to a literal, unless it's a const parameter of the trait.
+                let is_param = match &arr.len {
+                    Expr::Path(len) => matches!(
+                        len.path
+                            .get_ident()
+                            .and_then(|ident| generic_args?.get(ident)),
+                        Some(GenericResult::Type(_))
+                    ),
+                    _ => false,
                 };
+                if !is_param {
+                    let const_args = HashMap::new();
+                    let evaluator = ConstEvaluator::new(self.current_module.clone(), &const_args);
+                    resolved_type.len = Expr::Lit(ExprLit {
+                        attrs: vec![],
+                        lit: evaluator.eval_as(&arr.len, "usize")?,
+                    });
+                }
 
                 // Rewrite the type to the resolved one.
                 *arr = resolved_type.clone();
                 let resolved_type = Type::Array(resolved_type);
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
             Type::Path(path) => {
                 // Use the type parameter to resolve the path, if there's a matching one.
@@ -213,65 +334,66 @@ impl TypeResolver {
                     // The path is one single ident, which could be a generic argument.
                     if let Some(path) = path.path.get_ident() {
                         if let Some(resolved_type) = generic_args.get(path) {
-                            return resolved_type.clone();
+                            return Ok(resolved_type.clone());
                         }
                     }
                 }
 
                 // Resolve the path and rewrite the type to the resolved one.
                 let mut resolved_type = path.clone();
-                resolved_type.path = self.resolve_path(&mut path.path, generic_args).0;
+                resolved_type.path = self.resolve_path(&mut path.path, generic_args)?.0;
                 *path = resolved_type.clone();
                 let resolved_type = Type::Path(resolved_type);
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
             Type::Tuple(tu) => {
                 let mut resolved_type = tu.clone();
                 for elem in &mut resolved_type.elems {
-                    *elem = self.resolve_type_in_type(elem, generic_args).ty();
+                    *elem = self.resolve_type_in_type(elem, generic_args)?.ty();
                 }
                 *tu = resolved_type.clone();
                 let resolved_type = Type::Tuple(resolved_type);
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
-            Type::BareFn(x) => unimplemented!("{:#?}", x),
-            Type::Group(x) => unimplemented!("{:#?}", x),
-            Type::ImplTrait(x) => unimplemented!("{:#?}", x),
-            Type::Infer(x) => unimplemented!("{:#?}", x),
-            Type::Macro(_) => panic!("There's shouldn't be unexpended any macro at this point."),
-            Type::Never(x) => unimplemented!("{:#?}", x),
-            Type::Paren(x) => unimplemented!("{:#?}", x),
-            Type::Ptr(ptr) => {
-                GenericResult::Type(self.resolve_type_in_type(&mut ptr.elem, generic_args).ty())
-            }
-            Type::Reference(reference) => GenericResult::Type(
-                self.resolve_type_in_type(&mut reference.elem, generic_args)
+            Type::Macro(x) => Err(Error::new_spanned(
+                x,
+                "Macros in types must be expanded before running ngc",
+            )),
+            Type::Ptr(ptr) => Ok(GenericResult::Type(
+                self.resolve_type_in_type(&mut ptr.elem, generic_args)?
                     .ty(),
-            ),
+            )),
+            Type::Reference(reference) => Ok(GenericResult::Type(
+                self.resolve_type_in_type(&mut reference.elem, generic_args)?
+                    .ty(),
+            )),
             Type::Slice(slice) => {
                 let mut resolved_type = slice.clone();
                 *resolved_type.elem = self
-                    .resolve_type_in_type(&mut resolved_type.elem, generic_args)
+                    .resolve_type_in_type(&mut resolved_type.elem, generic_args)?
                     .ty();
                 let resolved_type = Type::Slice(resolved_type);
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
             Type::TraitObject(tr) => {
                 let mut resolved_type = tr.clone();
                 for bound in resolved_type.bounds.iter_mut() {
                     match bound {
                         syn::TypeParamBound::Trait(tr) => {
-                            tr.path = self.resolve_path(&mut tr.path, generic_args).0;
+                            let (path, node) = self.resolve_path(&mut tr.path, generic_args)?;
+                            if let Some(node) = node {
+                                self.record_instantiation(&path, &node, generic_args);
+                            }
+                            tr.path = path;
                         }
                         syn::TypeParamBound::Lifetime(_) => {}
                     }
                 }
                 *tr = resolved_type.clone();
                 let resolved_type = Type::TraitObject(resolved_type);
-                GenericResult::Type(resolved_type)
+                Ok(GenericResult::Type(resolved_type))
             }
-            Type::Verbatim(x) => unimplemented!("{:#?}", x),
-            Type::__Nonexhaustive => unimplemented!(),
+            ty => Err(unsupported_type(ty)),
         }
     }
 
@@ -285,125 +407,98 @@ impl TypeResolver {
         &mut self,
         path: &mut Path,
         generic_args: Option<&HashMap<Ident, GenericResult>>,
-    ) -> (Path, Option<SymbolTreeNode>) {
+    ) -> Result<(Path, Option<SymbolTreeNode>)> {
         trace!(
             "Resolving path {:?} with generic_args {:?}",
             path,
             generic_args
         );
-        let mut current_node = self.current_module.clone();
-        let mut path_segments: Vec<PathSegment> = path.segments.iter().cloned().collect();
+        let current_node = self.current_module.clone();
 
         // If the path starts with `::` and doesn't come from `crate` or `super, or it comes from
         // some unknown module(external module), we know that it's already fully qualified.
         if path.leading_colon.is_some()
             && PATH_MODIFIERS.contains(&path.segments.first().unwrap().ident.to_string())
-            || current_node.borrow().get(&path_segments[0].ident).is_none()
+            || current_node
+                .borrow()
+                .get(&path.segments.first().unwrap().ident)
+                .is_none()
         {
-            return (path.clone(), None);
+            // The generic arguments may still come from the IDL, e.g. `dyn Foo` of `Box<dyn Foo>`.
+            let final_segment = path.segments.last_mut().unwrap();
+            self.resolve_path_arguments(&mut final_segment.arguments, generic_args, &[])?;
+            return Ok((path.clone(), None));
         }
 
         // Walk the module tree and resolve the type.
-        let mut final_segment = path_segments.remove(path_segments.len() - 1);
-        for path_segment in path_segments {
-            if path_segment.arguments != PathArguments::None {
-                panic!("Path arguments(e.g. generics) is not supported in the inner path segments.\nViolating path: {:?}\nPath segment: {:?}", path, path_segment);
-            }
-
-            let current_node_ref = current_node.borrow();
-            let next_node = current_node_ref.get(&path_segment.ident);
-            let next_node = crate::expect!(
-                next_node,
-                "Unable to find {:?} in {:#?}",
-                path_segment.ident,
-                current_node
-            )
-            .clone();
-            drop(current_node_ref);
-            let next_node = next_node.borrow();
-            current_node = match &next_node
-                .terminal
-                .as_ref()
-                .expect("Expecting module, found unresolved")
-                .definition
-            {
-                Definition::Module(md) => {
-                    assert!(next_node.public);
-                    md.clone()
-                }
-                _ => panic!(
-                    "Resolving {:#?} for {:#?}. Node {:#?} is a symbol and cannot have child.",
-                    path_segment,
-                    current_node.borrow().node.borrow().path,
-                    next_node
-                ),
-            };
-        }
-
-        let current_node_ref = current_node.borrow();
-        let final_node = current_node_ref.get(&final_segment.ident);
-        let final_node = crate::expect!(
-            final_node,
-            "Unable to find {:?} in {:#?}",
-            final_segment.ident,
-            current_node_ref
-        )
-        .clone();
-        drop(current_node_ref);
-        let final_node_ref = final_node.borrow();
-        let mut resolved_path = match &final_node_ref.terminal.as_ref().unwrap().definition {
-            Definition::Module(md) => panic!("Expecting a type, but found a module. {:?}", md),
-            _ => idents_to_path(&final_node_ref.path),
-        };
-        drop(final_node_ref);
+        let final_node = walk_path(current_node, path)?;
+        let mut resolved_path = idents_to_path(&final_node.borrow().path);
+        let mut final_segment = path.segments.last().unwrap().clone();
 
         // Resolve the generic arguments and rewrite the AST.
-        self.resolve_path_arguments(&mut final_segment.arguments, generic_args);
-        *resolved_path.segments.last_mut().unwrap() = final_segment;
+        let const_types = const_param_types(&final_node);
+        self.resolve_path_arguments(&mut final_segment.arguments, generic_args, &const_types)?;
+        // Keep the resolved ident in case the type is imported under another name.
+        resolved_path.segments.last_mut().unwrap().arguments = final_segment.arguments;
 
         trace!("Path {:?} is resolved to {:?}.", path, resolved_path);
         *path = resolved_path.clone();
-        (resolved_path, Some(final_node.clone()))
+        Ok((resolved_path, Some(final_node)))
     }
 
     /// Resolve any types or constants in the path argument and returns the resolved path arguments back.
+    /// `const_types` are the declared types of the const generic parameters.
     fn resolve_path_arguments(
         &mut self,
         arguments: &mut PathArguments,
         generic_args: Option<&HashMap<Ident, GenericResult>>,
-    ) {
+        const_types: &[Option<String>],
+    ) -> Result<()> {
         let mut resolved_arguments = arguments.clone();
+        let const_args = HashMap::new();
+        let evaluator = ConstEvaluator::new(self.current_module.clone(), &const_args);
         if let PathArguments::AngleBracketed(generic) = &mut resolved_arguments {
-            for arg in generic.args.iter_mut() {
+            // Lifetimes are kept as they are and don't count in the positions of `const_types`.
+            let args = generic
+                .args
+                .iter_mut()
+                .filter(|arg| !matches!(arg, GenericArgument::Lifetime(_)));
+            for (i, arg) in args.enumerate() {
                 trace!("Resolving generic argument {:?}", arg);
                 let resolved_arg: Option<GenericArgument> = match arg {
-                    syn::GenericArgument::Lifetime(_) => {
-                        /* noop */
-                        None
+                    syn::GenericArgument::Lifetime(_) => unreachable!(),
+                    // A constant without braces, e.g. `QUEUE` of `RRefDeque<BlkReq, QUEUE>`.
+                    syn::GenericArgument::Type(Type::Path(ty))
+                        if ty.qself.is_none() && evaluator.is_constant(&ty.path) =>
+                    {
+                        let expr: Expr = syn::parse_quote!(#ty);
+                        Some(GenericArgument::Const(Expr::Lit(ExprLit {
+                            attrs: vec![],
+                            lit: eval_const_arg(&evaluator, &expr, const_types.get(i))?,
+                        })))
                     }
                     syn::GenericArgument::Type(ty) => {
                         // It is possible that `ty` is resolved into a constant literal.
-                        Some(self.resolve_type_in_type(ty, generic_args).into())
+                        Some(self.resolve_type_in_type(ty, generic_args)?.into())
+                    }
+                    // An associated type of a trait object, e.g. `Buf` of `dyn Disk<Buf = Block>`.
+                    syn::GenericArgument::Binding(binding) => {
+                        let mut resolved_binding = binding.clone();
+                        resolved_binding.ty = self
+                            .resolve_type_in_type(&mut binding.ty, generic_args)?
+                            .ty();
+                        Some(GenericArgument::Binding(resolved_binding))
+                    }
+                    syn::GenericArgument::Constraint(x) => {
+                        return Err(Error::new_spanned(
+                            x,
+                            "Trait constraints are not supported in generic arguments",
+                        ))
                     }
-                    syn::GenericArgument::Binding(x) => unimplemented!("{:#?}", x),
-                    syn::GenericArgument::Constraint(x) => unimplemented!("{:#?}", x),
                     syn::GenericArgument::Const(expr) => {
-                        let lit = match expr {
-                            syn::Expr::Lit(lit) => lit.lit.clone(),
-                            syn::Expr::Path(path) => {
-                                let (path, node) = self.resolve_path(&mut path.path, generic_args);
-                                let node = crate::expect!(node, "Generic experssion must not contains paths from external crates.\nPath arguments: {:?}\nForeign path: {:?}", arguments, path);
-                                let lit = match &node.borrow().terminal.as_ref().unwrap().definition {
-                                    Definition::Literal(lit) => lit.clone(),
-                                    _ => panic!("All generic constants must be able to resolved to a compile time constant.\nPath: {:?}\nNode: {:?}", path, node.borrow()),
-                                };
-                                lit
-                            }
-                            _ => unimplemented!(),
-                        };
                         Some(GenericArgument::Const(Expr::Lit(ExprLit {
                             attrs: vec![],
-                            lit,
+                            lit: eval_const_arg(&evaluator, expr, const_types.get(i))?,
                         })))
                     }
                 };
@@ -414,30 +509,21 @@ impl TypeResolver {
             }
         }
         *arguments = resolved_arguments;
+        Ok(())
     }
 }
 
-/// An enum represents what a generic argument can resolved into.
-/// In this compiler, a generic argument can be resolved into a type or a constant literal.
+/// What a generic argument is resolved into. The const parameters of a trait are kept as they
+/// are, so a generic argument is always a type here.
 #[derive(Debug, Clone)]
 enum GenericResult {
     Type(Type),
-    Literal(Lit),
 }
 
-#[allow(dead_code)]
 impl GenericResult {
     fn ty(self) -> Type {
         match self {
             GenericResult::Type(ty) => ty,
-            GenericResult::Literal(lit) => panic!("Expecting a type, but found {:#?}", lit),
-        }
-    }
-
-    fn lit(self) -> Lit {
-        match self {
-            GenericResult::Literal(lit) => lit,
-            GenericResult::Type(ty) => panic!("Expecting a literal, but found {:#?}", ty),
         }
     }
 }
@@ -446,9 +532,18 @@ impl Into<GenericArgument> for GenericResult {
     fn into(self) -> GenericArgument {
         match self {
             GenericResult::Type(ty) => GenericArgument::Type(ty),
-            GenericResult::Literal(lit) => {
-                GenericArgument::Const(Expr::Lit(ExprLit { attrs: vec![], lit }))
-            }
         }
     }
 }
+
+/// Evaluates the const generic argument `expr` as the declared type of its parameter, if known.
+fn eval_const_arg(
+    evaluator: &ConstEvaluator,
+    expr: &Expr,
+    ty: Option<&Option<String>>,
+) -> Result<Lit> {
+    match ty {
+        Some(Some(ty)) => evaluator.eval_as(expr, ty),
+        _ => evaluator.eval(expr),
+    }
+}
@@ -1,6 +1,13 @@
 use std::iter::FromIterator;
 
-use syn::{punctuated::Punctuated, Ident, Path, PathArguments, PathSegment, Visibility};
+use quote::ToTokens;
+use syn::{
+    punctuated::Punctuated, GenericParam, Ident, Item, Path, PathArguments, PathSegment, Type,
+    Visibility,
+};
+
+use super::symbol_tree::{Definition, Module, SymbolTreeNode, SymbolTreeNodeInner, Terminal};
+use crate::error::{Error, Result};
 
 /// Return truf if the visibility is private.
 pub fn is_prviate(vis: &Visibility) -> bool {
@@ -23,3 +30,170 @@ pub fn idents_to_path(path_segments: &[Ident]) -> Path {
         segments,
     }
 }
+
+/// Formats a path, e.g. `crate::foo::Bar`.
+pub fn path_to_string(path_segments: &[Ident]) -> String {
+    path_segments
+        .iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<String>>()
+        .join("::")
+}
+
+/// Walks `path` from `module` and returns the node of the type or constant it points to.
+/// Generic arguments in the final segment are ignored.
+pub fn walk_path(mut module: Module, path: &Path) -> Result<SymbolTreeNode> {
+    let (final_segment, path_segments) = path
+        .segments
+        .iter()
+        .collect::<Vec<_>>()
+        .split_last()
+        .map(|(last, rest)| (*last, rest.to_vec()))
+        .ok_or_else(|| Error::new_spanned(path, "Expecting a non-empty path"))?;
+    for path_segment in path_segments {
+        if path_segment.arguments != PathArguments::None {
+            return Err(Error::new_spanned(
+                path_segment,
+                "Path arguments(e.g. generics) are not supported in the inner path segments",
+            ));
+        }
+
+        let next_node = find_in_module(&module, path, &path_segment.ident)?;
+        let next_node = next_node.borrow();
+        module = match &terminal_of(&next_node, &path_segment.ident)?.definition {
+            Definition::Module(md) if next_node.public => md.clone(),
+            Definition::Module(_) => {
+                return Err(Error::new_spanned(
+                    &path_segment.ident,
+                    format!("Module `{}` is private", path_to_string(&next_node.path)),
+                ))
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    &path_segment.ident,
+                    format!("`{}` is not a module", path_to_string(&next_node.path)),
+                ))
+            }
+        };
+    }
+
+    let final_node = find_in_module(&module, path, &final_segment.ident)?;
+    if let Definition::Module(_) =
+        &terminal_of(&final_node.borrow(), &final_segment.ident)?.definition
+    {
+        return Err(Error::new_spanned(
+            &final_segment.ident,
+            format!(
+                "Expecting a type, but `{}` is a module",
+                path_to_string(&final_node.borrow().path)
+            ),
+        ));
+    }
+    Ok(final_node)
+}
+
+/// Returns the module `ident` declared in `module`, which is the frame of an inline module.
+pub fn find_child_module(module: &Module, ident: &Ident) -> Result<Module> {
+    let node = module.borrow().get(ident).cloned().ok_or_else(|| {
+        Error::new_spanned(
+            ident,
+            format!(
+                "Module `{}` is not found in `{}`",
+                ident,
+                path_to_string(&module.borrow().path)
+            ),
+        )
+    })?;
+    let node = node.borrow();
+    match &terminal_of(&node, ident)?.definition {
+        Definition::Module(md) => Ok(md.clone()),
+        _ => Err(Error::new_spanned(
+            ident,
+            format!("`{}` is not a module", path_to_string(&node.path)),
+        )),
+    }
+}
+
+/// Returns what `node`, named by `ident` in the input, resolves to.
+fn terminal_of<'a>(node: &'a SymbolTreeNodeInner, ident: &Ident) -> Result<&'a Terminal> {
+    node.terminal.as_ref().ok_or_else(|| {
+        Error::new_spanned(
+            ident,
+            format!("`{}` is not resolved", path_to_string(&node.path)),
+        )
+    })
+}
+
+/// Looks up `ident` of `path` in `module`.
+fn find_in_module(module: &Module, path: &Path, ident: &Ident) -> Result<SymbolTreeNode> {
+    let module = module.borrow();
+    let node = module.get(ident).cloned().ok_or_else(|| {
+        Error::new_spanned(
+            ident,
+            format!(
+                "Unable to resolve `{}`: `{}` is not found in `{}`",
+                path_to_string(
+                    &path
+                        .segments
+                        .iter()
+                        .map(|seg| seg.ident.clone())
+                        .collect::<Vec<_>>()
+                ),
+                ident,
+                path_to_string(&module.path)
+            ),
+        )
+    })?;
+    node.borrow().check_ambiguous(ident)?;
+    Ok(node)
+}
+
+/// Returns the declared types of the const generic parameters of the item `node` points to, e.g.
+/// `usize` for `N` of `RRefArray<T, const N: usize>`, by position. `None` for the type parameters.
+/// Lifetime parameters are skipped, since lifetime arguments may be elided.
+pub fn const_param_types(node: &SymbolTreeNode) -> Vec<Option<String>> {
+    generic_params(node)
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Const(param) => Some(Some(param.ty.to_token_stream().to_string())),
+            GenericParam::Type(_) => Some(None),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect()
+}
+
+/// Returns the number of lifetime parameters of the item `node` points to.
+pub fn lifetime_param_count(node: &SymbolTreeNode) -> usize {
+    generic_params(node)
+        .iter()
+        .filter(|param| matches!(param, GenericParam::Lifetime(_)))
+        .count()
+}
+
+/// Returns the generic parameters of the item `node` points to. Empty if it's not a generic item.
+fn generic_params(node: &SymbolTreeNode) -> Vec<GenericParam> {
+    let node = node.borrow();
+    let generics = match node.terminal.as_ref().map(|terminal| &terminal.definition) {
+        Some(Definition::Type(Item::Struct(item))) => &item.generics,
+        Some(Definition::Type(Item::Enum(item))) => &item.generics,
+        Some(Definition::Type(Item::Union(item))) => &item.generics,
+        Some(Definition::Type(Item::Type(item))) => &item.generics,
+        Some(Definition::Type(Item::Trait(item))) => &item.generics,
+        _ => return vec![],
+    };
+    generics.params.iter().cloned().collect()
+}
+
+/// Returns the error for a type that's not supported in interfaces.
+pub fn unsupported_type(ty: &Type) -> Error {
+    let kind = match ty {
+        Type::BareFn(_) => "Function pointer",
+        Type::Group(_) => "Invisible group",
+        Type::ImplTrait(_) => "`impl Trait`",
+        Type::Infer(_) => "Inferred",
+        Type::Never(_) => "Never",
+        Type::Paren(_) => "Parenthesized",
+        _ => "This",
+    };
+    Error::new_spanned(ty, format!("{} type is not supported in interfaces", kind))
+}
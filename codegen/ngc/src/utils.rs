@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use syn::{FnArg, NestedMeta};
 
+use crate::error::{Error, Result};
+
 #[macro_export]
 macro_rules! has_attribute {
     ($item: ident, $attr: ident) => {{
@@ -56,54 +58,51 @@ macro_rules! for_enums_add_attribute {
     };
 }
 
-/// A faster alternative for `result.expect(fmt, args)`
-/// `Result::expect` will run the formatter regardless of the result.
-/// This macros allows us to not run the formatter when the result is a `Some`.
-#[macro_export]
-macro_rules! expect {
-    ($result:expr, $fmt:expr, $($args:tt)*) => {
-        match $result {
-            Some(result) => result,
-            None => panic!($fmt, $($args)*),
-        }
-    };
-}
-
-pub fn create_attribue_map(attrs: &Vec<syn::Attribute>) -> HashMap<String, Option<syn::Lit>> {
+pub fn create_attribue_map(
+    attrs: &Vec<syn::Attribute>,
+) -> Result<HashMap<String, Option<syn::Lit>>> {
     let mut map = HashMap::new();
     for attr in attrs {
-        map.extend(create_attribue_map_from_meta(&attr.parse_meta().unwrap()))
+        map.extend(create_attribue_map_from_meta(&attr.parse_meta()?)?)
     }
-    map
+    Ok(map)
 }
 
-fn create_attribue_map_from_meta(meta: &syn::Meta) -> HashMap<String, Option<syn::Lit>> {
+fn create_attribue_map_from_meta(meta: &syn::Meta) -> Result<HashMap<String, Option<syn::Lit>>> {
     let mut map = HashMap::new();
     match meta {
         syn::Meta::List(lst) => {
             for meta in &lst.nested {
                 match meta {
-                    NestedMeta::Meta(meta) => map.extend(create_attribue_map_from_meta(meta)),
+                    NestedMeta::Meta(meta) => map.extend(create_attribue_map_from_meta(meta)?),
                     NestedMeta::Lit(lit) => {
                         match lit {
                             syn::Lit::Str(str) => map.insert(str.value(), None),
-                            _ => unimplemented!("{:#?}", lit),
+                            _ => {
+                                return Err(Error::new_spanned(
+                                    lit,
+                                    "Expecting a string or a `key = value` in the attribute",
+                                ))
+                            }
                         };
                     }
                 };
             }
         }
         syn::Meta::NameValue(kv) => {
-            map.insert(
-                kv.path.get_ident().unwrap().to_string(),
-                Some(kv.lit.clone()),
-            );
+            map.insert(attribute_key(&kv.path)?, Some(kv.lit.clone()));
         }
         syn::Meta::Path(path) => {
-            map.insert(path.get_ident().unwrap().to_string(), None);
+            map.insert(attribute_key(path)?, None);
         }
     }
-    map
+    Ok(map)
+}
+
+fn attribute_key(path: &syn::Path) -> Result<String> {
+    path.get_ident()
+        .map(|ident| ident.to_string())
+        .ok_or_else(|| Error::new_spanned(path, "Expecting an identifier in the attribute"))
 }
 
 // Remove `self` from the argument list.
@@ -115,24 +114,23 @@ pub fn get_selfless_args<'a, T: Iterator<Item = &'a FnArg>>(args: T) -> Vec<&'a
     .collect()
 }
 
-// Get `T` from `Boxed<T>`. Panic if it's not a box.
-pub fn get_type_inside_of_box(ty: &syn::Type) -> &syn::Type {
+// Get `T` from `Boxed<T>`.
+pub fn get_type_inside_of_box(ty: &syn::Type) -> Result<&syn::Type> {
+    let expecting_box = || Error::new_spanned(ty, "Expecting a `Box<T>`");
     match ty {
         syn::Type::Path(path) => {
             // TODO: check that this is actually a box.
-            let last_segement = path.path.segments.iter().last().unwrap();
+            let last_segement = path.path.segments.iter().last().ok_or_else(expecting_box)?;
             match &last_segement.arguments {
-                syn::PathArguments::AngleBracketed(args) => {
-                    assert_eq!(args.args.len(), 1);
-                    let arg = args.args.first().unwrap();
-                    match arg {
-                        syn::GenericArgument::Type(ty) => ty,
-                        _ => panic!("Expecting a type in the box but get: {:#?}", arg),
+                syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                    match args.args.first() {
+                        Some(syn::GenericArgument::Type(ty)) => Ok(ty),
+                        _ => Err(Error::new_spanned(args, "Expecting a type in the box")),
                     }
                 }
-                _ => panic!("Expecting Boxed<T> but get: {:#?}", path),
+                _ => Err(expecting_box()),
             }
         }
-        _ => panic!("Expecting a box but found: {:#?}", ty),
+        _ => Err(expecting_box()),
     }
 }
@@ -0,0 +1,467 @@
+//! Validation of the types that cross domains, following the rules in `idlc/design.md`.
+//!
+//! The arguments and return values of the interface methods are either `RRef`s, which are moved
+//! to the callee, `&RRef`s, which are borrowed, interface trait objects, which are proxied, or
+//! `SafeCopy` values, which are copied bit by bit. `SafeCopy` types are the `Copy` types without
+//! references, raw pointers and function pointers, which would point to the private memory of a
+//! domain. The values on the shared heap must be `SafeCopy` as well, except that structs, enums and
+//! unions there may also hold `Option<RRef>`s and `&RRef`s, which make up the `RRef`-trees.
+//!
+//! The types from external crates without sources can't be inspected, so they are trusted.
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashSet;
+use std::fmt;
+
+use quote::{quote, ToTokens};
+use syn::{
+    FnArg, GenericArgument, Ident, Item, ItemTrait, Pat, PathArguments, ReturnType, TraitItem,
+    Type, TypePath, TypeReference,
+};
+
+use crate::config::Config;
+use crate::error::{Diagnostics, Error};
+use crate::has_attribute;
+use crate::type_resolution::instantiation::{instances, is_generic};
+use crate::type_resolution::rref_kind::RRefKind;
+use crate::type_resolution::rrefed_finder::{Fields, RRefedFinder};
+use crate::type_resolution::supertraits;
+use crate::type_resolution::symbol_tree::{Definition, Module, SymbolTree};
+use crate::type_resolution::type_id::canonical_name;
+
+/// Checks that the arguments and the return values of all the interface methods in `ast` can cross
+/// domains. The types in `ast` must already be resolved by the `TypeResolver`.
+pub fn validate_interfaces(
+    ast: &syn::File,
+    symbol_tree: &SymbolTree,
+    config: &Config,
+) -> Result<(), Diagnostics> {
+    let mut validator = Validator {
+        finder: RRefedFinder::new(symbol_tree.clone()),
+        symbol_tree,
+        config,
+        errors: Diagnostics::new(),
+    };
+    validator.validate_items(&ast.items, symbol_tree.root_module());
+    validator.errors.finish(())
+}
+
+/// Why a type can't cross domains.
+struct Violation {
+    /// The canonical name of the type that breaks the rule.
+    ty: String,
+    /// The rule that it breaks, e.g. "is a raw pointer, which is not `SafeCopy`".
+    reason: String,
+    /// Where the type is, from the innermost field, e.g. "field `ptr` of `crate::Foo`".
+    trail: Vec<String>,
+}
+
+impl Violation {
+    fn new<R: fmt::Display>(ty: &Type, reason: R) -> Self {
+        Self {
+            ty: canonical_name(ty),
+            reason: reason.to_string(),
+            trail: vec![],
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` {}", self.ty, self.reason)?;
+        for place in &self.trail {
+            write!(f, ", in {}", place)?;
+        }
+        Ok(())
+    }
+}
+
+type Validation = std::result::Result<(), Violation>;
+
+/// How a struct, an enum or a union is checked.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Rule {
+    SafeCopy,
+    /// The value is on the shared heap.
+    RRefed,
+}
+
+struct Validator<'a> {
+    /// Resolves the types to their canonical types and the fields of the composite types.
+    finder: RRefedFinder,
+    symbol_tree: &'a SymbolTree,
+    config: &'a Config,
+    errors: Diagnostics,
+}
+
+impl Validator<'_> {
+    fn validate_items(&mut self, items: &[Item], module: Module) {
+        for item in items {
+            match item {
+                Item::Mod(md) => {
+                    if let Some((_, items)) = &md.content {
+                        let child = module.borrow().get(&md.ident).and_then(|node| {
+                            match &node.borrow().terminal.as_ref()?.definition {
+                                Definition::Module(child) => Some(child.clone()),
+                                _ => None,
+                            }
+                        });
+                        if let Some(child) = child {
+                            self.validate_items(items, child);
+                        }
+                    }
+                }
+                Item::Trait(tr) => {
+                    let interface_attr = &self.config.attributes.interface;
+                    if has_attribute!(tr, interface_attr) {
+                        self.validate_interface(tr, &module);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Checks the methods of the interface `tr`, or of its instantiations if it's generic, and of
+    /// its supertraits. The supertraits that are interfaces themselves are checked on their own.
+    fn validate_interface(&mut self, tr: &ItemTrait, module: &Module) {
+        // The types in a generic interface are known in its instantiations only.
+        if is_generic(tr) {
+            match instances(self.symbol_tree, module, tr) {
+                Ok(instances) => {
+                    for (_, instance) in instances {
+                        self.validate_methods(&instance, module);
+                    }
+                }
+                Err(err) => self.errors.push(err),
+            }
+        } else {
+            self.validate_methods(tr, module);
+        }
+        let supertraits = match supertraits::find_supertraits(module, tr) {
+            Ok(supertraits) => supertraits,
+            Err(err) => return self.errors.push(err),
+        };
+        let interface_attr = &self.config.attributes.interface;
+        for supertrait in supertraits {
+            let definition = &supertrait.definition;
+            if !has_attribute!(definition, interface_attr) {
+                self.validate_methods(definition, &supertrait.module);
+            }
+        }
+    }
+
+    fn validate_methods(&mut self, tr: &ItemTrait, module: &Module) {
+        for item in &tr.items {
+            let method = match item {
+                TraitItem::Method(method) => method,
+                _ => continue,
+            };
+            for arg in &method.sig.inputs {
+                if let FnArg::Typed(arg) = arg {
+                    let subject = match arg.pat.as_ref() {
+                        Pat::Ident(pat) => format!("Argument `{}`", pat.ident),
+                        _ => String::from("Argument"),
+                    };
+                    self.validate_type(&arg.pat, &subject, &arg.ty, module);
+                }
+            }
+            if let ReturnType::Type(arrow, ty) = &method.sig.output {
+                let subject = format!("Return value of `{}`", method.sig.ident);
+                self.validate_type(arrow, &subject, ty, module);
+            }
+        }
+    }
+
+    /// Checks the type `ty` of an argument or a return value, and reports the violation at
+    /// `tokens`.
+    fn validate_type<T: ToTokens>(&mut self, tokens: T, subject: &str, ty: &Type, module: &Module) {
+        let ty = match self.finder.resolve_type(module.clone(), ty) {
+            Ok(ty) => ty,
+            Err(err) => return self.errors.push(err),
+        };
+        if let Err(violation) = self.check_transferable(&ty) {
+            self.errors.push(Error::new_spanned(
+                tokens,
+                format!("{} can't cross domains: {}", subject, violation),
+            ));
+        }
+    }
+
+    /// Checks a type that is passed in an argument or a return value.
+    fn check_transferable(&self, ty: &Type) -> Validation {
+        if let Some(content) = rrefed_content(ty) {
+            return self.check_rrefed(content, &mut HashSet::new());
+        }
+
+        match ty {
+            // `RRef`s are borrowed by incrementing their borrow count.
+            Type::Reference(reference) if RRefKind::of_type(&reference.elem).is_some() => {
+                self.check_borrowed_rref(reference, &mut HashSet::new())
+            }
+            // Only the boxed trait objects are replaced with their proxies.
+            Type::Reference(reference) if matches!(*reference.elem, Type::TraitObject(_)) => {
+                Err(Violation::new(
+                    ty,
+                    "is a borrowed trait object, which is not replaced with a proxy; pass a `Box<dyn _>` instead",
+                ))
+            }
+            Type::Path(path) if self.fields(ty).is_none() => match wrapper(path)
+                .as_ref()
+                .map(|(name, args)| (*name, &args[..]))
+            {
+                Some(("Box", [trait_object @ Type::TraitObject(_)])) => {
+                    self.check_interface(ty, trait_object)
+                }
+                Some(("Option", args)) | Some(("Result", args)) => {
+                    args.iter().try_for_each(|arg| self.check_transferable(arg))
+                }
+                _ => self.check_safe_copy(ty, &mut HashSet::new()),
+            },
+            Type::Tuple(tuple) => tuple
+                .elems
+                .iter()
+                .try_for_each(|elem| self.check_transferable(elem)),
+            Type::Array(array) => self.check_transferable(&array.elem),
+            Type::Paren(paren) => self.check_transferable(&paren.elem),
+            _ => self.check_safe_copy(ty, &mut HashSet::new()),
+        }
+    }
+
+    /// Checks a type whose values are copied bit by bit.
+    fn check_safe_copy(&self, ty: &Type, visited: &mut HashSet<(Type, Rule)>) -> Validation {
+        if RRefKind::of_type(ty).is_some() {
+            return Err(Violation::new(
+                ty,
+                "is an `RRef`, which can only be passed in arguments, return values, and the structs, enums and unions on the shared heap",
+            ));
+        }
+
+        match ty {
+            Type::Path(path) => {
+                if self.fields(ty).is_some() {
+                    return self.check_fields(ty, Rule::SafeCopy, visited);
+                }
+                match wrapper(path)
+                    .as_ref()
+                    .map(|(name, args)| (*name, &args[..]))
+                {
+                    Some(("Box", _)) => Err(Violation::new(
+                        ty,
+                        "points to the private heap, which is not `SafeCopy`",
+                    )),
+                    Some(("Option", args)) | Some(("Result", args)) => args
+                        .iter()
+                        .try_for_each(|arg| self.check_safe_copy(arg, visited)),
+                    // Builtin types and types from the external crates without sources.
+                    _ => Ok(()),
+                }
+            }
+            Type::Tuple(tuple) => tuple
+                .elems
+                .iter()
+                .try_for_each(|elem| self.check_safe_copy(elem, visited)),
+            Type::Array(array) => self.check_safe_copy(&array.elem, visited),
+            Type::Paren(paren) => self.check_safe_copy(&paren.elem, visited),
+            Type::Reference(_) => Err(Violation::new(
+                ty,
+                "is a reference, which is not `SafeCopy`",
+            )),
+            Type::Ptr(_) => Err(Violation::new(
+                ty,
+                "is a raw pointer, which is not `SafeCopy`",
+            )),
+            Type::BareFn(_) => Err(Violation::new(
+                ty,
+                "is a function pointer, which is not `SafeCopy`",
+            )),
+            Type::TraitObject(_) => Err(Violation::new(
+                ty,
+                "is a trait object, which is not `SafeCopy`",
+            )),
+            Type::Slice(_) => Err(Violation::new(ty, "is a slice, which is not `SafeCopy`")),
+            _ => Err(Violation::new(ty, "is not `SafeCopy`")),
+        }
+    }
+
+    /// Checks the content of an `RRef`.
+    fn check_rrefed(&self, ty: &Type, visited: &mut HashSet<(Type, Rule)>) -> Validation {
+        match self.fields(ty) {
+            Some(_) => self.check_fields(ty, Rule::RRefed, visited),
+            None => self.check_safe_copy(ty, visited),
+        }
+    }
+
+    /// Checks a field of a struct, an enum or a union on the shared heap.
+    fn check_rrefed_field(&self, ty: &Type, visited: &mut HashSet<(Type, Rule)>) -> Validation {
+        if RRefKind::of_type(ty).is_some() {
+            return Err(Violation::new(
+                ty,
+                "is an `RRef` on the shared heap, which must be in an `Option` or borrowed",
+            ));
+        }
+
+        match ty {
+            Type::Reference(reference) if RRefKind::of_type(&reference.elem).is_some() => {
+                self.check_borrowed_rref(reference, visited)
+            }
+            Type::Path(_) if self.fields(ty).is_some() => {
+                self.check_fields(ty, Rule::RRefed, visited)
+            }
+            Type::Path(path) => match wrapper(path)
+                .as_ref()
+                .map(|(name, args)| (*name, &args[..]))
+            {
+                Some(("Option", [inner])) if RRefKind::of_type(inner).is_some() => {
+                    self.check_rrefed(rrefed_content(inner).unwrap(), visited)
+                }
+                _ => self.check_safe_copy(ty, visited),
+            },
+            Type::Tuple(tuple) => tuple
+                .elems
+                .iter()
+                .try_for_each(|elem| self.check_rrefed_field(elem, visited)),
+            Type::Array(array) => self.check_rrefed_field(&array.elem, visited),
+            Type::Paren(paren) => self.check_rrefed_field(&paren.elem, visited),
+            _ => self.check_safe_copy(ty, visited),
+        }
+    }
+
+    /// Checks a borrowed `RRef`, `RRefArray` or `RRefDeque`.
+    fn check_borrowed_rref(
+        &self,
+        reference: &TypeReference,
+        visited: &mut HashSet<(Type, Rule)>,
+    ) -> Validation {
+        if reference.mutability.is_some() {
+            return Err(Violation::new(
+                &Type::Reference(reference.clone()),
+                "is a mutable borrow of an `RRef`, which can only be borrowed immutably",
+            ));
+        }
+        self.check_rrefed(rrefed_content(&reference.elem).unwrap(), visited)
+    }
+
+    /// Checks the fields of the struct, enum or union `ty` by `rule`.
+    fn check_fields(
+        &self,
+        ty: &Type,
+        rule: Rule,
+        visited: &mut HashSet<(Type, Rule)>,
+    ) -> Validation {
+        // Recursive types are fine as long as the rest of the fields are.
+        if !visited.insert((ty.clone(), rule)) {
+            return Ok(());
+        }
+
+        let fields: Vec<(String, &Type)> = match self.fields(ty).unwrap() {
+            Fields::Struct(fields) | Fields::Union(fields) => fields
+                .iter()
+                .map(|(member, field_ty)| (quote!(#member).to_string(), field_ty))
+                .collect(),
+            Fields::Enum(variants) => variants
+                .iter()
+                .flat_map(|(variant, fields)| {
+                    fields.iter().map(move |(member, field_ty)| {
+                        (format!("{}::{}", variant, quote!(#member)), field_ty)
+                    })
+                })
+                .collect(),
+        };
+        for (member, field_ty) in fields {
+            let result = match rule {
+                Rule::SafeCopy => self.check_safe_copy(field_ty, visited),
+                Rule::RRefed => self.check_rrefed_field(field_ty, visited),
+            };
+            result.map_err(|mut violation| {
+                violation
+                    .trail
+                    .push(format!("field `{}` of `{}`", member, canonical_name(ty)));
+                violation
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the fields of `ty` if it's a struct, an enum or a union in the IDL.
+    fn fields(&self, ty: &Type) -> Option<&Fields> {
+        self.finder.fields(ty)
+    }
+
+    /// Checks that the trait object `trait_object`, which is passed as `ty`, is of an interface.
+    fn check_interface(&self, ty: &Type, trait_object: &Type) -> Validation {
+        let trait_object = match trait_object {
+            Type::TraitObject(trait_object) => trait_object,
+            _ => unreachable!(),
+        };
+        let interface_attr = &self.config.attributes.interface;
+        let is_interface = trait_object.bounds.iter().any(|bound| match bound {
+            syn::TypeParamBound::Trait(tr) => {
+                let idents: Vec<Ident> = tr
+                    .path
+                    .segments
+                    .iter()
+                    .map(|seg| seg.ident.clone())
+                    .collect();
+                let node = match self.symbol_tree.lookup(&idents) {
+                    Some(node) => node,
+                    None => return false,
+                };
+                let node = node.borrow();
+                matches!(
+                    node.terminal.as_ref().map(|terminal| &terminal.definition),
+                    Some(Definition::Type(Item::Trait(tr))) if has_attribute!(tr, interface_attr)
+                )
+            }
+            syn::TypeParamBound::Lifetime(_) => false,
+        });
+        match is_interface {
+            true => Ok(()),
+            false => Err(Violation::new(
+                ty,
+                "is not an interface, so its proxy can't be generated",
+            )),
+        }
+    }
+}
+
+/// Returns the type of the values that the `RRef`, `RRefArray` or `RRefDeque` `ty` holds.
+fn rrefed_content(ty: &Type) -> Option<&Type> {
+    RRefKind::of_type(ty)?;
+    match ty {
+        Type::Path(path) => match &path.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the name and the type arguments of `path` if it's one of the standard wrappers, i.e.
+/// `Box`, `Option` or `Result`, which are not defined in the IDL.
+fn wrapper(path: &TypePath) -> Option<(&'static str, Vec<Type>)> {
+    let last_segment = path.path.segments.last()?;
+    let name = match last_segment.ident.to_string().as_str() {
+        "Box" => "Box",
+        "Option" => "Option",
+        "Result" => "Result",
+        _ => return None,
+    };
+    let args = match &last_segment.arguments {
+        PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    };
+    Some((name, args))
+}
@@ -0,0 +1,219 @@
+use syn::parse_quote;
+
+use super::validate_interfaces;
+use crate::config::Config;
+use crate::type_resolution::resolve_types;
+
+/// Validates the interfaces in `ast`, after the `rref` module, and returns the error messages.
+fn validate(ast: syn::File) -> Vec<String> {
+    let rref_mod: syn::File = parse_quote! {
+        pub mod rref {
+            pub mod rref {
+                pub struct RRef<T> {
+                    value_pointer: *mut T,
+                }
+            }
+            pub mod rref_array {
+                pub struct RRefArray<T, const N: usize> {
+                    arr: crate::rref::rref::RRef<[Option<crate::rref::rref::RRef<T>>; N]>,
+                }
+            }
+        }
+        pub mod proxy {
+            pub trait Proxy {}
+        }
+    };
+    let mut ast = ast;
+    ast.items.splice(0..0, rref_mod.items);
+    let symbol_tree = resolve_types(&mut ast, &[]).unwrap();
+    match validate_interfaces(&ast, &symbol_tree, &Config::default()) {
+        Ok(()) => vec![],
+        Err(errors) => errors.iter().map(|err| err.message().to_owned()).collect(),
+    }
+}
+
+#[test]
+fn test_valid_interfaces() {
+    let errors = validate(parse_quote! {
+        use crate::rref::rref::RRef;
+        use crate::rref::rref_array::RRefArray;
+
+        pub type RpcResult<T> = Result<T, rpc::RpcError>;
+
+        #[derive(Clone, Copy)]
+        pub struct Header {
+            pub id: u64,
+            pub flags: [u8; 4],
+            pub len: Option<u32>,
+        }
+
+        pub struct Packet {
+            pub header: Header,
+            pub payload: Option<RRef<[u8; 1514]>>,
+            pub next: Option<RRef<Packet>>,
+        }
+
+        pub enum Message {
+            Empty,
+            Data(Header, Option<RRef<Packet>>),
+        }
+
+        pub mod net {
+            use super::{Header, Message, Packet, RpcResult};
+            use crate::rref::rref::RRef;
+            use crate::rref::rref_array::RRefArray;
+
+            #[interface]
+            pub trait Net {
+                fn send(&self, packet: RRef<Packet>) -> RpcResult<()>;
+                fn peek(&self, packet: &RRef<Packet>) -> RpcResult<(usize, Header)>;
+                fn recv(&self, messages: RRefArray<Message, 32>) -> RpcResult<RRefArray<Message, 32>>;
+                fn header(&self, headers: [Header; 2], id: (u64, char)) -> Option<Header>;
+                fn clone_net(&self) -> RpcResult<Box<dyn Net>>;
+                fn forward(&self, to: Box<dyn Net>) -> RpcResult<()>;
+            }
+        }
+    });
+    assert_eq!(errors, Vec::<String>::new());
+}
+
+#[test]
+fn test_pointers_in_arguments() {
+    let errors = validate(parse_quote! {
+        use crate::rref::rref::RRef;
+
+        pub struct Buffer {
+            pub len: usize,
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn borrow(&self, buffer: &Buffer);
+            fn raw(&self, buffer: *mut u8);
+            fn slice(&self) -> RRef<Box<[u8]>>;
+            fn mutate(&self, buffer: &mut RRef<Buffer>);
+        }
+    });
+    assert_eq!(
+        errors,
+        vec![
+            "Argument `buffer` can't cross domains: `&crate::Buffer` is a reference, which is not `SafeCopy`",
+            "Argument `buffer` can't cross domains: `*mut u8` is a raw pointer, which is not `SafeCopy`",
+            "Return value of `slice` can't cross domains: `Box<[u8]>` points to the private heap, which is not `SafeCopy`",
+            "Argument `buffer` can't cross domains: `&mut crate::rref::rref::RRef<crate::Buffer>` is a mutable borrow of an `RRef`, which can only be borrowed immutably",
+        ]
+    );
+}
+
+#[test]
+fn test_nested_fields() {
+    let errors = validate(parse_quote! {
+        use crate::rref::rref::RRef;
+
+        pub struct Inner {
+            pub id: u64,
+            pub callback: fn(u64) -> u64,
+        }
+
+        pub enum Outer {
+            Empty,
+            Full { inner: Inner },
+        }
+
+        pub struct Holder {
+            pub outer: Option<RRef<Outer>>,
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn bar(&self, holder: RRef<Holder>);
+        }
+    });
+    assert_eq!(
+        errors,
+        vec![
+            "Argument `holder` can't cross domains: `fn(u64)->u64` is a function pointer, which is not `SafeCopy`, in field `callback` of `crate::Inner`, in field `Full::inner` of `crate::Outer`, in field `outer` of `crate::Holder`",
+        ]
+    );
+}
+
+#[test]
+fn test_rrefs_in_rrefs() {
+    let errors = validate(parse_quote! {
+        use crate::rref::rref::RRef;
+
+        pub struct Node {
+            pub value: u64,
+            pub next: RRef<Node>,
+        }
+
+        #[derive(Clone, Copy)]
+        pub struct Copied {
+            pub value: u64,
+            pub node: Option<RRef<Node>>,
+        }
+
+        #[interface]
+        pub trait Foo {
+            fn node(&self, node: RRef<Node>);
+            fn copied(&self, copied: Copied);
+            fn nested(&self, nodes: RRef<[Option<RRef<u64>>; 4]>);
+        }
+    });
+    assert_eq!(
+        errors,
+        vec![
+            "Argument `node` can't cross domains: `crate::rref::rref::RRef<crate::Node>` is an `RRef` on the shared heap, which must be in an `Option` or borrowed, in field `next` of `crate::Node`",
+            "Argument `copied` can't cross domains: `crate::rref::rref::RRef<crate::Node>` is an `RRef`, which can only be passed in arguments, return values, and the structs, enums and unions on the shared heap, in field `node` of `crate::Copied`",
+            "Argument `nodes` can't cross domains: `crate::rref::rref::RRef<u64>` is an `RRef`, which can only be passed in arguments, return values, and the structs, enums and unions on the shared heap",
+        ]
+    );
+}
+
+#[test]
+fn test_trait_objects() {
+    let errors = validate(parse_quote! {
+        pub trait Callback {}
+
+        #[interface]
+        pub trait Foo {
+            fn callback(&self) -> Box<dyn Callback>;
+            fn register(&self, callback: &mut dyn Callback);
+            fn forward(&self, to: &dyn Foo);
+        }
+    });
+    assert_eq!(
+        errors,
+        vec![
+            "Return value of `callback` can't cross domains: `Box<dyn crate::Callback>` is not an interface, so its proxy can't be generated",
+            "Argument `callback` can't cross domains: `&mut dyn crate::Callback` is a borrowed trait object, which is not replaced with a proxy; pass a `Box<dyn _>` instead",
+            "Argument `to` can't cross domains: `&dyn crate::Foo` is a borrowed trait object, which is not replaced with a proxy; pass a `Box<dyn _>` instead",
+        ]
+    );
+}
+
+#[test]
+fn test_supertraits() {
+    let errors = validate(parse_quote! {
+        pub mod bdev {
+            pub trait BDev {
+                fn read(&self, block: u32, data: *mut u8);
+            }
+        }
+
+        #[interface]
+        pub trait NvmeBDev: bdev::BDev + Send + Sync {
+            fn flush(&self);
+        }
+
+        #[interface]
+        pub trait Foo: core::fmt::Debug {}
+    });
+    assert_eq!(
+        errors,
+        vec![
+            "Argument `data` can't cross domains: `*mut u8` is a raw pointer, which is not `SafeCopy`",
+            "Unsupported supertrait of `Foo`; expecting a trait in the IDL or one of `Send`, `Sync`, `Unpin`",
+        ]
+    );
+}